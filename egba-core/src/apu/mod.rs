@@ -0,0 +1,750 @@
+use bit::BitIndex;
+
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+mod psg;
+
+use psg::{NoiseChannel, SquareChannel, WaveChannel};
+
+const FIFO_CAPACITY: usize = 32;
+
+pub(crate) const SAMPLE_RATE: u32 = 32768;
+
+const CPU_CLOCK: u32 = 16_777_216;
+
+const CYCLES_PER_SAMPLE: u32 = CPU_CLOCK / SAMPLE_RATE;
+
+const CYCLES_PER_FRAME_STEP: u32 = CPU_CLOCK / 512;
+
+pub(crate) const TAP_COUNT: usize = 6;
+
+pub(crate) const TAP_NAMES: [&str; TAP_COUNT] =
+    ["square1", "square2", "wave", "noise", "fifo_a", "fifo_b"];
+
+#[derive(Clone)]
+struct Fifo {
+    data: [i8; FIFO_CAPACITY],
+    read_pos: usize,
+    write_pos: usize,
+    count: usize,
+}
+
+impl Default for Fifo {
+    fn default() -> Self {
+        Self {
+            data: [0; FIFO_CAPACITY],
+            read_pos: 0,
+            write_pos: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Fifo {
+    fn push(&mut self, sample: i8) {
+        if self.count < FIFO_CAPACITY {
+            self.data[self.write_pos] = sample;
+            self.write_pos = (self.write_pos + 1) % FIFO_CAPACITY;
+            self.count += 1;
+        }
+    }
+
+    fn pop(&mut self) -> i8 {
+        if self.count > 0 {
+            let sample = self.data[self.read_pos];
+            self.read_pos = (self.read_pos + 1) % FIFO_CAPACITY;
+            self.count -= 1;
+            sample
+        } else {
+            0
+        }
+    }
+
+    fn clear(&mut self) {
+        self.read_pos = 0;
+        self.write_pos = 0;
+        self.count = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+}
+
+#[derive(Clone, Default)]
+struct DirectSound {
+    fifo: Fifo,
+    volume_shift: u8,
+    timer_sel: u8,
+    enable_r: bool,
+    enable_l: bool,
+    current_sample: i8,
+}
+
+impl DirectSound {
+    fn output(&self) -> i16 {
+        let sample = self.current_sample as i16;
+        if self.volume_shift == 1 {
+            sample << 2
+        } else {
+            sample << 1
+        }
+    }
+}
+
+pub(crate) struct Apu {
+    ds_a: DirectSound,
+    ds_b: DirectSound,
+
+    square1: SquareChannel,
+    square2: SquareChannel,
+    wave: WaveChannel,
+    noise: NoiseChannel,
+    frame_step: u8,
+    frame_clock: u32,
+
+    soundcnt_l: u16,
+    soundcnt_h: u16,
+    soundcnt_x: u16,
+    soundbias: u16,
+
+    sample_buffer: Vec<(i16, i16)>,
+    tap_buffer: Option<Vec<[(i16, i16); TAP_COUNT]>>,
+    rate_changes: Vec<(usize, u32)>,
+    sample_clock: u32,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self {
+            ds_a: DirectSound::default(),
+            ds_b: DirectSound::default(),
+            square1: SquareChannel::new(true),
+            square2: SquareChannel::new(false),
+            wave: WaveChannel::default(),
+            noise: NoiseChannel::default(),
+            frame_step: 0,
+            frame_clock: 0,
+            soundcnt_l: 0,
+            soundcnt_h: 0,
+            soundcnt_x: 0,
+            soundbias: 0x0200,
+            sample_buffer: Vec::with_capacity(1024),
+            tap_buffer: None,
+            rate_changes: vec![(0, SAMPLE_RATE)],
+            sample_clock: 0,
+        }
+    }
+}
+
+impl Apu {
+    pub(crate) fn on_timer_overflow(&mut self, timer_id: u8) -> u8 {
+        let mut refill = 0u8;
+
+        if self.ds_a.timer_sel == timer_id {
+            self.ds_a.current_sample = self.ds_a.fifo.pop();
+            if self.ds_a.fifo.len() <= 16 {
+                refill |= 1;
+            }
+        }
+
+        if self.ds_b.timer_sel == timer_id {
+            self.ds_b.current_sample = self.ds_b.fifo.pop();
+            if self.ds_b.fifo.len() <= 16 {
+                refill |= 2;
+            }
+        }
+
+        refill
+    }
+
+    pub(crate) fn write_fifo(&mut self, fifo_id: usize, value: u32) {
+        let ds = if fifo_id == 0 {
+            &mut self.ds_a
+        } else {
+            &mut self.ds_b
+        };
+        for shift in [0, 8, 16, 24] {
+            ds.fifo.push(((value >> shift) & 0xFF) as i8);
+        }
+    }
+
+    fn master_enabled(&self) -> bool {
+        self.soundcnt_x.bit(7)
+    }
+
+    pub(crate) fn step(&mut self, cycles: u32) {
+        if !self.master_enabled() {
+            return;
+        }
+
+        let period = self.cycles_per_sample();
+        let mut remaining = cycles;
+        while remaining > 0 {
+            let chunk = remaining.min(period - self.sample_clock % period);
+            self.step_psg(chunk);
+            remaining -= chunk;
+
+            self.sample_clock = (self.sample_clock + chunk) % CYCLES_PER_SAMPLE;
+            if self.sample_clock.is_multiple_of(period) {
+                let (left, right) = self.mix_sample();
+                self.sample_buffer.push((left, right));
+                if let Some(mut taps) = self.tap_buffer.take() {
+                    taps.push(self.tap_sample());
+                    self.tap_buffer = Some(taps);
+                }
+            }
+        }
+    }
+
+    /// Samples are mixed in batches on the 32768 Hz base clock; every register that changes
+    /// the mix syncs the APU first, so higher resolutions need no extra events.
+    pub(crate) fn cycles_to_next_batch(&self) -> Option<u32> {
+        self.master_enabled()
+            .then(|| CYCLES_PER_SAMPLE - self.sample_clock)
+    }
+
+    fn resolution(&self) -> u16 {
+        self.soundbias.bit_range(14..16)
+    }
+
+    pub(crate) fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE << self.resolution()
+    }
+
+    fn cycles_per_sample(&self) -> u32 {
+        CYCLES_PER_SAMPLE >> self.resolution()
+    }
+
+    fn bias_output(&self, level: i16) -> i16 {
+        let bias = (self.soundbias & 0x3FE) as i32;
+        let dropped = (2 << self.resolution()) - 1;
+        let biased = (level as i32 + bias).clamp(0, 0x3FF) & !dropped;
+        ((biased - bias) * 32) as i16
+    }
+
+    fn step_psg(&mut self, cycles: u32) {
+        self.frame_clock += cycles;
+        while self.frame_clock >= CYCLES_PER_FRAME_STEP {
+            self.frame_clock -= CYCLES_PER_FRAME_STEP;
+            self.clock_frame_sequencer();
+        }
+
+        self.square1.step(cycles);
+        self.square2.step(cycles);
+        self.wave.step(cycles);
+        self.noise.step(cycles);
+    }
+
+    fn clock_frame_sequencer(&mut self) {
+        if self.frame_step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.square1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    fn reset_psg(&mut self) {
+        self.square1.reset();
+        self.square2.reset();
+        self.wave.reset();
+        self.noise.reset();
+        self.soundcnt_l = 0;
+        self.frame_step = 0;
+        self.frame_clock = 0;
+    }
+
+    fn psg_status(&self) -> u8 {
+        let mut status = 0u8;
+        status.set_bit(0, self.square1.enabled());
+        status.set_bit(1, self.square2.enabled());
+        status.set_bit(2, self.wave.enabled());
+        status.set_bit(3, self.noise.enabled());
+        status
+    }
+
+    fn psg_outputs(&self) -> [i16; 4] {
+        [
+            self.square1.output(),
+            self.square2.output(),
+            self.wave.output(),
+            self.noise.output(),
+        ]
+    }
+
+    fn psg_shift(&self) -> u16 {
+        match self.soundcnt_h.bit_range(0..2) {
+            0 => 2,
+            1 => 1,
+            _ => 0,
+        }
+    }
+
+    fn mix_psg(&self) -> (i16, i16) {
+        let outputs = self.psg_outputs();
+
+        let mut left: i16 = 0;
+        let mut right: i16 = 0;
+        for (i, sample) in outputs.iter().enumerate() {
+            if self.soundcnt_l.bit(8 + i) {
+                right += sample;
+            }
+            if self.soundcnt_l.bit(12 + i) {
+                left += sample;
+            }
+        }
+
+        left *= self.soundcnt_l.bit_range(4..7) as i16 + 1;
+        right *= self.soundcnt_l.bit_range(0..3) as i16 + 1;
+
+        let shift = self.psg_shift();
+        (left >> shift, right >> shift)
+    }
+
+    fn mix_sample(&self) -> (i16, i16) {
+        let ds_a_scaled = self.ds_a.output();
+        let ds_b_scaled = self.ds_b.output();
+
+        let (mut left, mut right) = self.mix_psg();
+
+        if self.ds_a.enable_l {
+            left += ds_a_scaled;
+        }
+        if self.ds_a.enable_r {
+            right += ds_a_scaled;
+        }
+        if self.ds_b.enable_l {
+            left += ds_b_scaled;
+        }
+        if self.ds_b.enable_r {
+            right += ds_b_scaled;
+        }
+
+        (self.bias_output(left), self.bias_output(right))
+    }
+
+    fn tap_sample(&self) -> [(i16, i16); TAP_COUNT] {
+        let left_volume = self.soundcnt_l.bit_range(4..7) as i16 + 1;
+        let right_volume = self.soundcnt_l.bit_range(0..3) as i16 + 1;
+        let shift = self.psg_shift();
+        let mut taps = [(0, 0); TAP_COUNT];
+        for (i, sample) in self.psg_outputs().into_iter().enumerate() {
+            let left = if self.soundcnt_l.bit(12 + i) { sample * left_volume } else { 0 };
+            let right = if self.soundcnt_l.bit(8 + i) { sample * right_volume } else { 0 };
+            taps[i] = (left >> shift, right >> shift);
+        }
+        for (tap, ds) in taps[4..].iter_mut().zip([&self.ds_a, &self.ds_b]) {
+            let sample = ds.output();
+            *tap = (if ds.enable_l { sample } else { 0 }, if ds.enable_r { sample } else { 0 });
+        }
+        taps.map(|(left, right)| (self.bias_output(left), self.bias_output(right)))
+    }
+
+    pub(crate) fn samples(&self) -> &[(i16, i16)] {
+        &self.sample_buffer
+    }
+
+    pub(crate) fn rate_changes(&self) -> &[(usize, u32)] {
+        &self.rate_changes
+    }
+
+    fn record_rate(&mut self) {
+        let rate = self.sample_rate();
+        match self.rate_changes.last_mut() {
+            Some(last) if last.1 == rate => {}
+            Some(last) if last.0 == self.sample_buffer.len() => last.1 = rate,
+            _ => self.rate_changes.push((self.sample_buffer.len(), rate)),
+        }
+    }
+
+    pub(crate) fn set_taps(&mut self, enabled: bool) {
+        self.tap_buffer = enabled.then(Vec::new);
+    }
+
+    pub(crate) fn taps(&self) -> &[[(i16, i16); TAP_COUNT]] {
+        self.tap_buffer.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn clear_samples(&mut self) {
+        self.sample_buffer.clear();
+        self.rate_changes.clear();
+        self.rate_changes.push((0, self.sample_rate()));
+        if let Some(taps) = &mut self.tap_buffer {
+            taps.clear();
+        }
+    }
+
+    fn update_soundcnt_h(&mut self) {
+        let h = self.soundcnt_h;
+        self.ds_a.volume_shift = ((h >> 2) & 1) as u8;
+        self.ds_b.volume_shift = ((h >> 3) & 1) as u8;
+        self.ds_a.enable_r = (h >> 8) & 1 != 0;
+        self.ds_a.enable_l = (h >> 9) & 1 != 0;
+        self.ds_a.timer_sel = ((h >> 10) & 1) as u8;
+        self.ds_b.enable_r = (h >> 12) & 1 != 0;
+        self.ds_b.enable_l = (h >> 13) & 1 != 0;
+        self.ds_b.timer_sel = ((h >> 14) & 1) as u8;
+    }
+}
+
+impl Bus for Apu {
+    fn read_byte(&self, addr: u32) -> u8 {
+        match addr {
+            0x060..=0x065 => self.square1.read_byte(addr - 0x060),
+            0x068..=0x069 => self.square2.read_byte(addr - 0x066),
+            0x06C..=0x06D => self.square2.read_byte(addr - 0x068),
+            0x070..=0x075 => self.wave.read_byte(addr - 0x070),
+            0x078..=0x07D => self.noise.read_byte(addr - 0x078),
+            0x080 => (self.soundcnt_l & 0x77) as u8,
+            0x081 => (self.soundcnt_l >> 8) as u8,
+            0x082 => self.soundcnt_h as u8,
+            0x083 => (self.soundcnt_h >> 8) as u8,
+            0x084 => (self.soundcnt_x & 0x80) as u8 | self.psg_status(),
+            0x088 => self.soundbias as u8,
+            0x089 => (self.soundbias >> 8) as u8,
+            0x090..=0x09F => self.wave.read_ram(addr - 0x090),
+            _ => 0,
+        }
+    }
+
+    fn write_byte(&mut self, addr: u32, value: u8) {
+        if (0x060..=0x081).contains(&addr) && !self.master_enabled() {
+            return;
+        }
+
+        match addr {
+            0x060..=0x065 => self.square1.write_byte(addr - 0x060, value),
+            0x068..=0x069 => self.square2.write_byte(addr - 0x066, value),
+            0x06C..=0x06D => self.square2.write_byte(addr - 0x068, value),
+            0x070..=0x075 => self.wave.write_byte(addr - 0x070, value),
+            0x078..=0x07D => self.noise.write_byte(addr - 0x078, value),
+            0x080 => {
+                self.soundcnt_l = (self.soundcnt_l & 0xFF00) | value as u16;
+            }
+            0x081 => {
+                self.soundcnt_l = (self.soundcnt_l & 0x00FF) | ((value as u16) << 8);
+            }
+            0x082 => {
+                self.soundcnt_h = (self.soundcnt_h & 0xFF00) | value as u16;
+                self.update_soundcnt_h();
+            }
+            0x083 => {
+                self.soundcnt_h = (self.soundcnt_h & 0x00FF) | ((value as u16) << 8);
+                if (self.soundcnt_h >> 11) & 1 != 0 {
+                    self.ds_a.fifo.clear();
+                    self.soundcnt_h &= !(1 << 11);
+                }
+                if (self.soundcnt_h >> 15) & 1 != 0 {
+                    self.ds_b.fifo.clear();
+                    self.soundcnt_h &= !(1 << 15);
+                }
+                self.update_soundcnt_h();
+            }
+            0x084 => {
+                let was_enabled = self.master_enabled();
+                self.soundcnt_x = (self.soundcnt_x & !0x80) | (value as u16 & 0x80);
+                if was_enabled != self.master_enabled() {
+                    self.reset_psg();
+                }
+                if !self.master_enabled() {
+                    self.ds_a.fifo.clear();
+                    self.ds_b.fifo.clear();
+                    self.ds_a.current_sample = 0;
+                    self.ds_b.current_sample = 0;
+                }
+            }
+            0x088 => {
+                self.soundbias = (self.soundbias & 0xFF00) | value as u16;
+            }
+            0x089 => {
+                self.soundbias = (self.soundbias & 0x00FF) | ((value as u16) << 8);
+                self.record_rate();
+            }
+            0x090..=0x09F => self.wave.write_ram(addr - 0x090, value),
+            _ => {}
+        }
+    }
+}
+
+impl Snapshot for Fifo {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.data.save_state(w);
+        self.read_pos.save_state(w);
+        self.write_pos.save_state(w);
+        self.count.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.data.load_state(r)?;
+        self.read_pos.load_state(r)?;
+        self.write_pos.load_state(r)?;
+        self.count.load_state(r)?;
+        if self.read_pos >= FIFO_CAPACITY
+            || self.write_pos >= FIFO_CAPACITY
+            || self.count > FIFO_CAPACITY
+        {
+            return Err(StateError::Corrupt("sound FIFO"));
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot for Apu {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.ds_a.fifo.save_state(w);
+        self.ds_a.current_sample.save_state(w);
+        self.ds_b.fifo.save_state(w);
+        self.ds_b.current_sample.save_state(w);
+        self.square1.save_state(w);
+        self.square2.save_state(w);
+        self.wave.save_state(w);
+        self.noise.save_state(w);
+        self.frame_step.save_state(w);
+        self.frame_clock.save_state(w);
+        self.soundcnt_l.save_state(w);
+        self.soundcnt_h.save_state(w);
+        self.soundcnt_x.save_state(w);
+        self.soundbias.save_state(w);
+        self.sample_clock.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ds_a.fifo.load_state(r)?;
+        self.ds_a.current_sample.load_state(r)?;
+        self.ds_b.fifo.load_state(r)?;
+        self.ds_b.current_sample.load_state(r)?;
+        self.square1.load_state(r)?;
+        self.square2.load_state(r)?;
+        self.wave.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.frame_step.load_state(r)?;
+        self.frame_clock.load_state(r)?;
+        self.soundcnt_l.load_state(r)?;
+        self.soundcnt_h.load_state(r)?;
+        self.soundcnt_x.load_state(r)?;
+        self.soundbias.load_state(r)?;
+        self.sample_clock.load_state(r)?;
+        if self.frame_step > 7
+            || self.frame_clock >= CYCLES_PER_FRAME_STEP
+            || self.sample_clock >= CYCLES_PER_SAMPLE
+        {
+            return Err(StateError::Corrupt("APU sequencer"));
+        }
+        self.update_soundcnt_h();
+        self.clear_samples();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_apu(soundcnt_h: u16) -> Apu {
+        let mut apu = Apu::default();
+        apu.write_byte(0x084, 0x80);
+        apu.write_byte(0x082, soundcnt_h as u8);
+        apu.write_byte(0x083, (soundcnt_h >> 8) as u8);
+        apu
+    }
+
+    #[test]
+    fn apu_volume_shift_zero_halves_dsa() {
+        let h = (1u16 << 8) | (1u16 << 9);
+        let mut apu = setup_apu(h);
+        apu.ds_a.current_sample = 100;
+        let (l, r) = apu.mix_sample();
+        assert_eq!(l, 200 * 32, "left at 50% of the 10-bit range then i16-amplified");
+        assert_eq!(r, 200 * 32, "right at 50% of the 10-bit range then i16-amplified");
+    }
+
+    #[test]
+    fn apu_volume_shift_one_keeps_dsa() {
+        let h = (1u16 << 2) | (1u16 << 8) | (1u16 << 9);
+        let mut apu = setup_apu(h);
+        apu.ds_a.current_sample = 100;
+        let (l, r) = apu.mix_sample();
+        assert_eq!(l, 400 * 32, "left at 100% of the 10-bit range then i16-amplified");
+        assert_eq!(r, 400 * 32, "right at 100% of the 10-bit range then i16-amplified");
+    }
+
+    #[test]
+    fn soundbias_selects_rate_resolution_and_clipping() {
+        let mut apu = setup_apu((1 << 2) | (1 << 3) | (1 << 8) | (1 << 9) | (3 << 12));
+        apu.ds_a.current_sample = 0x7F;
+        apu.step(CYCLES_PER_SAMPLE);
+        assert_eq!(apu.sample_rate(), 32768);
+        assert_eq!(apu.samples(), [(0x1FC * 32, 0x1FC * 32)], "full-scale FIFO fits 10 bits");
+        assert_eq!(apu.bias_output(15), 14 * 32, "9-bit drops the low bit");
+
+        apu.clear_samples();
+        apu.ds_b.current_sample = 0x7F;
+        apu.step(CYCLES_PER_SAMPLE);
+        assert_eq!(apu.samples(), [(0x1FE * 32, 0x1FE * 32)], "two FIFOs clip at 0x3FF");
+
+        apu.clear_samples();
+        apu.ds_b.current_sample = 0;
+        apu.write_byte(0x089, 0xC2);
+        apu.step(CYCLES_PER_SAMPLE);
+        assert_eq!(apu.sample_rate(), 262144);
+        assert_eq!(apu.samples(), [(0x1F0 * 32, 0x1F0 * 32); 8], "6-bit at 8x the rate");
+
+        apu.clear_samples();
+        apu.write_byte(0x088, 0xC0);
+        apu.write_byte(0x089, 0xC3);
+        apu.step(CYCLES_PER_SAMPLE >> 3);
+        assert_eq!(apu.samples(), [(0x30 * 32, 0x30 * 32)], "clipped at 0x3FF above a high bias");
+    }
+
+    #[test]
+    fn apu_timer_overflow_pops_fifo() {
+        let mut apu = setup_apu((1u16 << 8) | (1u16 << 9));
+        apu.write_fifo(0, 0x04030201);
+        let _ = apu.on_timer_overflow(0);
+        assert_eq!(apu.ds_a.current_sample, 0x01, "first byte popped on T0 overflow");
+        assert_eq!(apu.ds_a.fifo.len(), 3, "three samples remain");
+    }
+
+    fn trigger_square1(apu: &mut Apu, length: u8, length_enable: bool) {
+        apu.write_byte(0x062, length);
+        apu.write_byte(0x063, 0xF0);
+        apu.write_byte(0x064, 0xFF);
+        apu.write_byte(0x065, 0x87 | ((length_enable as u8) << 6));
+    }
+
+    #[test]
+    fn soundcnt_x_reports_psg_channel_status() {
+        let mut apu = setup_apu(0);
+        assert_eq!(apu.read_byte(0x084), 0x80, "no channel running");
+        trigger_square1(&mut apu, 0, false);
+        assert_eq!(apu.read_byte(0x084), 0x81, "sound 1 on flag");
+    }
+
+    #[test]
+    fn square2_registers_map_to_duty_envelope_and_frequency() {
+        let mut apu = setup_apu(0);
+        apu.write_byte(0x06A, 0xFF);
+        apu.write_byte(0x06B, 0xFF);
+        apu.write_byte(0x068, 0x80);
+        apu.write_byte(0x069, 0xF0);
+        apu.write_byte(0x06C, 0x00);
+        apu.write_byte(0x06D, 0x87);
+        assert_eq!(apu.read_byte(0x084), 0x82, "sound 2 on flag");
+        assert_eq!(apu.read_byte(0x068), 0x80, "duty");
+        assert_eq!(apu.read_byte(0x069), 0xF0, "envelope");
+        assert_eq!(apu.read_byte(0x06D), 0x00, "length disabled");
+    }
+
+    #[test]
+    fn frame_sequencer_clocks_length_at_256hz() {
+        let mut apu = setup_apu(0);
+        trigger_square1(&mut apu, 63, true);
+        apu.step(CYCLES_PER_FRAME_STEP - 1);
+        assert_eq!(apu.read_byte(0x084) & 1, 1, "length not clocked before first 512 Hz step");
+        apu.step(1);
+        assert_eq!(apu.read_byte(0x084) & 1, 0, "step 0 clocks length 1 -> 0");
+    }
+
+    #[test]
+    fn master_disable_resets_psg_and_blocks_writes() {
+        let mut apu = setup_apu(0);
+        apu.write_byte(0x080, 0x77);
+        trigger_square1(&mut apu, 0, false);
+        apu.write_byte(0x090, 0x5A);
+
+        apu.write_byte(0x084, 0x00);
+        assert_eq!(apu.read_byte(0x080), 0, "SOUNDCNT_L cleared");
+        assert_eq!(apu.read_byte(0x063), 0, "SOUND1CNT_H cleared");
+        assert_eq!(apu.read_byte(0x084), 0, "all channels off");
+
+        apu.write_byte(0x063, 0xF0);
+        assert_eq!(apu.read_byte(0x063), 0, "PSG registers read-only while master is off");
+        assert_eq!(apu.read_byte(0x090), 0x5A, "wave RAM survives master disable");
+    }
+
+    #[test]
+    fn psg_mix_applies_master_volume_panning_and_ratio() {
+        let cases: [(u16, u16, i16, i16, &str); 4] = [
+            (0x1177, 0x0002, 120, 120, "ch1 both sides, vol 7, 100%"),
+            (0x1077, 0x0002, 120, 0, "ch1 left only"),
+            (0x1170, 0x0002, 120, 15, "right master volume 0 = 1/8"),
+            (0x1177, 0x0000, 30, 30, "25% ratio"),
+        ];
+        for (cnt_l, cnt_h, want_l, want_r, label) in cases {
+            let mut apu = setup_apu(cnt_h);
+            apu.write_byte(0x080, cnt_l as u8);
+            apu.write_byte(0x081, (cnt_l >> 8) as u8);
+            trigger_square1(&mut apu, 0xC0, false);
+            apu.step(16);
+            let (l, r) = apu.mix_psg();
+            assert_eq!((l, r), (want_l, want_r), "{label}");
+        }
+    }
+
+    #[test]
+    fn taps_split_the_mix_by_source() {
+        let mut apu = setup_apu((1 << 2) | (1 << 9) | (1 << 12) | (1 << 13) | 0x0002);
+        apu.write_byte(0x080, 0x77);
+        apu.write_byte(0x081, 0x11);
+        trigger_square1(&mut apu, 0xC0, false);
+        apu.write_fifo(0, 0x0000_0040);
+        apu.write_fifo(1, 0x0000_00E0);
+        let _ = apu.on_timer_overflow(0);
+        let _ = apu.on_timer_overflow(1);
+
+        apu.step(CYCLES_PER_SAMPLE);
+        assert!(apu.taps().is_empty(), "taps are off by default");
+        apu.clear_samples();
+        apu.set_taps(true);
+        apu.step(CYCLES_PER_SAMPLE * 4);
+        assert_eq!(apu.taps().len(), apu.samples().len());
+
+        for (mixed, taps) in apu.samples().iter().zip(apu.taps()) {
+            let square = taps[0];
+            assert_eq!(square.0.abs(), 120 * 32, "square 1 at full master volume");
+            assert_eq!(taps[1..4], [(0, 0); 3]);
+            assert_eq!(taps[4], (0x100 * 32, 0), "FIFO A panned left at 100%");
+            assert_eq!(taps[5], (-0x40 * 32, -0x40 * 32), "FIFO B on both sides at 50%");
+            let sum = taps.iter().fold((0, 0), |acc, tap| (acc.0 + tap.0, acc.1 + tap.1));
+            assert_eq!(*mixed, sum);
+        }
+        apu.clear_samples();
+        assert!(apu.taps().is_empty());
+
+        apu.write_byte(0x089, 0xC2);
+        apu.step(CYCLES_PER_SAMPLE >> 3);
+        let taps = apu.taps()[0];
+        assert_eq!(taps[0].0.abs(), 112 * 32, "taps share the 6-bit resolution of the mix");
+        assert_eq!(taps[5], (-0x40 * 32, -0x40 * 32));
+    }
+
+    #[test]
+    fn rate_changes_are_recorded_with_the_sample_index() {
+        let mut apu = setup_apu(0);
+        apu.step(CYCLES_PER_SAMPLE * 2);
+        apu.write_byte(0x089, 0x42);
+        apu.step(CYCLES_PER_SAMPLE);
+        apu.write_byte(0x089, 0x82);
+        apu.write_byte(0x089, 0xC2);
+        assert_eq!(apu.cycles_to_next_batch(), Some(CYCLES_PER_SAMPLE));
+        apu.step(CYCLES_PER_SAMPLE);
+        assert_eq!(apu.samples().len(), 2 + 2 + 8);
+        assert_eq!(apu.rate_changes(), [(0, 32768), (2, 65536), (4, 262144)]);
+
+        apu.clear_samples();
+        assert_eq!(apu.rate_changes(), [(0, 262144)]);
+    }
+}
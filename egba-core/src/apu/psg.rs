@@ -0,0 +1,895 @@
+use bit::BitIndex;
+
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+const SQUARE_LENGTH_MAX: u16 = 64;
+const WAVE_LENGTH_MAX: u16 = 256;
+const NOISE_LENGTH_MAX: u16 = 64;
+
+const WAVE_BANK_SIZE: usize = 16;
+
+#[derive(Clone, Default)]
+struct LengthCounter {
+    counter: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    fn load(&mut self, max: u16, value: u16) {
+        self.counter = max - value;
+    }
+
+    fn trigger(&mut self, max: u16) {
+        if self.counter == 0 {
+            self.counter = max;
+        }
+    }
+
+    fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+#[derive(Clone, Default)]
+struct Envelope {
+    initial: u8,
+    increase: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn write(&mut self, value: u8) {
+        self.period = value.bit_range(0..3);
+        self.increase = value.bit(3);
+        self.initial = value.bit_range(4..8);
+    }
+
+    fn dac_enabled(&self) -> bool {
+        self.initial != 0 || self.increase
+    }
+
+    fn trigger(&mut self) {
+        self.volume = self.initial;
+        self.timer = self.period;
+    }
+
+    fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(super) struct SquareChannel {
+    has_sweep: bool,
+
+    sweep_shift: u8,
+    sweep_decrease: bool,
+    sweep_period: u8,
+    sweep_timer: u8,
+    sweep_shadow: u16,
+    sweep_enabled: bool,
+
+    duty: u8,
+    duty_pos: u8,
+    frequency: u16,
+    timer: u32,
+
+    length: LengthCounter,
+    envelope: Envelope,
+    enabled: bool,
+}
+
+impl SquareChannel {
+    pub(super) fn new(has_sweep: bool) -> Self {
+        Self {
+            has_sweep,
+            ..Self::default()
+        }
+    }
+
+    pub(super) fn reset(&mut self) {
+        *self = Self::new(self.has_sweep);
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 16
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger(SQUARE_LENGTH_MAX);
+        self.envelope.trigger();
+        self.timer = self.period();
+
+        if self.has_sweep {
+            self.sweep_shadow = self.frequency;
+            self.sweep_timer = if self.sweep_period == 0 { 8 } else { self.sweep_period };
+            self.sweep_enabled = self.sweep_period != 0 || self.sweep_shift != 0;
+            if self.sweep_shift != 0 {
+                self.sweep_calculate();
+            }
+        }
+    }
+
+    fn sweep_calculate(&mut self) -> u16 {
+        let delta = self.sweep_shadow >> self.sweep_shift;
+        let next = if self.sweep_decrease {
+            self.sweep_shadow.wrapping_sub(delta)
+        } else {
+            self.sweep_shadow + delta
+        };
+        if next > 2047 {
+            self.enabled = false;
+        }
+        next
+    }
+
+    pub(super) fn clock_sweep(&mut self) {
+        if !self.has_sweep {
+            return;
+        }
+        if self.sweep_timer > 0 {
+            self.sweep_timer -= 1;
+        }
+        if self.sweep_timer != 0 {
+            return;
+        }
+        self.sweep_timer = if self.sweep_period == 0 { 8 } else { self.sweep_period };
+        if self.sweep_enabled && self.sweep_period != 0 {
+            let next = self.sweep_calculate();
+            if next <= 2047 && self.sweep_shift != 0 {
+                self.frequency = next;
+                self.sweep_shadow = next;
+                self.sweep_calculate();
+            }
+        }
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub(super) fn step(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        let mut remaining = cycles;
+        while remaining >= self.timer {
+            remaining -= self.timer;
+            self.timer = self.period();
+            self.duty_pos = (self.duty_pos + 1) & 7;
+        }
+        self.timer -= remaining;
+    }
+
+    pub(super) fn output(&self) -> i16 {
+        if !self.enabled {
+            return 0;
+        }
+        let volume = self.envelope.volume as i16;
+        if (DUTY_PATTERNS[self.duty as usize] >> (7 - self.duty_pos)) & 1 != 0 {
+            volume
+        } else {
+            -volume
+        }
+    }
+}
+
+impl Bus for SquareChannel {
+    fn read_byte(&self, addr: u32) -> u8 {
+        match addr {
+            0x0 => {
+                let mut value = self.sweep_shift;
+                value.set_bit(3, self.sweep_decrease);
+                value.set_bit_range(4..7, self.sweep_period);
+                value
+            }
+            0x2 => self.duty << 6,
+            0x3 => {
+                let mut value = self.envelope.period;
+                value.set_bit(3, self.envelope.increase);
+                value.set_bit_range(4..8, self.envelope.initial);
+                value
+            }
+            0x5 => (self.length.enabled as u8) << 6,
+            _ => 0,
+        }
+    }
+
+    fn write_byte(&mut self, addr: u32, value: u8) {
+        match addr {
+            0x0 => {
+                self.sweep_shift = value.bit_range(0..3);
+                self.sweep_decrease = value.bit(3);
+                self.sweep_period = value.bit_range(4..7);
+            }
+            0x2 => {
+                self.length.load(SQUARE_LENGTH_MAX, value.bit_range(0..6) as u16);
+                self.duty = value.bit_range(6..8);
+            }
+            0x3 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            0x4 => {
+                self.frequency.set_bit_range(0..8, value as u16);
+            }
+            0x5 => {
+                self.frequency.set_bit_range(8..11, value.bit_range(0..3) as u16);
+                self.length.enabled = value.bit(6);
+                if value.bit(7) {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(super) struct WaveChannel {
+    ram: [u8; WAVE_BANK_SIZE * 2],
+    two_banks: bool,
+    bank: usize,
+    playing: bool,
+
+    volume_code: u8,
+    force_75: bool,
+    rate: u16,
+    timer: u32,
+    position: usize,
+
+    length: LengthCounter,
+    enabled: bool,
+}
+
+impl WaveChannel {
+    pub(super) fn reset(&mut self) {
+        let ram = self.ram;
+        *self = Self::default();
+        self.ram = ram;
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.rate as u32) * 8
+    }
+
+    fn sample_count(&self) -> usize {
+        if self.two_banks {
+            WAVE_BANK_SIZE * 4
+        } else {
+            WAVE_BANK_SIZE * 2
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.playing;
+        self.length.trigger(WAVE_LENGTH_MAX);
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn step(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        let mut remaining = cycles;
+        while remaining >= self.timer {
+            remaining -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % self.sample_count();
+        }
+        self.timer -= remaining;
+    }
+
+    fn current_sample(&self) -> u8 {
+        let bank = if self.position >= WAVE_BANK_SIZE * 2 {
+            self.bank ^ 1
+        } else {
+            self.bank
+        };
+        let index = self.position % (WAVE_BANK_SIZE * 2);
+        let byte = self.ram[bank * WAVE_BANK_SIZE + index / 2];
+        if index & 1 == 0 {
+            byte >> 4
+        } else {
+            byte & 0xF
+        }
+    }
+
+    pub(super) fn output(&self) -> i16 {
+        if !self.enabled {
+            return 0;
+        }
+        let level = self.current_sample() as i16 * 2 - 15;
+        if self.force_75 {
+            return level * 3 / 4;
+        }
+        match self.volume_code {
+            0 => 0,
+            1 => level,
+            2 => level >> 1,
+            _ => level >> 2,
+        }
+    }
+
+    pub(super) fn read_ram(&self, offset: u32) -> u8 {
+        self.ram[(self.bank ^ 1) * WAVE_BANK_SIZE + offset as usize]
+    }
+
+    pub(super) fn write_ram(&mut self, offset: u32, value: u8) {
+        self.ram[(self.bank ^ 1) * WAVE_BANK_SIZE + offset as usize] = value;
+    }
+}
+
+impl Bus for WaveChannel {
+    fn read_byte(&self, addr: u32) -> u8 {
+        match addr {
+            0x0 => {
+                let mut value = 0u8;
+                value.set_bit(5, self.two_banks);
+                value.set_bit(6, self.bank != 0);
+                value.set_bit(7, self.playing);
+                value
+            }
+            0x3 => {
+                let mut value = self.volume_code << 5;
+                value.set_bit(7, self.force_75);
+                value
+            }
+            0x5 => (self.length.enabled as u8) << 6,
+            _ => 0,
+        }
+    }
+
+    fn write_byte(&mut self, addr: u32, value: u8) {
+        match addr {
+            0x0 => {
+                self.two_banks = value.bit(5);
+                self.bank = value.bit(6) as usize;
+                self.playing = value.bit(7);
+                if !self.playing {
+                    self.enabled = false;
+                }
+            }
+            0x2 => {
+                self.length.load(WAVE_LENGTH_MAX, value as u16);
+            }
+            0x3 => {
+                self.volume_code = value.bit_range(5..7);
+                self.force_75 = value.bit(7);
+            }
+            0x4 => {
+                self.rate.set_bit_range(0..8, value as u16);
+            }
+            0x5 => {
+                self.rate.set_bit_range(8..11, value.bit_range(0..3) as u16);
+                self.length.enabled = value.bit(6);
+                if value.bit(7) {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(super) struct NoiseChannel {
+    ratio: u8,
+    narrow: bool,
+    shift: u8,
+    lfsr: u16,
+    high: bool,
+    timer: u32,
+
+    length: LengthCounter,
+    envelope: Envelope,
+    enabled: bool,
+}
+
+impl NoiseChannel {
+    pub(super) fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn period(&self) -> u32 {
+        let divisor = if self.ratio == 0 { 32 } else { 64 * self.ratio as u32 };
+        divisor << self.shift
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger(NOISE_LENGTH_MAX);
+        self.envelope.trigger();
+        self.timer = self.period();
+        self.lfsr = if self.narrow { 0x40 } else { 0x4000 };
+        self.high = false;
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    fn shift_lfsr(&mut self) {
+        let carry = self.lfsr & 1 != 0;
+        self.lfsr >>= 1;
+        self.high = carry;
+        if carry {
+            self.lfsr ^= if self.narrow { 0x60 } else { 0x6000 };
+        }
+    }
+
+    pub(super) fn step(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        let mut remaining = cycles;
+        while remaining >= self.timer {
+            remaining -= self.timer;
+            self.timer = self.period();
+            self.shift_lfsr();
+        }
+        self.timer -= remaining;
+    }
+
+    pub(super) fn output(&self) -> i16 {
+        if !self.enabled {
+            return 0;
+        }
+        let volume = self.envelope.volume as i16;
+        if self.high {
+            volume
+        } else {
+            -volume
+        }
+    }
+}
+
+impl Bus for NoiseChannel {
+    fn read_byte(&self, addr: u32) -> u8 {
+        match addr {
+            0x1 => {
+                let mut value = self.envelope.period;
+                value.set_bit(3, self.envelope.increase);
+                value.set_bit_range(4..8, self.envelope.initial);
+                value
+            }
+            0x4 => {
+                let mut value = self.ratio;
+                value.set_bit(3, self.narrow);
+                value.set_bit_range(4..8, self.shift);
+                value
+            }
+            0x5 => (self.length.enabled as u8) << 6,
+            _ => 0,
+        }
+    }
+
+    fn write_byte(&mut self, addr: u32, value: u8) {
+        match addr {
+            0x0 => {
+                self.length.load(NOISE_LENGTH_MAX, value.bit_range(0..6) as u16);
+            }
+            0x1 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            0x4 => {
+                self.ratio = value.bit_range(0..3);
+                self.narrow = value.bit(3);
+                self.shift = value.bit_range(4..8);
+            }
+            0x5 => {
+                self.length.enabled = value.bit(6);
+                if value.bit(7) {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Snapshot for LengthCounter {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.counter.save_state(w);
+        self.enabled.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.counter.load_state(r)?;
+        self.enabled.load_state(r)
+    }
+}
+
+impl Snapshot for Envelope {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.initial.save_state(w);
+        self.increase.save_state(w);
+        self.period.save_state(w);
+        self.volume.save_state(w);
+        self.timer.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.initial.load_state(r)?;
+        self.increase.load_state(r)?;
+        self.period.load_state(r)?;
+        self.volume.load_state(r)?;
+        self.timer.load_state(r)?;
+        if self.initial > 15 || self.period > 7 || self.volume > 15 {
+            return Err(StateError::Corrupt("envelope"));
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot for SquareChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.sweep_shift.save_state(w);
+        self.sweep_decrease.save_state(w);
+        self.sweep_period.save_state(w);
+        self.sweep_timer.save_state(w);
+        self.sweep_shadow.save_state(w);
+        self.sweep_enabled.save_state(w);
+        self.duty.save_state(w);
+        self.duty_pos.save_state(w);
+        self.frequency.save_state(w);
+        self.timer.save_state(w);
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        self.enabled.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.sweep_shift.load_state(r)?;
+        self.sweep_decrease.load_state(r)?;
+        self.sweep_period.load_state(r)?;
+        self.sweep_timer.load_state(r)?;
+        self.sweep_shadow.load_state(r)?;
+        self.sweep_enabled.load_state(r)?;
+        self.duty.load_state(r)?;
+        self.duty_pos.load_state(r)?;
+        self.frequency.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        self.enabled.load_state(r)?;
+        if self.duty > 3
+            || self.duty_pos > 7
+            || self.frequency > 0x7FF
+            || self.sweep_shift > 7
+            || self.sweep_period > 7
+            || (self.enabled && self.timer == 0)
+        {
+            return Err(StateError::Corrupt("square channel"));
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot for WaveChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.ram.save_state(w);
+        self.two_banks.save_state(w);
+        self.bank.save_state(w);
+        self.playing.save_state(w);
+        self.volume_code.save_state(w);
+        self.force_75.save_state(w);
+        self.rate.save_state(w);
+        self.timer.save_state(w);
+        self.position.save_state(w);
+        self.length.save_state(w);
+        self.enabled.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ram.load_state(r)?;
+        self.two_banks.load_state(r)?;
+        self.bank.load_state(r)?;
+        self.playing.load_state(r)?;
+        self.volume_code.load_state(r)?;
+        self.force_75.load_state(r)?;
+        self.rate.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.position.load_state(r)?;
+        self.length.load_state(r)?;
+        self.enabled.load_state(r)?;
+        if self.bank > 1
+            || self.position >= WAVE_BANK_SIZE * 4
+            || self.rate > 0x7FF
+            || self.volume_code > 3
+            || (self.enabled && self.timer == 0)
+        {
+            return Err(StateError::Corrupt("wave channel"));
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot for NoiseChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.ratio.save_state(w);
+        self.narrow.save_state(w);
+        self.shift.save_state(w);
+        self.lfsr.save_state(w);
+        self.high.save_state(w);
+        self.timer.save_state(w);
+        self.length.save_state(w);
+        self.envelope.save_state(w);
+        self.enabled.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ratio.load_state(r)?;
+        self.narrow.load_state(r)?;
+        self.shift.load_state(r)?;
+        self.lfsr.load_state(r)?;
+        self.high.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.length.load_state(r)?;
+        self.envelope.load_state(r)?;
+        self.enabled.load_state(r)?;
+        if self.ratio > 7 || self.shift > 15 || (self.enabled && self.timer == 0) {
+            return Err(StateError::Corrupt("noise channel"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered_square(duty: u8, volume: u8, frequency: u16) -> SquareChannel {
+        let mut ch = SquareChannel::new(false);
+        ch.write_byte(0x2, duty << 6);
+        ch.write_byte(0x3, volume << 4);
+        ch.write_byte(0x4, frequency as u8);
+        ch.write_byte(0x5, 0x80 | (frequency >> 8) as u8);
+        ch
+    }
+
+    #[test]
+    fn square_duty_pattern_follows_timer_period() {
+        let mut ch = triggered_square(2, 15, 2047);
+        let mut pattern = 0u8;
+        for _ in 0..8 {
+            ch.step(16);
+            pattern = (pattern << 1) | (ch.output() > 0) as u8;
+        }
+        assert_eq!(pattern.count_ones(), 4, "50% duty must be high for 4 of 8 steps");
+    }
+
+    #[test]
+    fn square_trigger_with_dac_off_stays_disabled() {
+        let ch = triggered_square(2, 0, 1024);
+        assert!(!ch.enabled(), "volume 0 + decrease = DAC off");
+        assert_eq!(ch.output(), 0);
+    }
+
+    #[test]
+    fn square_length_expiry_disables_channel() {
+        let mut ch = SquareChannel::new(false);
+        ch.write_byte(0x2, 62);
+        ch.write_byte(0x3, 0xF0);
+        ch.write_byte(0x5, 0xC0);
+        ch.clock_length();
+        assert!(ch.enabled(), "one length tick left");
+        ch.clock_length();
+        assert!(!ch.enabled(), "64-62 = 2 ticks exhausts length");
+    }
+
+    #[test]
+    fn envelope_decrease_steps_volume_down() {
+        let mut ch = SquareChannel::new(false);
+        ch.write_byte(0x3, 0x21);
+        ch.write_byte(0x5, 0x80);
+        assert_eq!(ch.envelope.volume, 2);
+        ch.clock_envelope();
+        assert_eq!(ch.envelope.volume, 1);
+        ch.clock_envelope();
+        ch.clock_envelope();
+        assert_eq!(ch.envelope.volume, 0, "envelope saturates at 0");
+    }
+
+    #[test]
+    fn sweep_overflow_disables_channel() {
+        let mut ch = SquareChannel::new(true);
+        ch.write_byte(0x0, 0x11);
+        ch.write_byte(0x3, 0xF0);
+        ch.write_byte(0x4, 0xFF);
+        ch.write_byte(0x5, 0x80 | 0x04);
+        assert!(ch.enabled(), "0x4FF + 0x27F fits in 11 bits");
+        ch.clock_sweep();
+        assert!(!ch.enabled(), "second sweep step overflows past 2047");
+    }
+
+    #[test]
+    fn sweep_increase_updates_frequency() {
+        let mut ch = SquareChannel::new(true);
+        ch.write_byte(0x0, 0x12);
+        ch.write_byte(0x3, 0xF0);
+        ch.write_byte(0x4, 0x00);
+        ch.write_byte(0x5, 0x80 | 0x01);
+        ch.clock_sweep();
+        assert_eq!(ch.frequency, 0x100 + 0x40, "f + f>>2");
+    }
+
+    #[test]
+    fn wave_cpu_access_targets_non_playing_bank() {
+        let mut ch = WaveChannel::default();
+        ch.write_byte(0x0, 0x00);
+        ch.write_ram(0, 0xAB);
+        assert_eq!(ch.ram[WAVE_BANK_SIZE], 0xAB, "bank 0 playing -> CPU writes bank 1");
+        ch.write_byte(0x0, 0x40);
+        ch.write_ram(0, 0xCD);
+        assert_eq!(ch.ram[0], 0xCD, "bank 1 playing -> CPU writes bank 0");
+        assert_eq!(ch.read_ram(0), 0xCD);
+    }
+
+    #[test]
+    fn wave_two_bank_mode_plays_64_samples() {
+        let mut ch = WaveChannel::default();
+        ch.ram[0] = 0xF0;
+        ch.ram[WAVE_BANK_SIZE] = 0x0F;
+        ch.write_byte(0x0, 0xA0);
+        ch.write_byte(0x3, 0x20);
+        ch.write_byte(0x4, 0xFF);
+        ch.write_byte(0x5, 0x87);
+        assert_eq!(ch.output(), 15, "first nibble of bank 0");
+        ch.step(8 * 33);
+        assert_eq!(ch.output(), 15, "position 33 = second nibble of bank 1");
+        ch.step(8 * 31);
+        assert_eq!(ch.position, 0, "wraps after 64 samples");
+    }
+
+    #[test]
+    fn wave_volume_codes_scale_output() {
+        let cases: [(u8, i16, &str); 5] = [
+            (0x00, 0, "0% mutes"),
+            (0x20, 15, "100%"),
+            (0x40, 7, "50%"),
+            (0x60, 3, "25%"),
+            (0x80, 11, "forced 75%"),
+        ];
+        for (cnt_h, expected, label) in cases {
+            let mut ch = WaveChannel::default();
+            ch.ram[0] = 0xF0;
+            ch.write_byte(0x0, 0x80);
+            ch.write_byte(0x3, cnt_h);
+            ch.write_byte(0x5, 0x80);
+            assert_eq!(ch.output(), expected, "{label}");
+        }
+    }
+
+    #[test]
+    fn noise_lfsr_taps_per_gbatek() {
+        let mut ch = NoiseChannel::default();
+        ch.write_byte(0x1, 0xF0);
+        ch.write_byte(0x4, 0x00);
+        ch.write_byte(0x5, 0x80);
+        assert_eq!(ch.lfsr, 0x4000);
+        for _ in 0..14 {
+            ch.step(32);
+        }
+        assert_eq!(ch.lfsr, 0x0001, "15-bit LFSR shifts the seed down");
+        assert!(ch.output() < 0, "no carry yet -> output low");
+        ch.step(32);
+        assert_eq!(ch.lfsr, 0x6000, "carry out XORs 0x6000");
+        assert!(ch.output() > 0, "carry -> output high");
+    }
+
+    #[test]
+    fn noise_period_uses_ratio_and_shift() {
+        let cases: [(u8, u32, &str); 3] = [
+            (0x00, 32, "r=0 -> 0.5"),
+            (0x01, 64, "r=1"),
+            (0x23, 768, "r=3, s=2"),
+        ];
+        for (cnt_h, expected, label) in cases {
+            let mut ch = NoiseChannel::default();
+            ch.write_byte(0x4, cnt_h);
+            assert_eq!(ch.period(), expected, "{label}");
+        }
+    }
+
+    #[test]
+    fn corrupt_channel_state_is_rejected() {
+        let load = |save: &dyn Fn(&mut StateWriter), noise: bool| {
+            let mut w = StateWriter::new();
+            save(&mut w);
+            let data = w.finish();
+            let mut r = StateReader::new(&data).unwrap();
+            if noise {
+                NoiseChannel::default().load_state(&mut r)
+            } else {
+                SquareChannel::new(true).load_state(&mut r)
+            }
+        };
+
+        let mut noise = NoiseChannel::default();
+        noise.write_byte(0x1, 0xF0);
+        noise.write_byte(0x5, 0x80);
+        assert_eq!(load(&|w| noise.save_state(w), true), Ok(()));
+        let mut stalled = noise.clone();
+        stalled.timer = 0;
+        assert_eq!(
+            load(&|w| stalled.save_state(w), true),
+            Err(StateError::Corrupt("noise channel")),
+            "a zero timer would spin step() forever"
+        );
+        let mut shifted = noise.clone();
+        shifted.shift = 40;
+        assert_eq!(
+            load(&|w| shifted.save_state(w), true),
+            Err(StateError::Corrupt("noise channel"))
+        );
+
+        let mut square = triggered_square(2, 15, 2047);
+        square.sweep_shift = 16;
+        assert_eq!(
+            load(&|w| square.save_state(w), false),
+            Err(StateError::Corrupt("square channel"))
+        );
+    }
+}
@@ -0,0 +1,735 @@
+use std::ops::Range;
+
+use bit::BitIndex;
+
+use crate::{
+    bus::Bus,
+    bios::HLE_INTR_WAIT_ADDR,
+    cpu::{
+        cpu::{BankedRegisters, CPU, LR_INDEX, PC_INDEX, SP_INDEX},
+        exception::Exception,
+        psr::{OperatingMode, OperatingState},
+    },
+};
+
+const SWI_CYCLES: u32 = 24;
+
+const BIOS_IF: u32 = 0x0300_7FF8;
+const BIOS_CHECKSUM: u32 = 0xBAAE_187F;
+
+const REG_DISPCNT: u32 = 0x0400_0000;
+const REG_SOUNDBIAS: u32 = 0x0400_0088;
+const REG_RCNT: u32 = 0x0400_0134;
+const REG_KEYCNT: u32 = 0x0400_0132;
+const REG_IME: u32 = 0x0400_0208;
+const REG_HALTCNT: u32 = 0x0400_0301;
+
+pub(crate) fn software_interrupt(
+    cpu: &mut CPU,
+    bus: &mut impl Bus,
+    comment: u8,
+    return_address: u32,
+) -> bool {
+    match comment {
+        0x00 => soft_reset(cpu, bus),
+        0x01 => register_ram_reset(cpu, bus),
+        0x02 => bus.write_byte(REG_HALTCNT, 0x00),
+        0x03 => bus.write_byte(REG_HALTCNT, 0x80),
+        0x04 => intr_wait(cpu, bus, return_address),
+        0x05 => {
+            cpu.reg[0] = 1;
+            cpu.reg[1] = 1;
+            intr_wait(cpu, bus, return_address);
+        }
+        0x06 => {
+            let (num, den) = (cpu.reg[0] as i32, cpu.reg[1] as i32);
+            div(cpu, num, den);
+        }
+        0x07 => {
+            let (num, den) = (cpu.reg[1] as i32, cpu.reg[0] as i32);
+            div(cpu, num, den);
+        }
+        0x08 => cpu.reg[0] = sqrt(cpu.reg[0]),
+        0x09 => cpu.reg[0] = arctan(cpu.reg[0] as i32) as i16 as u32,
+        0x0A => cpu.reg[0] = arctan2(cpu.reg[0] as i32, cpu.reg[1] as i32) as u16 as u32,
+        0x0B => cpu_set(cpu, bus),
+        0x0C => cpu_fast_set(cpu, bus),
+        0x0D => cpu.reg[0] = BIOS_CHECKSUM,
+        0x0E => bg_affine_set(cpu, bus),
+        0x0F => obj_affine_set(cpu, bus),
+        0x10 => bit_unpack(cpu, bus),
+        0x11 => lz77_uncomp(cpu, bus, false),
+        0x12 => lz77_uncomp(cpu, bus, true),
+        0x13 => huff_uncomp(cpu, bus),
+        0x14 => rl_uncomp(cpu, bus, false),
+        0x15 => rl_uncomp(cpu, bus, true),
+        0x19 => sound_bias(cpu, bus),
+        _ => return false,
+    }
+    bus.tick(SWI_CYCLES);
+    true
+}
+
+fn charge(bus: &mut impl Bus, addr: u32, width: u32) {
+    let c = bus.access_cycles(addr, width);
+    bus.tick(c);
+}
+
+fn read8(bus: &mut impl Bus, addr: u32) -> u8 {
+    charge(bus, addr, 1);
+    bus.read_byte(addr)
+}
+
+fn read16(bus: &mut impl Bus, addr: u32) -> u16 {
+    charge(bus, addr, 2);
+    bus.read_hword(addr)
+}
+
+fn read32(bus: &mut impl Bus, addr: u32) -> u32 {
+    charge(bus, addr, 4);
+    bus.read_word(addr)
+}
+
+fn write8(bus: &mut impl Bus, addr: u32, value: u8) {
+    charge(bus, addr, 1);
+    bus.write_byte(addr, value);
+}
+
+fn write16(bus: &mut impl Bus, addr: u32, value: u16) {
+    charge(bus, addr, 2);
+    bus.write_hword(addr, value);
+}
+
+fn write32(bus: &mut impl Bus, addr: u32, value: u32) {
+    charge(bus, addr, 4);
+    bus.write_word(addr, value);
+}
+
+fn clear(bus: &mut impl Bus, range: Range<u32>) {
+    for addr in range.step_by(4) {
+        write32(bus, addr, 0);
+    }
+}
+
+fn soft_reset(cpu: &mut CPU, bus: &mut impl Bus) {
+    let entry = if read8(bus, 0x0300_7FFA) == 0 { 0x0800_0000 } else { 0x0200_0000 };
+    clear(bus, 0x0300_7E00..0x0300_8000);
+
+    cpu.set_mode(OperatingMode::sys);
+    cpu.banks[OperatingMode::svc.current_bank_index()] =
+        BankedRegisters { sp: 0x0300_7FE0, ..Default::default() };
+    cpu.banks[OperatingMode::irq.current_bank_index()] =
+        BankedRegisters { sp: 0x0300_7FA0, ..Default::default() };
+    cpu.reg = [0; 16];
+    cpu.reg[SP_INDEX] = 0x0300_7F00;
+    cpu.reg[LR_INDEX] = entry;
+    cpu.reg[PC_INDEX] = entry;
+    cpu.cpsr.operating_state = OperatingState::ARM;
+    cpu.cpsr.irq_disable_bit = false;
+    cpu.flush_pipeline(bus);
+}
+
+fn register_ram_reset(cpu: &CPU, bus: &mut impl Bus) {
+    let flags = cpu.reg[0];
+    write16(bus, REG_DISPCNT, 0x0080);
+
+    if flags.bit(0) {
+        clear(bus, 0x0200_0000..0x0204_0000);
+    }
+    if flags.bit(1) {
+        clear(bus, 0x0300_0000..0x0300_7E00);
+    }
+    if flags.bit(2) {
+        clear(bus, 0x0500_0000..0x0500_0400);
+    }
+    if flags.bit(3) {
+        clear(bus, 0x0600_0000..0x0601_8000);
+    }
+    if flags.bit(4) {
+        clear(bus, 0x0700_0000..0x0700_0400);
+    }
+    if flags.bit(5) {
+        clear(bus, 0x0400_0120..0x0400_0130);
+        write16(bus, REG_RCNT, 0x8000);
+    }
+    if flags.bit(6) {
+        clear(bus, 0x0400_0060..0x0400_00A0);
+        write16(bus, REG_SOUNDBIAS, 0x0200);
+    }
+    if flags.bit(7) {
+        clear(bus, 0x0400_0004..0x0400_0060);
+        clear(bus, 0x0400_00B0..0x0400_00E0);
+        clear(bus, 0x0400_0100..0x0400_0110);
+        clear(bus, 0x0400_0200..0x0400_020C);
+        write16(bus, REG_KEYCNT, 0);
+    }
+}
+
+fn intr_wait(cpu: &mut CPU, bus: &mut impl Bus, return_address: u32) {
+    let flags = cpu.reg[1] as u16;
+    write8(bus, REG_IME, 1);
+
+    let bios_if = read16(bus, BIOS_IF);
+    if cpu.reg[0] != 0 {
+        write16(bus, BIOS_IF, bios_if & !flags);
+    } else if bios_if & flags != 0 {
+        write16(bus, BIOS_IF, bios_if & !flags);
+        return;
+    }
+
+    if return_address == HLE_INTR_WAIT_ADDR + 8 {
+        bus.write_byte(REG_HALTCNT, 0x00);
+        cpu.reg[PC_INDEX] = HLE_INTR_WAIT_ADDR + 4;
+    } else {
+        cpu.reg[0] = 0;
+        cpu.setup_exception(Exception::SoftwareInterrupt, return_address);
+        cpu.reg[PC_INDEX] = HLE_INTR_WAIT_ADDR;
+    }
+    cpu.flush_pipeline(bus);
+}
+
+fn div(cpu: &mut CPU, num: i32, den: i32) {
+    if den == 0 {
+        cpu.reg[0] = if num < 0 { -1i32 as u32 } else { 1 };
+        cpu.reg[1] = num as u32;
+        cpu.reg[3] = 1;
+        return;
+    }
+    let quotient = num.wrapping_div(den);
+    cpu.reg[0] = quotient as u32;
+    cpu.reg[1] = num.wrapping_rem(den) as u32;
+    cpu.reg[3] = quotient.unsigned_abs();
+}
+
+fn sqrt(value: u32) -> u32 {
+    let mut n = value;
+    let mut result = 0u32;
+    let mut bit = 1u32 << 30;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if n >= result + bit {
+            n -= result + bit;
+            result = (result >> 1) + bit;
+        } else {
+            result >>= 1;
+        }
+        bit >>= 2;
+    }
+    result
+}
+
+fn arctan(i: i32) -> i32 {
+    const COEFFS: [i32; 6] = [0x91C, 0xFB6, 0x16AA, 0x2081, 0x3651, 0xA2F9];
+
+    let a = (i.wrapping_mul(i) >> 14).wrapping_neg();
+    let mut b = (0xA9i32.wrapping_mul(a) >> 14) + 0x390;
+    for c in COEFFS {
+        b = (b.wrapping_mul(a) >> 14) + c;
+    }
+    i.wrapping_mul(b) >> 16
+}
+
+fn arctan2(x: i32, y: i32) -> i32 {
+    if y == 0 {
+        return if x >= 0 { 0 } else { 0x8000 };
+    }
+    if x == 0 {
+        return if y >= 0 { 0x4000 } else { 0xC000 };
+    }
+
+    if y >= 0 {
+        if x >= 0 {
+            if x >= y {
+                return arctan((y << 14).wrapping_div(x));
+            }
+        } else if -x >= y {
+            return arctan((y << 14).wrapping_div(x)) + 0x8000;
+        }
+        0x4000 - arctan((x << 14).wrapping_div(y))
+    } else {
+        if x <= 0 {
+            if -x > -y {
+                return arctan((y << 14).wrapping_div(x)) + 0x8000;
+            }
+        } else if x >= -y {
+            return arctan((y << 14).wrapping_div(x)) + 0x10000;
+        }
+        0xC000 - arctan((x << 14).wrapping_div(y))
+    }
+}
+
+fn cpu_set(cpu: &CPU, bus: &mut impl Bus) {
+    let (mut src, mut dst, control) = (cpu.reg[0], cpu.reg[1], cpu.reg[2]);
+    if src < 0x0200_0000 {
+        return;
+    }
+    let count = control.bit_range(0..21);
+    let fill = control.bit(24);
+
+    if control.bit(26) {
+        src &= !0b11;
+        dst &= !0b11;
+        for _ in 0..count {
+            let value = read32(bus, src);
+            write32(bus, dst, value);
+            dst = dst.wrapping_add(4);
+            if !fill {
+                src = src.wrapping_add(4);
+            }
+        }
+    } else {
+        src &= !0b1;
+        dst &= !0b1;
+        for _ in 0..count {
+            let value = read16(bus, src);
+            write16(bus, dst, value);
+            dst = dst.wrapping_add(2);
+            if !fill {
+                src = src.wrapping_add(2);
+            }
+        }
+    }
+}
+
+fn cpu_fast_set(cpu: &CPU, bus: &mut impl Bus) {
+    let (mut src, mut dst, control) = (cpu.reg[0] & !0b11, cpu.reg[1] & !0b11, cpu.reg[2]);
+    if src < 0x0200_0000 {
+        return;
+    }
+    let count = (control.bit_range(0..21) + 7) & !7;
+    let fill = control.bit(24);
+
+    for _ in 0..count {
+        let value = read32(bus, src);
+        write32(bus, dst, value);
+        dst = dst.wrapping_add(4);
+        if !fill {
+            src = src.wrapping_add(4);
+        }
+    }
+}
+
+fn sin_cos(angle: u16) -> (i32, i32) {
+    let theta = (angle >> 8) as f64 * std::f64::consts::PI / 128.0;
+    (
+        (theta.sin() * 16384.0).round() as i32,
+        (theta.cos() * 16384.0).round() as i32,
+    )
+}
+
+fn bg_affine_set(cpu: &CPU, bus: &mut impl Bus) {
+    let (mut src, mut dst) = (cpu.reg[0], cpu.reg[1]);
+
+    for _ in 0..cpu.reg[2] {
+        let ox = read32(bus, src) as i32;
+        let oy = read32(bus, src.wrapping_add(4)) as i32;
+        let cx = read16(bus, src.wrapping_add(8)) as i16 as i32;
+        let cy = read16(bus, src.wrapping_add(10)) as i16 as i32;
+        let sx = read16(bus, src.wrapping_add(12)) as i16 as i32;
+        let sy = read16(bus, src.wrapping_add(14)) as i16 as i32;
+        let (sin, cos) = sin_cos(read16(bus, src.wrapping_add(16)));
+
+        let pa = (sx * cos) >> 14;
+        let pb = (-sx * sin) >> 14;
+        let pc = (sy * sin) >> 14;
+        let pd = (sy * cos) >> 14;
+        let x = ox.wrapping_sub((pa * cx).wrapping_add(pb * cy));
+        let y = oy.wrapping_sub((pc * cx).wrapping_add(pd * cy));
+
+        write16(bus, dst, pa as u16);
+        write16(bus, dst.wrapping_add(2), pb as u16);
+        write16(bus, dst.wrapping_add(4), pc as u16);
+        write16(bus, dst.wrapping_add(6), pd as u16);
+        write32(bus, dst.wrapping_add(8), x as u32);
+        write32(bus, dst.wrapping_add(12), y as u32);
+
+        src = src.wrapping_add(20);
+        dst = dst.wrapping_add(16);
+    }
+}
+
+fn obj_affine_set(cpu: &CPU, bus: &mut impl Bus) {
+    let (mut src, mut dst, stride) = (cpu.reg[0], cpu.reg[1], cpu.reg[3]);
+
+    for _ in 0..cpu.reg[2] {
+        let sx = read16(bus, src) as i16 as i32;
+        let sy = read16(bus, src.wrapping_add(2)) as i16 as i32;
+        let (sin, cos) = sin_cos(read16(bus, src.wrapping_add(4)));
+
+        write16(bus, dst, ((sx * cos) >> 14) as u16);
+        write16(bus, dst.wrapping_add(stride), ((-sx * sin) >> 14) as u16);
+        write16(bus, dst.wrapping_add(stride.wrapping_mul(2)), ((sy * sin) >> 14) as u16);
+        write16(bus, dst.wrapping_add(stride.wrapping_mul(3)), ((sy * cos) >> 14) as u16);
+
+        src = src.wrapping_add(8);
+        dst = dst.wrapping_add(stride.wrapping_mul(4));
+    }
+}
+
+fn bit_unpack(cpu: &CPU, bus: &mut impl Bus) {
+    let (mut src, mut dst, info) = (cpu.reg[0], cpu.reg[1], cpu.reg[2]);
+    let len = read16(bus, info);
+    let src_width = read8(bus, info.wrapping_add(2)) as u32;
+    let dst_width = read8(bus, info.wrapping_add(3)) as u32;
+    let data = read32(bus, info.wrapping_add(4));
+    let offset = data.bit_range(0..31);
+    let zero_data = data.bit(31);
+
+    if !matches!(src_width, 1 | 2 | 4 | 8) || !matches!(dst_width, 1 | 2 | 4 | 8 | 16 | 32) {
+        return;
+    }
+    let src_mask = (1u32 << src_width) - 1;
+    let dst_mask = u32::MAX >> (32 - dst_width);
+
+    let mut out = 0u32;
+    let mut out_bits = 0;
+    for _ in 0..len {
+        let byte = read8(bus, src) as u32;
+        src = src.wrapping_add(1);
+
+        for shift in (0..8).step_by(src_width as usize) {
+            let mut unit = (byte >> shift) & src_mask;
+            if unit != 0 || zero_data {
+                unit = unit.wrapping_add(offset) & dst_mask;
+            }
+            out |= unit << out_bits;
+            out_bits += dst_width;
+            if out_bits == 32 {
+                write32(bus, dst, out);
+                dst = dst.wrapping_add(4);
+                out = 0;
+                out_bits = 0;
+            }
+        }
+    }
+}
+
+fn write_output(bus: &mut impl Bus, dst: u32, data: &[u8], vram: bool) {
+    if vram {
+        for (i, pair) in data.chunks(2).enumerate() {
+            let value = u16::from_le_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]);
+            write16(bus, dst.wrapping_add(i as u32 * 2), value);
+        }
+    } else {
+        for (i, &byte) in data.iter().enumerate() {
+            write8(bus, dst.wrapping_add(i as u32), byte);
+        }
+    }
+}
+
+fn lz77_uncomp(cpu: &CPU, bus: &mut impl Bus, vram: bool) {
+    let mut src = cpu.reg[0];
+    let size = (read32(bus, src) >> 8) as usize;
+    src = src.wrapping_add(4);
+
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        let flags = read8(bus, src);
+        src = src.wrapping_add(1);
+
+        for i in (0..8).rev() {
+            if out.len() >= size {
+                break;
+            }
+            if flags.bit(i) {
+                let b0 = read8(bus, src) as usize;
+                let b1 = read8(bus, src.wrapping_add(1)) as usize;
+                src = src.wrapping_add(2);
+
+                let disp = ((b0 & 0xF) << 8 | b1) + 1;
+                for _ in 0..(b0 >> 4) + 3 {
+                    let value = if disp <= out.len() { out[out.len() - disp] } else { 0 };
+                    out.push(value);
+                }
+            } else {
+                out.push(read8(bus, src));
+                src = src.wrapping_add(1);
+            }
+        }
+    }
+    out.truncate(size);
+    write_output(bus, cpu.reg[1], &out, vram);
+}
+
+fn huff_uncomp(cpu: &CPU, bus: &mut impl Bus) {
+    let (src, mut dst) = (cpu.reg[0], cpu.reg[1]);
+    let header = read32(bus, src);
+    let bits = header.bit_range(0..4);
+    let size = header >> 8;
+    if !matches!(bits, 1 | 2 | 4 | 8) {
+        return;
+    }
+
+    let tree = src.wrapping_add(4);
+    let tree_len = (read8(bus, tree) as u32 + 1) * 2;
+    let root = tree.wrapping_add(1);
+    let mut stream = tree.wrapping_add(tree_len);
+
+    let mut node_addr = root;
+    let mut node = read8(bus, node_addr);
+    let mut out = 0u32;
+    let mut out_bits = 0;
+    let mut written = 0;
+
+    while written < size {
+        let word = read32(bus, stream);
+        stream = stream.wrapping_add(4);
+
+        for i in (0..32).rev() {
+            let right = word.bit(i);
+            let offset = (node as u32 & 0x3F) * 2 + 2 + right as u32;
+            let child = (node_addr & !1).wrapping_add(offset);
+            if child.wrapping_sub(tree) >= tree_len {
+                return;
+            }
+            let is_data = if right { node.bit(6) } else { node.bit(7) };
+
+            if !is_data {
+                node_addr = child;
+                node = read8(bus, node_addr);
+                continue;
+            }
+
+            out |= (read8(bus, child) as u32 & ((1 << bits) - 1)) << out_bits;
+            out_bits += bits;
+            node_addr = root;
+            node = read8(bus, node_addr);
+
+            if out_bits == 32 {
+                write32(bus, dst, out);
+                dst = dst.wrapping_add(4);
+                written += 4;
+                out = 0;
+                out_bits = 0;
+                if written >= size {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn rl_uncomp(cpu: &CPU, bus: &mut impl Bus, vram: bool) {
+    let mut src = cpu.reg[0];
+    let size = (read32(bus, src) >> 8) as usize;
+    src = src.wrapping_add(4);
+
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        let flag = read8(bus, src);
+        src = src.wrapping_add(1);
+
+        if flag.bit(7) {
+            let value = read8(bus, src);
+            src = src.wrapping_add(1);
+            out.resize(out.len() + (flag & 0x7F) as usize + 3, value);
+        } else {
+            for _ in 0..(flag & 0x7F) as usize + 1 {
+                out.push(read8(bus, src));
+                src = src.wrapping_add(1);
+            }
+        }
+    }
+    out.truncate(size);
+    write_output(bus, cpu.reg[1], &out, vram);
+}
+
+fn sound_bias(cpu: &CPU, bus: &mut impl Bus) {
+    let bias = read16(bus, REG_SOUNDBIAS);
+    let level = if cpu.reg[0] != 0 { 0x200 } else { 0 };
+    write16(bus, REG_SOUNDBIAS, (bias & !0x3FF) | level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bios::Bios, cartridge::Cartridge, memory::Memory, rom::Rom};
+    use std::path::Path;
+
+    const EWRAM: u32 = 0x0200_0000;
+
+    fn setup() -> (CPU, Memory) {
+        let cart = Cartridge::new(Rom::new(&vec![0u8; 0x1000]), Path::new("/nonexistent/no.sav"))
+            .expect("cart");
+        (CPU::new(), Memory::new(Bios::hle(), cart))
+    }
+
+    fn load(bus: &mut Memory, addr: u32, data: &[u8]) {
+        for (i, &b) in data.iter().enumerate() {
+            bus.write_byte(addr + i as u32, b);
+        }
+    }
+
+    fn call(cpu: &mut CPU, bus: &mut Memory, comment: u8, regs: [u32; 4]) {
+        cpu.reg[..4].copy_from_slice(&regs);
+        assert!(software_interrupt(cpu, bus, comment, 0x0800_0000), "SWI {comment:#04x} handled");
+    }
+
+    #[test]
+    fn div_returns_quotient_remainder_and_abs() {
+        let (mut cpu, mut bus) = setup();
+        let cases = [
+            (7, 2, (3, 1, 3), "positive"),
+            (-7, 2, (-3, -1, 3), "negative numerator"),
+            (7, -2, (-3, 1, 3), "negative denominator"),
+            (-5i32, 0i32, (-1i32, -5i32, 1u32), "divide by zero"),
+        ];
+        for (num, den, (q, r, abs), label) in cases {
+            call(&mut cpu, &mut bus, 0x06, [num as u32, den as u32, 0, 0]);
+            assert_eq!(
+                (cpu.reg[0] as i32, cpu.reg[1] as i32, cpu.reg[3]),
+                (q, r, abs),
+                "{label}"
+            );
+        }
+
+        call(&mut cpu, &mut bus, 0x07, [2, 7, 0, 0]);
+        assert_eq!(cpu.reg[0], 3, "DivArm swaps operands");
+    }
+
+    #[test]
+    fn sqrt_floors_result() {
+        let cases: [(u32, u32); 5] = [(0, 0), (1, 1), (15, 3), (16, 4), (u32::MAX, 0xFFFF)];
+        for (input, want) in cases {
+            assert_eq!(sqrt(input), want, "sqrt({input})");
+        }
+    }
+
+    #[test]
+    fn arctan2_covers_all_quadrants() {
+        let cases: [(i32, i32, i32, &str); 6] = [
+            (0x4000, 0, 0x0000, "+x axis"),
+            (0, 0x4000, 0x4000, "+y axis"),
+            (-0x4000, 0, 0x8000, "-x axis"),
+            (0, -0x4000, 0xC000, "-y axis"),
+            (0x4000, 0x4000, 0x2000, "45 degrees"),
+            (-0x4000, -0x4000, 0xA000, "225 degrees"),
+        ];
+        for (x, y, want, label) in cases {
+            let got = arctan2(x, y) as u16 as i32;
+            assert!((got - want).abs() <= 2, "{label}: got {got:#06x}, want {want:#06x}");
+        }
+    }
+
+    #[test]
+    fn cpu_set_copies_and_fills() {
+        let (mut cpu, mut bus) = setup();
+        load(&mut bus, EWRAM, &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        call(&mut cpu, &mut bus, 0x0B, [EWRAM, EWRAM + 0x100, 4, 0]);
+        assert_eq!(bus.read_word(EWRAM + 0x100), 0x0403_0201, "16-bit copy, first word");
+        assert_eq!(bus.read_word(EWRAM + 0x104), 0x0807_0605, "16-bit copy, second word");
+
+        call(&mut cpu, &mut bus, 0x0B, [EWRAM, EWRAM + 0x200, 3 | 1 << 24 | 1 << 26, 0]);
+        for i in 0..3 {
+            assert_eq!(bus.read_word(EWRAM + 0x200 + i * 4), 0x0403_0201, "32-bit fill word {i}");
+        }
+        assert_eq!(bus.read_word(EWRAM + 0x20C), 0, "fill stops after count");
+
+        call(&mut cpu, &mut bus, 0x0C, [EWRAM, EWRAM + 0x300, 1 | 1 << 24, 0]);
+        assert_eq!(bus.read_word(EWRAM + 0x31C), 0x0403_0201, "CpuFastSet rounds up to 8 words");
+        assert_eq!(bus.read_word(EWRAM + 0x320), 0, "CpuFastSet stops at 8 words");
+    }
+
+    #[test]
+    fn lz77_expands_back_references() {
+        let (mut cpu, mut bus) = setup();
+        load(
+            &mut bus,
+            EWRAM,
+            &[0x10, 10, 0, 0, 0b0001_0000, b'A', b'B', b'C', 0x30, 0x02, b'X'],
+        );
+
+        call(&mut cpu, &mut bus, 0x11, [EWRAM, EWRAM + 0x100, 0, 0]);
+        let out: Vec<u8> = (0..10).map(|i| bus.read_byte(EWRAM + 0x100 + i)).collect();
+        assert_eq!(out, b"ABCABCABCX");
+    }
+
+    #[test]
+    fn rl_expands_runs_and_literals() {
+        let (mut cpu, mut bus) = setup();
+        load(&mut bus, EWRAM, &[0x30, 6, 0, 0, 0x82, b'A', 0x00, b'B']);
+
+        call(&mut cpu, &mut bus, 0x15, [EWRAM, 0x0600_0000, 0, 0]);
+        let out: Vec<u8> = (0..6).map(|i| bus.read_byte(0x0600_0000 + i)).collect();
+        assert_eq!(out, b"AAAAAB");
+    }
+
+    #[test]
+    fn huffman_walks_tree_msb_first() {
+        let (mut cpu, mut bus) = setup();
+        load(&mut bus, EWRAM, &[0x28, 4, 0, 0, 1, 0xC0, b'X', b'Y', 0, 0, 0, 0x60]);
+
+        call(&mut cpu, &mut bus, 0x13, [EWRAM, EWRAM + 0x100, 0, 0]);
+        let out: Vec<u8> = (0..4).map(|i| bus.read_byte(EWRAM + 0x100 + i)).collect();
+        assert_eq!(out, b"XYYX");
+    }
+
+    #[test]
+    fn huffman_stops_on_nodes_outside_the_tree() {
+        let (mut cpu, mut bus) = setup();
+        load(&mut bus, EWRAM, &[0x28, 4, 0, 0, 1, 0x3F, b'X', b'Y', 0, 0, 0, 0]);
+
+        call(&mut cpu, &mut bus, 0x13, [EWRAM, EWRAM + 0x100, 0, 0]);
+        assert_eq!(bus.read_word(EWRAM + 0x100), 0, "nothing decoded");
+    }
+
+    #[test]
+    fn soft_reset_clears_stack_area_and_jumps_to_entry() {
+        let (mut cpu, mut bus) = setup();
+        bus.write_word(0x0300_7F10, 0xDEAD_BEEF);
+        bus.write_byte(0x0300_7FFA, 1);
+        cpu.reg[4] = 4;
+
+        call(&mut cpu, &mut bus, 0x00, [0; 4]);
+        assert_eq!(bus.read_word(0x0300_7F10), 0);
+        assert_eq!(cpu.reg[4], 0);
+        assert_eq!(cpu.reg[SP_INDEX], 0x0300_7F00);
+        assert_eq!(cpu.cpsr.mode, OperatingMode::sys);
+        assert_eq!(cpu.reg[PC_INDEX], 0x0200_0000 + 8, "RAM entry, pipeline refilled");
+    }
+
+    #[test]
+    fn bit_unpack_widens_and_offsets_nonzero_units() {
+        let (mut cpu, mut bus) = setup();
+        load(&mut bus, EWRAM, &[0b0000_0101]);
+        load(&mut bus, EWRAM + 0x10, &[1, 0, 1, 4, 1, 0, 0, 0]);
+
+        call(&mut cpu, &mut bus, 0x10, [EWRAM, EWRAM + 0x100, EWRAM + 0x10, 0]);
+        assert_eq!(bus.read_word(EWRAM + 0x100), 0x0000_0202);
+    }
+
+    #[test]
+    fn obj_affine_set_rotates_and_scales() {
+        let (mut cpu, mut bus) = setup();
+        let cases: [(u16, [u16; 4], &str); 2] = [
+            (0x0000, [0x100, 0, 0, 0x100], "identity"),
+            (0x4000, [0, 0xFF00, 0x100, 0], "90 degrees"),
+        ];
+        for (angle, want, label) in cases {
+            bus.write_hword(EWRAM, 0x100);
+            bus.write_hword(EWRAM + 2, 0x100);
+            bus.write_hword(EWRAM + 4, angle);
+            call(&mut cpu, &mut bus, 0x0F, [EWRAM, EWRAM + 0x100, 1, 2]);
+            let got: Vec<u16> = (0..4).map(|i| bus.read_hword(EWRAM + 0x100 + i * 2)).collect();
+            assert_eq!(got, want, "{label}");
+        }
+    }
+
+    #[test]
+    fn obj_affine_set_wraps_large_strides() {
+        let (mut cpu, mut bus) = setup();
+        bus.write_hword(EWRAM, 0x100);
+        bus.write_hword(EWRAM + 2, 0x100);
+        call(&mut cpu, &mut bus, 0x0F, [EWRAM, EWRAM + 0x100, 1, 0x4000_0000]);
+        assert_eq!(bus.read_hword(EWRAM + 0x100), 0x100);
+    }
+
+    #[test]
+    fn unknown_swi_falls_through() {
+        let (mut cpu, mut bus) = setup();
+        assert!(!software_interrupt(&mut cpu, &mut bus, 0x2A, 0x0800_0000));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{
+    bus::Bus,
+    rom::{InvalidROM, Rom},
+};
+
+pub(crate) mod hle;
+
+const BIOS_SIZE: usize = 0x4000;
+
+const HLE_VECTORS: [(u32, u32); 8] = [
+    (0x0000, 0xE3A0_F408), // mov pc, #0x08000000
+    (0x0004, 0xE1B0_F00E), // movs pc, lr
+    (0x0008, 0xE1B0_F00E), // movs pc, lr
+    (0x000C, 0xE25E_F004), // subs pc, lr, #4
+    (0x0010, 0xE25E_F008), // subs pc, lr, #8
+    (0x0014, 0xEAFF_FFFE), // b .
+    (0x0018, 0xEA00_0042), // b 0x128
+    (0x001C, 0xE25E_F004), // subs pc, lr, #4
+];
+
+const HLE_IRQ_HANDLER: [u32; 6] = [
+    0xE92D_500F, // stmfd sp!, {r0-r3, r12, lr}
+    0xE3A0_0301, // mov r0, #0x04000000
+    0xE28F_E000, // add lr, pc, #0
+    0xE510_F004, // ldr pc, [r0, #-4]
+    0xE8BD_500F, // ldmfd sp!, {r0-r3, r12, lr}
+    0xE25E_F004, // subs pc, lr, #4
+];
+
+const HLE_IRQ_HANDLER_ADDR: usize = 0x128;
+
+const HLE_INTR_WAIT: [u32; 4] = [
+    0xE321_F01F, // msr cpsr_c, #0x1F
+    0xEF04_0000, // swi 0x04
+    0xE321_F093, // msr cpsr_c, #0x93
+    0xE1B0_F00E, // movs pc, lr
+];
+
+pub(crate) const HLE_INTR_WAIT_ADDR: u32 = 0x140;
+
+pub struct Bios {
+    rom: Rom,
+    hle: bool,
+}
+
+impl Bios {
+    pub fn new(rom: Rom) -> Result<Bios, InvalidROM> {
+        if rom.len() != BIOS_SIZE {
+            return Err(InvalidROM);
+        }
+
+        Ok(Self { rom, hle: false })
+    }
+
+    pub fn hle() -> Bios {
+        let mut image = vec![0u8; BIOS_SIZE];
+        for (addr, instr) in HLE_VECTORS {
+            image[addr as usize..addr as usize + 4].copy_from_slice(&instr.to_le_bytes());
+        }
+        for (i, instr) in HLE_IRQ_HANDLER.iter().enumerate() {
+            let addr = HLE_IRQ_HANDLER_ADDR + i * 4;
+            image[addr..addr + 4].copy_from_slice(&instr.to_le_bytes());
+        }
+        for (i, instr) in HLE_INTR_WAIT.iter().enumerate() {
+            let addr = HLE_INTR_WAIT_ADDR as usize + i * 4;
+            image[addr..addr + 4].copy_from_slice(&instr.to_le_bytes());
+        }
+
+        Self {
+            rom: Rom::new(&image),
+            hle: true,
+        }
+    }
+
+    pub fn is_hle(&self) -> bool {
+        self.hle
+    }
+
+    pub fn read(&self, addr: u32) -> u8 {
+        self.rom.read_byte(addr)
+    }
+}
@@ -12,10 +12,22 @@ pub(crate) trait Bus {
         1
     }
 
+    fn fetch_cycles(&mut self, addr: u32, width: u32) -> u32 {
+        self.access_cycles(addr, width)
+    }
+
     fn invalidate_rom_seq(&mut self) {}
 
     fn notify_pc(&mut self, _pc: u32) {}
 
+    fn notify_access(&mut self, _addr: u32, _width: u32, _write: bool) {}
+
+    fn notify_swi(&mut self, _comment: u8) {}
+
+    fn hle_bios(&self) -> bool {
+        false
+    }
+
     fn read_hword(&self, addr: u32) -> u16 {
         let addr = addr & !0b1;
         u16::from_le_bytes([self.read_byte(addr), self.read_byte(addr.wrapping_add(1))])
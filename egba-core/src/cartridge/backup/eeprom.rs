@@ -1,6 +1,9 @@
 use std::{cell::RefCell, fs, path::Path};
 
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 use super::BackupBuffer;
 
@@ -186,3 +189,36 @@ impl Bus for EEPROM {
         }
     }
 }
+
+impl Snapshot for EEPROM {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.data.borrow().save_state(w);
+        (*self.state.borrow() as u8).save_state(w);
+        self.buffer.borrow().save_state(w);
+        self.address.borrow().save_state(w);
+        self.bits_read.borrow().save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        const STATES: [EepromState; 8] = [
+            EepromState::Ready,
+            EepromState::Command,
+            EepromState::AddressRead,
+            EepromState::AddressWrite,
+            EepromState::WriteData,
+            EepromState::WriteStop,
+            EepromState::ReadDummy,
+            EepromState::ReadData,
+        ];
+
+        self.data.get_mut().load_state(r)?;
+        let mut state = 0u8;
+        state.load_state(r)?;
+        *self.state.get_mut() = *STATES
+            .get(state as usize)
+            .ok_or(StateError::Corrupt("EEPROM state"))?;
+        self.buffer.get_mut().load_state(r)?;
+        self.address.get_mut().load_state(r)?;
+        self.bits_read.get_mut().load_state(r)
+    }
+}
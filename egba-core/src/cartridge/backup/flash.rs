@@ -1,7 +1,10 @@
 use std::{fs, path::Path};
 
 use super::BackupBuffer;
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum FlashState {
@@ -174,3 +177,37 @@ impl Bus for Flash {
         }
     }
 }
+
+impl Snapshot for Flash {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.data.save_state(w);
+        (self.state as u8).save_state(w);
+        self.bank.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        const STATES: [FlashState; 9] = [
+            FlashState::Ready,
+            FlashState::Cmd1,
+            FlashState::Cmd2,
+            FlashState::EraseSetup,
+            FlashState::EraseCmd1,
+            FlashState::EraseCmd2,
+            FlashState::WriteSetup,
+            FlashState::BankSwitchSetup,
+            FlashState::IdMode,
+        ];
+
+        self.data.load_state(r)?;
+        let mut state = 0u8;
+        state.load_state(r)?;
+        self.state = *STATES
+            .get(state as usize)
+            .ok_or(StateError::Corrupt("flash state"))?;
+        self.bank.load_state(r)?;
+        if self.bank > 1 {
+            return Err(StateError::Corrupt("flash bank"));
+        }
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ pub enum BackupMedia {
     Sram(SRAM),
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum BackupType {
     NoBackup,
     Eeprom512B,
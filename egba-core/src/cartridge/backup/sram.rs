@@ -1,6 +1,9 @@
 use std::{fs, path::Path};
 
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 use super::BackupBuffer;
 
@@ -41,3 +44,13 @@ impl Default for SRAM {
         Self::new()
     }
 }
+
+impl Snapshot for SRAM {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.0.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.0.load_state(r)
+    }
+}
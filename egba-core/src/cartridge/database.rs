@@ -0,0 +1,91 @@
+use super::backup::BackupType;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Peripheral {
+    Rtc,
+    Solar,
+    Gyro,
+    Rumble,
+    Tilt,
+}
+
+#[derive(Debug)]
+pub struct GameOverride {
+    pub code: &'static str,
+    pub backup: Option<BackupType>,
+    pub peripherals: &'static [Peripheral],
+    pub mirroring: bool,
+}
+
+const fn game(
+    code: &'static str,
+    backup: Option<BackupType>,
+    peripherals: &'static [Peripheral],
+) -> GameOverride {
+    GameOverride {
+        code,
+        backup,
+        peripherals,
+        mirroring: false,
+    }
+}
+
+const fn classic_nes(code: &'static str) -> GameOverride {
+    GameOverride {
+        code,
+        backup: Some(BackupType::Eeprom512B),
+        peripherals: &[],
+        mirroring: true,
+    }
+}
+
+const FLASH_128: Option<BackupType> = Some(BackupType::Flash128KB);
+
+const GAMES: &[GameOverride] = &[
+    game("AXV", FLASH_128, &[Peripheral::Rtc]),
+    game("AXP", FLASH_128, &[Peripheral::Rtc]),
+    game("BPE", FLASH_128, &[Peripheral::Rtc]),
+    game("BPR", FLASH_128, &[]),
+    game("BPG", FLASH_128, &[]),
+    game("AX4", FLASH_128, &[]),
+    game("BKA", FLASH_128, &[Peripheral::Rtc]),
+    game("BR4", Some(BackupType::Flash64KB), &[Peripheral::Rtc]),
+    game("U3I", Some(BackupType::Eeprom8KB), &[Peripheral::Rtc, Peripheral::Solar]),
+    game("U32", Some(BackupType::Eeprom8KB), &[Peripheral::Rtc, Peripheral::Solar]),
+    game("U33", None, &[Peripheral::Rtc, Peripheral::Solar]),
+    game("V49", Some(BackupType::Sram32KB), &[Peripheral::Rumble]),
+    game("RZW", Some(BackupType::Sram32KB), &[Peripheral::Gyro, Peripheral::Rumble]),
+    game("KYG", None, &[Peripheral::Tilt]),
+    game("KHP", None, &[Peripheral::Tilt]),
+    classic_nes("FBM"),
+    classic_nes("FAD"),
+    classic_nes("FDK"),
+    classic_nes("FDM"),
+    classic_nes("FEB"),
+    classic_nes("FIC"),
+    classic_nes("FLB"),
+    classic_nes("FMR"),
+    classic_nes("FP7"),
+    classic_nes("FSM"),
+    classic_nes("FXV"),
+    classic_nes("FZL"),
+];
+
+pub fn lookup(game_code: &str) -> Option<&'static GameOverride> {
+    GAMES.iter().find(|game| game_code.starts_with(game.code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_ignores_region_letter() {
+        let emerald = lookup("BPEJ").expect("emerald");
+        assert_eq!(emerald.backup, Some(BackupType::Flash128KB));
+        assert_eq!(emerald.peripherals, [Peripheral::Rtc]);
+        assert!(lookup("FSME").expect("smb").mirroring);
+        assert!(lookup("AGSE").is_none());
+        assert!(lookup("").is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
+
+const PIN_SAMPLE: u8 = 0b0001;
+const PIN_CLK: u8 = 0b0010;
+const PIN_DATA: u8 = 0b0100;
+
+const GYRO_CENTER: i32 = 0x6C0;
+
+pub struct Gyro {
+    rate: i16,
+    sample: u16,
+    edge: bool,
+    data: bool,
+}
+
+impl Gyro {
+    pub fn new() -> Self {
+        Self {
+            rate: 0,
+            sample: 0,
+            edge: false,
+            data: false,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: i16) {
+        self.rate = rate;
+    }
+
+    pub(crate) fn read_pins(&self) -> u8 {
+        if self.data {
+            PIN_DATA
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn write_pins(&mut self, pins: u8) {
+        if pins & PIN_SAMPLE != 0 {
+            self.sample = (GYRO_CENTER + (self.rate as i32 >> 5)) as u16;
+        }
+        if self.edge && pins & PIN_CLK == 0 {
+            self.data = self.sample & 0x8000 != 0;
+            self.sample <<= 1;
+        }
+        self.edge = pins & PIN_CLK != 0;
+    }
+}
+
+impl Default for Gyro {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for Gyro {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.sample.save_state(w);
+        self.edge.save_state(w);
+        self.data.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.sample.load_state(r)?;
+        self.edge.load_state(r)?;
+        self.data.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_shifts_out_msb_first_on_falling_clock() {
+        let mut gyro = Gyro::new();
+        gyro.set_rate(-0x800);
+        gyro.write_pins(PIN_SAMPLE);
+        gyro.write_pins(0);
+
+        let mut value = 0u16;
+        for _ in 0..16 {
+            gyro.write_pins(PIN_CLK);
+            gyro.write_pins(0);
+            value = (value << 1) | (gyro.read_pins() >> 2) as u16;
+        }
+        assert_eq!(value, 0x6C0 - 0x40);
+    }
+}
@@ -0,0 +1,216 @@
+pub mod gyro;
+pub mod rtc;
+pub mod rumble;
+pub mod solar;
+
+use super::database::Peripheral;
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
+use gyro::Gyro;
+use rtc::{Rtc, RtcClock};
+use rumble::Rumble;
+use solar::SolarSensor;
+
+pub const GPIO_DATA: u32 = 0x0800_00C4;
+pub const GPIO_DIRECTION: u32 = 0x0800_00C6;
+pub const GPIO_CONTROL: u32 = 0x0800_00C8;
+
+pub enum GpioDevice {
+    Rtc(Rtc),
+    Solar(SolarSensor),
+    Gyro(Gyro),
+    Rumble(Rumble),
+}
+
+impl GpioDevice {
+    fn read_pins(&self) -> u8 {
+        match self {
+            GpioDevice::Rtc(rtc) => rtc.read_pins(),
+            GpioDevice::Solar(solar) => solar.read_pins(),
+            GpioDevice::Gyro(gyro) => gyro.read_pins(),
+            GpioDevice::Rumble(_) => 0,
+        }
+    }
+
+    fn write_pins(&mut self, pins: u8) {
+        match self {
+            GpioDevice::Rtc(rtc) => rtc.write_pins(pins),
+            GpioDevice::Solar(solar) => solar.write_pins(pins),
+            GpioDevice::Gyro(gyro) => gyro.write_pins(pins),
+            GpioDevice::Rumble(rumble) => rumble.write_pins(pins),
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            GpioDevice::Rtc(_) => 0,
+            GpioDevice::Solar(_) => 1,
+            GpioDevice::Gyro(_) => 2,
+            GpioDevice::Rumble(_) => 3,
+        }
+    }
+}
+
+pub struct Gpio {
+    pins: u8,
+    direction: u8,
+    readable: bool,
+    devices: Vec<GpioDevice>,
+}
+
+impl Gpio {
+    pub fn new(devices: Vec<GpioDevice>) -> Self {
+        Self {
+            pins: 0,
+            direction: 0,
+            readable: false,
+            devices,
+        }
+    }
+
+    pub fn from_peripherals(peripherals: &[Peripheral]) -> Option<Self> {
+        let devices: Vec<GpioDevice> = peripherals
+            .iter()
+            .filter_map(|peripheral| match peripheral {
+                Peripheral::Rtc => Some(GpioDevice::Rtc(Rtc::new(RtcClock::Host))),
+                Peripheral::Solar => Some(GpioDevice::Solar(SolarSensor::new())),
+                Peripheral::Gyro => Some(GpioDevice::Gyro(Gyro::new())),
+                Peripheral::Rumble => Some(GpioDevice::Rumble(Rumble::default())),
+                Peripheral::Tilt => None,
+            })
+            .collect();
+        (!devices.is_empty()).then(|| Self::new(devices))
+    }
+
+    pub fn devices(&self) -> &[GpioDevice] {
+        &self.devices
+    }
+
+    pub fn devices_mut(&mut self) -> &mut [GpioDevice] {
+        &mut self.devices
+    }
+
+    pub fn contains(addr: u32) -> bool {
+        (GPIO_DATA..GPIO_CONTROL + 2).contains(&addr)
+    }
+
+    pub fn readable(&self) -> bool {
+        self.readable
+    }
+
+    pub fn read_byte(&self, addr: u32) -> u8 {
+        match addr {
+            GPIO_DATA => {
+                let output = self.devices.iter().fold(0, |acc, d| acc | d.read_pins());
+                ((self.pins & self.direction) | (output & !self.direction)) & 0xF
+            }
+            GPIO_DIRECTION => self.direction,
+            GPIO_CONTROL => self.readable as u8,
+            _ => 0,
+        }
+    }
+
+    pub fn write_byte(&mut self, addr: u32, value: u8) {
+        match addr {
+            GPIO_DATA => {
+                self.pins = value & 0xF;
+                for device in self.devices.iter_mut() {
+                    device.write_pins(self.pins & self.direction);
+                }
+            }
+            GPIO_DIRECTION => self.direction = value & 0xF,
+            GPIO_CONTROL => self.readable = value & 1 != 0,
+            _ => {}
+        }
+    }
+}
+
+impl Snapshot for Gpio {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.pins.save_state(w);
+        self.direction.save_state(w);
+        self.readable.save_state(w);
+        (self.devices.len() as u8).save_state(w);
+        for device in &self.devices {
+            device.kind().save_state(w);
+            match device {
+                GpioDevice::Rtc(rtc) => rtc.save_state(w),
+                GpioDevice::Solar(solar) => solar.save_state(w),
+                GpioDevice::Gyro(gyro) => gyro.save_state(w),
+                GpioDevice::Rumble(rumble) => rumble.is_active().save_state(w),
+            }
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.pins.load_state(r)?;
+        self.direction.load_state(r)?;
+        self.readable.load_state(r)?;
+        let mut count = 0u8;
+        count.load_state(r)?;
+        if count as usize != self.devices.len() {
+            return Err(StateError::Corrupt("gpio devices"));
+        }
+        for device in self.devices.iter_mut() {
+            let mut kind = 0u8;
+            kind.load_state(r)?;
+            if kind != device.kind() {
+                return Err(StateError::Corrupt("gpio devices"));
+            }
+            match device {
+                GpioDevice::Rtc(rtc) => rtc.load_state(r)?,
+                GpioDevice::Solar(solar) => solar.load_state(r)?,
+                GpioDevice::Gyro(gyro) => gyro.load_state(r)?,
+                GpioDevice::Rumble(rumble) => {
+                    let mut active = false;
+                    active.load_state(r)?;
+                    rumble.write_pins((active as u8) << 3);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_masks_pins_driven_to_device() {
+        let mut gpio = Gpio::new(vec![GpioDevice::Rtc(Rtc::new(RtcClock::Fixed(0)))]);
+        gpio.write_byte(GPIO_DIRECTION, 0xFF);
+        gpio.write_byte(GPIO_CONTROL, 0x03);
+        assert_eq!(gpio.read_byte(GPIO_DIRECTION), 0x0F);
+        assert_eq!(gpio.read_byte(GPIO_CONTROL), 0x01);
+
+        gpio.write_byte(GPIO_DATA, 0xF5);
+        assert_eq!(gpio.read_byte(GPIO_DATA), 0x05);
+
+        gpio.write_byte(GPIO_DIRECTION, 0b0101);
+        gpio.write_byte(GPIO_DATA, 0b0010);
+        assert_eq!(
+            gpio.read_byte(GPIO_DATA),
+            0,
+            "SIO is an input and the RTC drives it low"
+        );
+    }
+
+    #[test]
+    fn tilt_is_not_a_gpio_device() {
+        let gpio = Gpio::from_peripherals(&[Peripheral::Gyro, Peripheral::Tilt, Peripheral::Rumble])
+            .expect("gpio");
+        let kinds: Vec<u8> = gpio.devices().iter().map(GpioDevice::kind).collect();
+        assert_eq!(kinds, [2, 3]);
+        assert!(Gpio::from_peripherals(&[Peripheral::Tilt]).is_none());
+    }
+
+    #[test]
+    fn rumble_follows_motor_pin() {
+        let mut gpio = Gpio::new(vec![GpioDevice::Rumble(Rumble::default())]);
+        gpio.write_byte(GPIO_DIRECTION, 0b1000);
+        gpio.write_byte(GPIO_DATA, 0b1000);
+        assert!(matches!(gpio.devices()[0], GpioDevice::Rumble(ref r) if r.is_active()));
+        gpio.write_byte(GPIO_DATA, 0);
+        assert!(matches!(gpio.devices()[0], GpioDevice::Rumble(ref r) if !r.is_active()));
+    }
+}
@@ -0,0 +1,471 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
+
+const PIN_SCK: u8 = 0b001;
+const PIN_SIO: u8 = 0b010;
+const PIN_CS: u8 = 0b100;
+
+const CMD_RESET: u8 = 0;
+const CMD_STATUS: u8 = 1;
+const CMD_DATETIME: u8 = 2;
+const CMD_TIME: u8 = 3;
+
+const STATUS_24H: u8 = 0x40;
+const STATUS_WRITABLE: u8 = 0x6A;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const CPU_CLOCK: u64 = 16_777_216;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RtcClock {
+    Host,
+    Fixed(i64),
+    Emulated(i64),
+}
+
+impl RtcClock {
+    fn now(self, cycles: u64) -> i64 {
+        match self {
+            RtcClock::Host => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64),
+            RtcClock::Fixed(t) => t,
+            RtcClock::Emulated(start) => start + (cycles / CPU_CLOCK) as i64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RtcState {
+    Idle,
+    Command,
+    Read,
+    Write,
+}
+
+pub struct Rtc {
+    clock: RtcClock,
+    state: RtcState,
+    cs: bool,
+    sck: bool,
+    sio: bool,
+    shift: u8,
+    bits: u8,
+    command: u8,
+    buffer: [u8; 7],
+    index: usize,
+    length: usize,
+    status: u8,
+    offset: i64,
+    cycles: u64,
+}
+
+impl Rtc {
+    pub fn new(clock: RtcClock) -> Self {
+        Self {
+            clock,
+            state: RtcState::Idle,
+            cs: false,
+            sck: false,
+            sio: false,
+            shift: 0,
+            bits: 0,
+            command: 0,
+            buffer: [0; 7],
+            index: 0,
+            length: 0,
+            status: STATUS_24H,
+            offset: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: RtcClock) {
+        self.clock = clock;
+    }
+
+    pub(crate) fn set_cycles(&mut self, cycles: u64) {
+        self.cycles = cycles;
+    }
+
+    pub(crate) fn read_pins(&self) -> u8 {
+        if self.sio {
+            PIN_SIO
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn write_pins(&mut self, pins: u8) {
+        let sck = pins & PIN_SCK != 0;
+        let cs = pins & PIN_CS != 0;
+
+        if !cs {
+            self.state = RtcState::Idle;
+        } else if !self.cs {
+            self.state = RtcState::Command;
+            self.shift = 0;
+            self.bits = 0;
+        } else if !self.sck && sck {
+            self.clock_bit(pins & PIN_SIO != 0);
+        }
+
+        self.cs = cs;
+        self.sck = sck;
+    }
+
+    fn clock_bit(&mut self, sio: bool) {
+        match self.state {
+            RtcState::Idle => {}
+            RtcState::Command => {
+                self.shift = (self.shift << 1) | sio as u8;
+                self.bits += 1;
+                if self.bits == 8 {
+                    self.start_command(self.shift);
+                }
+            }
+            RtcState::Read => {
+                self.sio = (self.buffer[self.index] >> self.bits) & 1 != 0;
+                self.next_bit();
+            }
+            RtcState::Write => {
+                self.buffer[self.index] |= (sio as u8) << self.bits;
+                self.next_bit();
+            }
+        }
+    }
+
+    fn next_bit(&mut self) {
+        self.bits += 1;
+        if self.bits < 8 {
+            return;
+        }
+        self.bits = 0;
+        self.index += 1;
+        if self.index == self.length {
+            if self.state == RtcState::Write {
+                self.finish_write();
+            }
+            self.state = RtcState::Idle;
+        }
+    }
+
+    fn start_command(&mut self, byte: u8) {
+        let byte = if byte >> 4 == 0b0110 {
+            byte
+        } else {
+            byte.reverse_bits()
+        };
+        self.state = RtcState::Idle;
+        if byte >> 4 != 0b0110 {
+            return;
+        }
+
+        self.command = (byte >> 1) & 0b111;
+        let read = byte & 1 != 0;
+        self.length = match self.command {
+            CMD_STATUS => 1,
+            CMD_DATETIME => 7,
+            CMD_TIME => 3,
+            _ => 0,
+        };
+        self.index = 0;
+        self.bits = 0;
+
+        if self.command == CMD_RESET {
+            self.status = 0;
+            self.offset = 0;
+            return;
+        }
+        if self.length == 0 {
+            return;
+        }
+
+        if read {
+            self.fill_read_buffer();
+            self.state = RtcState::Read;
+        } else {
+            self.buffer = [0; 7];
+            self.state = RtcState::Write;
+        }
+    }
+
+    fn fill_read_buffer(&mut self) {
+        match self.command {
+            CMD_STATUS => self.buffer[0] = self.status,
+            CMD_DATETIME => self.buffer = self.datetime(),
+            CMD_TIME => {
+                let datetime = self.datetime();
+                self.buffer[..3].copy_from_slice(&datetime[4..]);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_write(&mut self) {
+        let now = self.clock.now(self.cycles) + self.offset;
+        match self.command {
+            CMD_STATUS => self.status = self.buffer[0] & STATUS_WRITABLE,
+            CMD_DATETIME => {
+                if let Some(t) = decode_datetime(&self.buffer) {
+                    self.offset += t - now;
+                }
+            }
+            CMD_TIME => {
+                if let Some(secs) = decode_time(&self.buffer[..3]) {
+                    let midnight = now - now.rem_euclid(SECONDS_PER_DAY);
+                    self.offset += midnight + secs - now;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn datetime(&self) -> [u8; 7] {
+        let t = self.clock.now(self.cycles) + self.offset;
+        let days = t.div_euclid(SECONDS_PER_DAY);
+        let secs = t.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let weekday = (days + 4).rem_euclid(7);
+        let hour = secs / 3600;
+
+        let mut hour_reg = if self.status & STATUS_24H != 0 {
+            bcd(hour as u8)
+        } else {
+            bcd((hour % 12) as u8)
+        };
+        if hour >= 12 {
+            hour_reg |= 0x80;
+        }
+
+        [
+            bcd(year.rem_euclid(100) as u8),
+            bcd(month as u8),
+            bcd(day as u8),
+            bcd(weekday as u8),
+            hour_reg,
+            bcd((secs / 60 % 60) as u8),
+            bcd((secs % 60) as u8),
+        ]
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.status];
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out
+    }
+
+    pub(crate) fn restore(&mut self, bytes: &[u8]) {
+        if bytes.len() == 9 {
+            self.status = bytes[0] & STATUS_WRITABLE;
+            self.offset = i64::from_le_bytes(bytes[1..9].try_into().unwrap());
+        }
+    }
+}
+
+fn bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+fn from_bcd(value: u8) -> Option<i64> {
+    let (hi, lo) = (value >> 4, value & 0xF);
+    (hi < 10 && lo < 10).then_some((hi * 10 + lo) as i64)
+}
+
+fn decode_time(bytes: &[u8]) -> Option<i64> {
+    let hour = from_bcd(bytes[0] & 0x3F)?;
+    let minute = from_bcd(bytes[1])?;
+    let second = from_bcd(bytes[2])?;
+    (hour < 24 && minute < 60 && second < 60).then_some(hour * 3600 + minute * 60 + second)
+}
+
+fn decode_datetime(bytes: &[u8; 7]) -> Option<i64> {
+    let year = 2000 + from_bcd(bytes[0])?;
+    let month = from_bcd(bytes[1])?;
+    let day = from_bcd(bytes[2])?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY + decode_time(&bytes[4..])?)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+impl Snapshot for Rtc {
+    fn save_state(&self, w: &mut StateWriter) {
+        (self.state as u8).save_state(w);
+        self.cs.save_state(w);
+        self.sck.save_state(w);
+        self.sio.save_state(w);
+        self.shift.save_state(w);
+        self.bits.save_state(w);
+        self.command.save_state(w);
+        self.buffer.save_state(w);
+        self.index.save_state(w);
+        self.length.save_state(w);
+        self.status.save_state(w);
+        (self.offset as u64).save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        const STATES: [RtcState; 4] = [
+            RtcState::Idle,
+            RtcState::Command,
+            RtcState::Read,
+            RtcState::Write,
+        ];
+
+        let mut state = 0u8;
+        state.load_state(r)?;
+        self.state = *STATES
+            .get(state as usize)
+            .ok_or(StateError::Corrupt("rtc state"))?;
+        self.cs.load_state(r)?;
+        self.sck.load_state(r)?;
+        self.sio.load_state(r)?;
+        self.shift.load_state(r)?;
+        self.bits.load_state(r)?;
+        self.command.load_state(r)?;
+        self.buffer.load_state(r)?;
+        self.index.load_state(r)?;
+        self.length.load_state(r)?;
+        self.status.load_state(r)?;
+        let mut offset = 0u64;
+        offset.load_state(r)?;
+        self.offset = offset as i64;
+        if self.bits > 8 || self.length > self.buffer.len() || self.index > self.length {
+            return Err(StateError::Corrupt("rtc transfer"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOON_2024_02_29: i64 = 1_709_208_000;
+
+    fn send_byte(rtc: &mut Rtc, byte: u8, msb_first: bool) {
+        for i in 0..8 {
+            let bit = if msb_first {
+                (byte >> (7 - i)) & 1
+            } else {
+                (byte >> i) & 1
+            };
+            rtc.write_pins(PIN_CS | bit << 1);
+            rtc.write_pins(PIN_CS | PIN_SCK | bit << 1);
+        }
+    }
+
+    fn read_byte(rtc: &mut Rtc) -> u8 {
+        let mut value = 0;
+        for i in 0..8 {
+            rtc.write_pins(PIN_CS);
+            rtc.write_pins(PIN_CS | PIN_SCK);
+            value |= ((rtc.read_pins() & PIN_SIO) >> 1) << i;
+        }
+        value
+    }
+
+    fn begin(rtc: &mut Rtc) {
+        rtc.write_pins(PIN_SCK);
+        rtc.write_pins(PIN_SCK | PIN_CS);
+    }
+
+    fn end(rtc: &mut Rtc) {
+        rtc.write_pins(PIN_SCK);
+    }
+
+    #[test]
+    fn datetime_read_returns_bcd_from_clock() {
+        let mut rtc = Rtc::new(RtcClock::Fixed(NOON_2024_02_29));
+        begin(&mut rtc);
+        send_byte(&mut rtc, 0x65, true);
+        let bytes: Vec<u8> = (0..7).map(|_| read_byte(&mut rtc)).collect();
+        end(&mut rtc);
+        assert_eq!(bytes, [0x24, 0x02, 0x29, 0x04, 0x92, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn emulated_clock_follows_elapsed_cycles() {
+        let mut rtc = Rtc::new(RtcClock::Emulated(NOON_2024_02_29));
+        rtc.set_cycles(CPU_CLOCK * 61 - 1);
+        assert_eq!(rtc.datetime()[4..], [0x92, 0x01, 0x00]);
+        rtc.set_cycles(CPU_CLOCK * 61);
+        assert_eq!(rtc.datetime()[4..], [0x92, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn lsb_first_command_byte_is_accepted() {
+        let mut rtc = Rtc::new(RtcClock::Fixed(0));
+        begin(&mut rtc);
+        send_byte(&mut rtc, 0x63, false);
+        assert_eq!(read_byte(&mut rtc), STATUS_24H);
+    }
+
+    #[test]
+    fn datetime_write_shifts_clock_and_persists() {
+        let mut rtc = Rtc::new(RtcClock::Fixed(NOON_2024_02_29));
+        begin(&mut rtc);
+        send_byte(&mut rtc, 0x64, true);
+        for b in [0x01, 0x06, 0x15, 0x05, 0x23, 0x59, 0x30] {
+            send_byte(&mut rtc, b, false);
+        }
+        end(&mut rtc);
+
+        let mut restored = Rtc::new(RtcClock::Fixed(NOON_2024_02_29));
+        restored.restore(&rtc.to_bytes());
+        assert_eq!(
+            restored.datetime(),
+            [0x01, 0x06, 0x15, 0x05, 0xA3, 0x59, 0x30]
+        );
+    }
+
+    #[test]
+    fn status_write_and_reset() {
+        let mut rtc = Rtc::new(RtcClock::Fixed(0));
+        begin(&mut rtc);
+        send_byte(&mut rtc, 0x62, true);
+        send_byte(&mut rtc, 0xFF, false);
+        end(&mut rtc);
+        assert_eq!(rtc.status, STATUS_WRITABLE);
+        assert_eq!(rtc.datetime()[4], 0x00, "midnight in 24h mode");
+
+        begin(&mut rtc);
+        send_byte(&mut rtc, 0x60, true);
+        end(&mut rtc);
+        assert_eq!(rtc.status, 0);
+    }
+
+    #[test]
+    fn civil_conversions_round_trip() {
+        for days in [-1, 0, 10_957, 19_782, 40_000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
@@ -0,0 +1,16 @@
+const PIN_MOTOR: u8 = 0b1000;
+
+#[derive(Default)]
+pub struct Rumble {
+    active: bool,
+}
+
+impl Rumble {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub(crate) fn write_pins(&mut self, pins: u8) {
+        self.active = pins & PIN_MOTOR != 0;
+    }
+}
@@ -0,0 +1,103 @@
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
+
+const PIN_CLK: u8 = 0b0001;
+const PIN_RESET: u8 = 0b0010;
+const PIN_CS: u8 = 0b0100;
+const PIN_FLAG: u8 = 0b1000;
+
+pub struct SolarSensor {
+    level: u8,
+    sample: u8,
+    counter: u16,
+    edge: bool,
+    flag: bool,
+}
+
+impl SolarSensor {
+    pub fn new() -> Self {
+        Self {
+            level: 0,
+            sample: 0xFF,
+            counter: 0,
+            edge: false,
+            flag: false,
+        }
+    }
+
+    pub fn set_level(&mut self, level: u8) {
+        self.level = level;
+    }
+
+    pub(crate) fn read_pins(&self) -> u8 {
+        if self.flag {
+            PIN_FLAG
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn write_pins(&mut self, pins: u8) {
+        if pins & PIN_CS != 0 {
+            return;
+        }
+        if pins & PIN_RESET != 0 {
+            self.counter = 0;
+            self.sample = 0xFF - self.level;
+        }
+        if pins & PIN_CLK != 0 && self.edge {
+            self.counter = self.counter.saturating_add(1);
+        }
+        self.edge = pins & PIN_CLK == 0;
+        self.flag = self.counter >= self.sample as u16;
+    }
+}
+
+impl Default for SolarSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for SolarSensor {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.sample.save_state(w);
+        self.counter.save_state(w);
+        self.edge.save_state(w);
+        self.flag.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.sample.load_state(r)?;
+        self.counter.load_state(r)?;
+        self.edge.load_state(r)?;
+        self.flag.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clocks_until_flag(sensor: &mut SolarSensor) -> u32 {
+        sensor.write_pins(PIN_RESET);
+        sensor.write_pins(0);
+        let mut clocks = 0;
+        while sensor.read_pins() & PIN_FLAG == 0 {
+            sensor.write_pins(PIN_CLK);
+            sensor.write_pins(0);
+            clocks += 1;
+        }
+        clocks
+    }
+
+    #[test]
+    fn brighter_light_trips_flag_sooner() {
+        let mut sensor = SolarSensor::new();
+        sensor.set_level(0x20);
+        let dim = clocks_until_flag(&mut sensor);
+        sensor.set_level(0xC0);
+        let bright = clocks_until_flag(&mut sensor);
+        assert_eq!(dim, 0xDF);
+        assert_eq!(bright, 0x3F);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod backup;
+pub mod database;
+pub mod gpio;
+pub mod tilt;
 
 use std::{
     fs,
@@ -8,8 +11,12 @@ use std::{
 use crate::{
     bus::Bus,
     rom::{InvalidROM, Rom},
+    state::{Snapshot, StateError, StateReader, StateWriter},
 };
 use backup::{eeprom::EEPROM, flash::Flash, sram::SRAM, BackupBuffer, BackupMedia, BackupType};
+use database::Peripheral;
+use gpio::{rtc::RtcClock, Gpio, GpioDevice};
+use tilt::Tilt;
 
 #[derive(Clone, Copy)]
 enum EepromRange {
@@ -21,16 +28,31 @@ pub struct Cartridge {
     rom: Rom,
     backup: Option<BackupMedia>,
     eeprom_range: Option<EepromRange>,
+    gpio: Option<Gpio>,
+    tilt: Option<Tilt>,
+    mirroring: bool,
     sav_path: PathBuf,
 }
 
 impl Cartridge {
     pub fn new(rom: Rom, backup_path: &Path) -> Result<Cartridge, InvalidROM> {
+        Self::with_backup_data(rom, backup_path, fs::read(backup_path).ok())
+    }
+
+    pub fn with_backup_data(
+        rom: Rom,
+        backup_path: &Path,
+        backup_data: Option<Vec<u8>>,
+    ) -> Result<Cartridge, InvalidROM> {
         if rom.len() > 0x2000000 {
             return Err(InvalidROM);
         }
 
-        let backup = if let Ok(buf) = fs::read(backup_path) {
+        let game = rom
+            .header()
+            .and_then(|header| database::lookup(&header.game_code));
+
+        let backup = if let Some(buf) = backup_data {
             match buf.len() {
                 0x8000 => Some(BackupMedia::Sram(SRAM::from(buf))),
                 0x200 | 0x2000 => Some(BackupMedia::Eeprom(EEPROM::from(buf))),
@@ -38,7 +60,10 @@ impl Cartridge {
                 _ => None,
             }
         } else {
-            match rom.get_backup_type() {
+            let backup_type = game
+                .and_then(|game| game.backup.clone())
+                .unwrap_or_else(|| rom.get_backup_type());
+            match backup_type {
                 BackupType::Eeprom512B => Some(BackupMedia::Eeprom(EEPROM::new(1))),
                 BackupType::Eeprom8KB => Some(BackupMedia::Eeprom(EEPROM::new(8))),
                 BackupType::Flash64KB => Some(BackupMedia::Flash(Flash::new(64))),
@@ -57,14 +82,100 @@ impl Cartridge {
             _ => None,
         };
 
+        let peripherals = game.map_or(&[][..], |game| game.peripherals);
+        let mut gpio = Gpio::from_peripherals(peripherals);
+        if let (Some(gpio), Ok(buf)) = (gpio.as_mut(), fs::read(backup_path.with_extension("rtc"))) {
+            for device in gpio.devices_mut() {
+                if let GpioDevice::Rtc(rtc) = device {
+                    rtc.restore(&buf);
+                }
+            }
+        }
+        let tilt = peripherals.contains(&Peripheral::Tilt).then(Tilt::new);
+
         Ok(Self {
             rom,
             backup,
             eeprom_range,
+            gpio,
+            tilt,
+            mirroring: game.is_some_and(|game| game.mirroring),
             sav_path: backup_path.to_path_buf(),
         })
     }
 
+    pub fn gpio(&self) -> Option<&Gpio> {
+        self.gpio.as_ref()
+    }
+
+    fn gpio_devices(&mut self) -> impl Iterator<Item = &mut GpioDevice> {
+        self.gpio.iter_mut().flat_map(|gpio| gpio.devices_mut().iter_mut())
+    }
+
+    pub fn set_rtc_clock(&mut self, clock: RtcClock) {
+        for device in self.gpio_devices() {
+            if let GpioDevice::Rtc(rtc) = device {
+                rtc.set_clock(clock);
+            }
+        }
+    }
+
+    pub fn rtc_state(&self) -> Option<Vec<u8>> {
+        self.gpio.iter().flat_map(Gpio::devices).find_map(|device| match device {
+            GpioDevice::Rtc(rtc) => Some(rtc.to_bytes()),
+            _ => None,
+        })
+    }
+
+    pub fn restore_rtc(&mut self, bytes: &[u8]) {
+        for device in self.gpio_devices() {
+            if let GpioDevice::Rtc(rtc) = device {
+                rtc.restore(bytes);
+            }
+        }
+    }
+
+    pub(crate) fn sync_rtc(&mut self, cycles: u64) {
+        for device in self.gpio_devices() {
+            if let GpioDevice::Rtc(rtc) = device {
+                rtc.set_cycles(cycles);
+            }
+        }
+    }
+
+    pub fn set_light_level(&mut self, level: u8) {
+        for device in self.gpio_devices() {
+            if let GpioDevice::Solar(solar) = device {
+                solar.set_level(level);
+            }
+        }
+    }
+
+    pub fn set_gyro(&mut self, rate: i16) {
+        for device in self.gpio_devices() {
+            if let GpioDevice::Gyro(gyro) = device {
+                gyro.set_rate(rate);
+            }
+        }
+    }
+
+    pub fn set_tilt(&mut self, x: i16, y: i16) {
+        if let Some(tilt) = self.tilt.as_mut() {
+            tilt.set(x, y);
+        }
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.gpio.iter().flat_map(Gpio::devices).any(|device| match device {
+            GpioDevice::Rumble(rumble) => rumble.is_active(),
+            _ => false,
+        })
+    }
+
+    fn tilt_read(&self, addr: u32) -> Option<u8> {
+        self.tilt.as_ref().and_then(|tilt| tilt.read_byte(addr))
+    }
+
     fn eeprom_read(&self, addr: usize) -> bool {
         match self.eeprom_range {
             Some(EepromRange::Full) => (0x0D00_0000..=0x0DFF_FFFF).contains(&addr),
@@ -73,6 +184,15 @@ impl Cartridge {
         }
     }
 
+    fn header_id(&self) -> [u8; 0x1E] {
+        let mut id = [0; 0x1E];
+        let data = self.rom.data();
+        if data.len() >= 0xBE {
+            id.copy_from_slice(&data[0xA0..0xBE]);
+        }
+        id
+    }
+
     pub fn save(&self) {
         match &self.backup {
             Some(BackupMedia::Sram(m)) => m.save(&self.sav_path),
@@ -80,12 +200,26 @@ impl Cartridge {
             Some(BackupMedia::Eeprom(m)) => m.save(&self.sav_path),
             None => {}
         }
+        for device in self.gpio.iter().flat_map(Gpio::devices) {
+            if let GpioDevice::Rtc(rtc) = device {
+                let path = self.sav_path.with_extension("rtc");
+                if fs::write(&path, rtc.to_bytes()).is_err() {
+                    panic!("Failed to save data to: {:?}", path.file_name());
+                }
+            }
+        }
     }
 }
 
 impl Bus for Cartridge {
     fn read_byte(&self, addr: u32) -> u8 {
         match addr {
+            _ if Gpio::contains(addr) && self.gpio.as_ref().is_some_and(Gpio::readable) => {
+                match (&self.gpio, addr & 1) {
+                    (Some(gpio), 0) => gpio.read_byte(addr),
+                    _ => 0,
+                }
+            }
             0x0800_0000..=0x0DFF_FFFF => {
                 if self.eeprom_read(addr as usize) {
                     match &self.backup {
@@ -96,6 +230,8 @@ impl Bus for Cartridge {
                     let rom_addr = (addr & 0x01FF_FFFF) as usize;
                     if rom_addr < self.rom.len() {
                         self.rom.data()[rom_addr]
+                    } else if self.mirroring && !self.rom.is_empty() {
+                        self.rom.data()[rom_addr % self.rom.len()]
                     } else {
                         let halfword = (addr >> 1) as u16;
                         if addr & 1 == 0 {
@@ -106,9 +242,10 @@ impl Bus for Cartridge {
                     }
                 }
             }
-            0x0E00_0000..=0x0E00_FFFF => match &self.backup {
-                Some(BackupMedia::Sram(media)) => media.read_byte(addr & 0x7FFF),
-                Some(BackupMedia::Flash(media)) => media.read_byte(addr & 0xFFFF),
+            0x0E00_0000..=0x0E00_FFFF => match (self.tilt_read(addr), &self.backup) {
+                (Some(value), _) => value,
+                (None, Some(BackupMedia::Sram(media))) => media.read_byte(addr & 0x7FFF),
+                (None, Some(BackupMedia::Flash(media))) => media.read_byte(addr & 0xFFFF),
                 _ => 0xFF,
             },
             _ => 0xFF,
@@ -117,12 +254,22 @@ impl Bus for Cartridge {
 
     fn write_byte(&mut self, addr: u32, value: u8) {
         match addr {
+            _ if Gpio::contains(addr) && self.gpio.is_some() => {
+                if let Some(gpio) = self.gpio.as_mut() {
+                    gpio.write_byte(addr, value);
+                }
+            }
             0x0800_0000..=0x0DFF_FFFF if self.eeprom_read(addr as usize) => {
                 if let Some(BackupMedia::Eeprom(eeprom)) = self.backup.as_mut() {
                     eeprom.write_byte(addr, value);
                 }
             }
             0x0800_0000..=0x0DFF_FFFF => {}
+            0x0E00_0000..=0x0E00_FFFF if self.tilt.is_some() => {
+                if let Some(tilt) = self.tilt.as_mut() {
+                    tilt.write_byte(addr, value);
+                }
+            }
             0x0E00_0000..=0x0E00_FFFF => match self.backup.as_mut() {
                 Some(BackupMedia::Sram(media)) => media.write_byte(addr & 0x7FFF, value),
                 Some(BackupMedia::Flash(media)) => media.write_byte(addr & 0xFFFF, value),
@@ -133,6 +280,64 @@ impl Bus for Cartridge {
     }
 }
 
+impl Snapshot for Cartridge {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.put(&self.header_id());
+        match &self.backup {
+            None => 0u8.save_state(w),
+            Some(BackupMedia::Eeprom(m)) => {
+                1u8.save_state(w);
+                m.save_state(w);
+            }
+            Some(BackupMedia::Flash(m)) => {
+                2u8.save_state(w);
+                m.save_state(w);
+            }
+            Some(BackupMedia::Sram(m)) => {
+                3u8.save_state(w);
+                m.save_state(w);
+            }
+        }
+        self.gpio.is_some().save_state(w);
+        if let Some(gpio) = &self.gpio {
+            gpio.save_state(w);
+        }
+        self.tilt.is_some().save_state(w);
+        if let Some(tilt) = &self.tilt {
+            tilt.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        if r.take(0x1E)? != self.header_id() {
+            return Err(StateError::RomMismatch);
+        }
+        let mut kind = 0u8;
+        kind.load_state(r)?;
+        match (kind, self.backup.as_mut()) {
+            (0, None) => Ok(()),
+            (1, Some(BackupMedia::Eeprom(m))) => m.load_state(r),
+            (2, Some(BackupMedia::Flash(m))) => m.load_state(r),
+            (3, Some(BackupMedia::Sram(m))) => m.load_state(r),
+            _ => Err(StateError::Corrupt("backup type")),
+        }?;
+        let mut has_gpio = false;
+        has_gpio.load_state(r)?;
+        match (has_gpio, self.gpio.as_mut()) {
+            (false, None) => Ok(()),
+            (true, Some(gpio)) => gpio.load_state(r),
+            _ => Err(StateError::Corrupt("gpio")),
+        }?;
+        let mut has_tilt = false;
+        has_tilt.load_state(r)?;
+        match (has_tilt, self.tilt.as_mut()) {
+            (false, None) => Ok(()),
+            (true, Some(tilt)) => tilt.load_state(r),
+            _ => Err(StateError::Corrupt("tilt")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,4 +401,44 @@ mod tests {
             assert_eq!(cart.read_byte(addr), want, "{label}");
         }
     }
+
+    #[test]
+    fn gpio_registers_read_as_rom_until_enabled() {
+        let mut rom = vec![0u8; 0x1000];
+        rom[0xAC..0xB0].copy_from_slice(b"AXVE");
+        rom[0xC4] = 0xAB;
+        let mut cart =
+            Cartridge::new(Rom::new(&rom), Path::new("/nonexistent/no.sav")).expect("cart");
+        cart.set_rtc_clock(RtcClock::Fixed(0));
+
+        cart.write_byte(0x0800_00C6, 0x07);
+        assert_eq!(
+            cart.read_byte(0x0800_00C4),
+            0xAB,
+            "GPIO is write-only by default"
+        );
+        cart.write_byte(0x0800_00C8, 0x01);
+        assert_eq!(cart.read_byte(0x0800_00C6), 0x07);
+        assert_eq!(cart.read_byte(0x0800_00C8), 0x01);
+
+        let plain = Cartridge::new(Rom::new(&[0u8; 0x1000]), Path::new("/nonexistent/no.sav"))
+            .expect("cart");
+        assert!(plain.gpio().is_none());
+    }
+
+    #[test]
+    fn database_overrides_backup_and_enables_mirroring() {
+        let mut rom = vec![0u8; 0x1000];
+        rom[0xAC..0xB0].copy_from_slice(b"FSME");
+        rom[0x10] = 0x5A;
+        let cart = Cartridge::new(Rom::new(&rom), Path::new("/nonexistent/no.sav")).expect("cart");
+        assert!(matches!(cart.backup, Some(BackupMedia::Eeprom(_))));
+        assert_eq!(cart.read_byte(0x0800_1010), 0x5A);
+        assert_eq!(cart.read_byte(0x09FF_F010), 0x5A);
+
+        rom[0xAC..0xB0].copy_from_slice(b"AGSE");
+        let cart = Cartridge::new(Rom::new(&rom), Path::new("/nonexistent/no.sav")).expect("cart");
+        assert!(cart.backup.is_none());
+        assert_eq!(cart.read_byte(0x0800_1010), 0x08, "open bus without the quirk");
+    }
 }
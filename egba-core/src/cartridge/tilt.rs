@@ -0,0 +1,92 @@
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
+
+const TILT_CENTER: i32 = 0x3A0;
+
+pub struct Tilt {
+    x: i16,
+    y: i16,
+    latched: (u16, u16),
+    armed: bool,
+}
+
+impl Tilt {
+    pub fn new() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            latched: (TILT_CENTER as u16, TILT_CENTER as u16),
+            armed: false,
+        }
+    }
+
+    pub fn set(&mut self, x: i16, y: i16) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn read_byte(&self, addr: u32) -> Option<u8> {
+        let (x, y) = self.latched;
+        match addr & 0xFFFF {
+            0x8200 => Some(x as u8),
+            0x8300 => Some((x >> 8) as u8 & 0xF | 0x80),
+            0x8400 => Some(y as u8),
+            0x8500 => Some((y >> 8) as u8 & 0xF),
+            _ => None,
+        }
+    }
+
+    pub fn write_byte(&mut self, addr: u32, value: u8) {
+        match (addr & 0xFFFF, value) {
+            (0x8000, 0x55) => self.armed = true,
+            (0x8100, 0xAA) if self.armed => {
+                self.armed = false;
+                self.latched = (
+                    (TILT_CENTER + (self.x as i32 >> 5)) as u16,
+                    (TILT_CENTER + (self.y as i32 >> 5)) as u16,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for Tilt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot for Tilt {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.latched.0.save_state(w);
+        self.latched.1.save_state(w);
+        self.armed.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.latched.0.load_state(r)?;
+        self.latched.1.load_state(r)?;
+        self.armed.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_latches_after_unlock_sequence() {
+        let mut tilt = Tilt::new();
+        tilt.set(0x1000, -0x1000);
+        tilt.write_byte(0x0E00_8100, 0xAA);
+        assert_eq!(tilt.read_byte(0x0E00_8200), Some(0xA0), "no sample without 0x55 first");
+
+        tilt.write_byte(0x0E00_8000, 0x55);
+        tilt.write_byte(0x0E00_8100, 0xAA);
+        assert_eq!(tilt.read_byte(0x0E00_8200), Some(0x20));
+        assert_eq!(tilt.read_byte(0x0E00_8300), Some(0x84));
+        assert_eq!(tilt.read_byte(0x0E00_8400), Some(0x20));
+        assert_eq!(tilt.read_byte(0x0E00_8500), Some(0x03));
+        assert_eq!(tilt.read_byte(0x0E00_0000), None);
+    }
+}
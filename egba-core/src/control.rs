@@ -3,6 +3,7 @@ use bit::BitIndex;
 use crate::{
     bus::Bus,
     cpu::{cpu::CPU, exception::Exception, psr::OperatingState},
+    state::{Snapshot, StateError, StateReader, StateWriter},
 };
 
 #[derive(Default)]
@@ -93,6 +94,17 @@ pub(crate) enum PowerMode {
     Stop,
 }
 
+const PREFETCH_SLOTS: u32 = 8;
+
+#[derive(Default, Clone, Copy)]
+struct Prefetch {
+    active: bool,
+    head: u32,
+    tail: u32,
+    count: u32,
+    progress: u32,
+}
+
 pub(crate) struct SystemControl {
     waitcnt: u16,
     power: PowerMode,
@@ -100,6 +112,7 @@ pub(crate) struct SystemControl {
     ws_s: [u32; 3],
     sram_n: u32,
     postflg: u8,
+    prefetch: Prefetch,
 }
 
 impl Default for SystemControl {
@@ -111,6 +124,7 @@ impl Default for SystemControl {
             ws_s: [0; 3],
             sram_n: 0,
             postflg: 0,
+            prefetch: Prefetch::default(),
         };
         s.recompute_waitcnt();
         s
@@ -183,6 +197,61 @@ impl SystemControl {
     pub(crate) fn sram_access_cycles(&self) -> u32 {
         1 + self.sram_n
     }
+
+    #[inline]
+    pub(crate) fn prefetch_enabled(&self) -> bool {
+        self.waitcnt.bit(14)
+    }
+
+    pub(crate) fn prefetch_start(&mut self, addr: u32) {
+        self.prefetch = Prefetch {
+            active: true,
+            head: addr,
+            tail: addr,
+            count: 0,
+            progress: 0,
+        };
+    }
+
+    pub(crate) fn prefetch_stop(&mut self) {
+        self.prefetch.active = false;
+    }
+
+    pub(crate) fn prefetch_idle(&mut self, cycles: u32) {
+        if !self.prefetch.active || !self.prefetch_enabled() {
+            return;
+        }
+        self.prefetch.progress += cycles;
+        while self.prefetch.count < PREFETCH_SLOTS {
+            let duration = self.rom_seq_cycles(self.prefetch.tail, 2);
+            if self.prefetch.progress < duration {
+                return;
+            }
+            self.prefetch.progress -= duration;
+            self.prefetch.count += 1;
+            self.prefetch.tail = self.prefetch.tail.wrapping_add(2);
+        }
+        self.prefetch.progress = 0;
+    }
+
+    pub(crate) fn prefetch_read(&mut self, addr: u32, width: u32) -> Option<u32> {
+        if !self.prefetch.active || !self.prefetch_enabled() || addr != self.prefetch.head {
+            return None;
+        }
+        let slots = width / 2;
+        self.prefetch.head = addr.wrapping_add(width);
+        if self.prefetch.count >= slots {
+            self.prefetch.count -= slots;
+            return Some(1);
+        }
+        let duration = self.rom_seq_cycles(self.prefetch.tail, 2);
+        let missing = slots - self.prefetch.count;
+        let cycles = (duration - self.prefetch.progress) + (missing - 1) * duration;
+        self.prefetch.tail = self.prefetch.head;
+        self.prefetch.count = 0;
+        self.prefetch.progress = 0;
+        Some(cycles)
+    }
 }
 
 impl Bus for SystemControl {
@@ -216,6 +285,56 @@ impl Bus for SystemControl {
     }
 }
 
+impl Snapshot for InterruptControl {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.master.save_state(w);
+        self.enable.save_state(w);
+        self.request.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.master.load_state(r)?;
+        self.enable.load_state(r)?;
+        self.request.load_state(r)
+    }
+}
+
+impl Snapshot for SystemControl {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.waitcnt.save_state(w);
+        (self.power as u8).save_state(w);
+        self.postflg.save_state(w);
+        self.prefetch.active.save_state(w);
+        self.prefetch.head.save_state(w);
+        self.prefetch.tail.save_state(w);
+        self.prefetch.count.save_state(w);
+        self.prefetch.progress.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.waitcnt.load_state(r)?;
+        let mut power = 0u8;
+        power.load_state(r)?;
+        self.power = match power {
+            0 => PowerMode::Active,
+            1 => PowerMode::Halt,
+            2 => PowerMode::Stop,
+            _ => return Err(StateError::Corrupt("power mode")),
+        };
+        self.postflg.load_state(r)?;
+        self.prefetch.active.load_state(r)?;
+        self.prefetch.head.load_state(r)?;
+        self.prefetch.tail.load_state(r)?;
+        self.prefetch.count.load_state(r)?;
+        self.prefetch.progress.load_state(r)?;
+        if self.prefetch.count > PREFETCH_SLOTS {
+            return Err(StateError::Corrupt("prefetch buffer"));
+        }
+        self.recompute_waitcnt();
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,7 +1,9 @@
 use bit::BitIndex;
 
 use crate::bus::Bus;
+use crate::cpu::decode::DecodeCache;
 use crate::cpu::psr::{OperatingMode, OperatingState, ProgramStatusRegister};
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
 
 use super::bit_r;
 
@@ -24,6 +26,7 @@ pub struct CPU {
     pub(crate) spsr: u32,
     pub pipeline: [u32; 3],
     pub(crate) pipeline_dirty: bool,
+    decode_cache: DecodeCache,
 }
 
 impl CPU {
@@ -37,6 +40,7 @@ impl CPU {
             spsr: 0,
             pipeline: [0, 0, 0],
             pipeline_dirty: false,
+            decode_cache: DecodeCache::new(),
         }
     }
 
@@ -80,7 +84,7 @@ impl CPU {
             OperatingState::THUMB => (bus.read_hword(addr) as u32, 2),
         };
         self.reg[PC_INDEX] = addr.wrapping_add(width);
-        let c = bus.access_cycles(addr, width);
+        let c = bus.fetch_cycles(addr, width);
         bus.tick(c);
         instr
     }
@@ -88,10 +92,17 @@ impl CPU {
     fn execute(&mut self, bus: &mut impl Bus, instr: u32) {
         match self.cpsr.operating_state {
             OperatingState::ARM => {
-                self.arm_opcodes(bus, instr);
+                if !self.condition_check(bit_r!(instr, 28..32)) {
+                    return;
+                }
+                let addr = self.reg[PC_INDEX].wrapping_sub(8);
+                let decoded = self.decode_cache.arm(addr, instr);
+                self.arm_execute(bus, decoded);
             }
             OperatingState::THUMB => {
-                self.thumb_opcodes(bus, bit_r!(instr, 0..16) as u16);
+                let addr = self.reg[PC_INDEX].wrapping_sub(4);
+                let decoded = self.decode_cache.thumb(addr, instr as u16);
+                self.thumb_execute(bus, decoded);
             }
         }
     }
@@ -130,3 +141,41 @@ impl Default for CPU {
         Self::new()
     }
 }
+
+impl Snapshot for BankedRegisters {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.sp.save_state(w);
+        self.lr.save_state(w);
+        self.spsr.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.sp.load_state(r)?;
+        self.lr.load_state(r)?;
+        self.spsr.load_state(r)
+    }
+}
+
+impl Snapshot for CPU {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.reg.save_state(w);
+        self.fiq_r8_12_banked.save_state(w);
+        self.banks.save_state(w);
+        u32::from(self.cpsr).save_state(w);
+        self.spsr.save_state(w);
+        self.pipeline.save_state(w);
+        self.pipeline_dirty.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.reg.load_state(r)?;
+        self.fiq_r8_12_banked.load_state(r)?;
+        self.banks.load_state(r)?;
+        let mut cpsr = 0u32;
+        cpsr.load_state(r)?;
+        self.cpsr = cpsr.into();
+        self.spsr.load_state(r)?;
+        self.pipeline.load_state(r)?;
+        self.pipeline_dirty.load_state(r)
+    }
+}
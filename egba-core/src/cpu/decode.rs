@@ -0,0 +1,100 @@
+use super::cpu::CPU;
+use super::modes::{ArmInstr, ThumbInstr};
+
+const PAGE_SLOTS: usize = 1024;
+const EWRAM_LEN: u32 = 0x4_0000;
+const IWRAM_LEN: u32 = 0x8000;
+const ROM_LEN: u32 = 0x200_0000;
+const CODE_LEN: u32 = EWRAM_LEN + IWRAM_LEN + ROM_LEN;
+
+fn code_offset(addr: u32) -> Option<u32> {
+    match addr >> 24 {
+        0x2 => Some(addr & (EWRAM_LEN - 1)),
+        0x3 => Some(EWRAM_LEN + (addr & (IWRAM_LEN - 1))),
+        0x8..=0xD => Some(EWRAM_LEN + IWRAM_LEN + (addr & (ROM_LEN - 1))),
+        _ => None,
+    }
+}
+
+type Page<T> = Box<[(u32, T)]>;
+
+struct Pages<T> {
+    blank: Page<T>,
+    pages: Vec<Option<Page<T>>>,
+}
+
+impl<T: Copy> Pages<T> {
+    fn new(slots: usize, blank: T) -> Self {
+        Self {
+            blank: vec![(0, blank); PAGE_SLOTS].into_boxed_slice(),
+            pages: (0..slots.div_ceil(PAGE_SLOTS)).map(|_| None).collect(),
+        }
+    }
+
+    #[inline]
+    fn get_or_decode(&mut self, slot: usize, opcode: u32, decode: impl FnOnce() -> T) -> T {
+        let page = match &mut self.pages[slot / PAGE_SLOTS] {
+            Some(page) => page,
+            empty => empty.insert(self.blank.clone()),
+        };
+        let entry = &mut page[slot % PAGE_SLOTS];
+        if entry.0 != opcode {
+            *entry = (opcode, decode());
+        }
+        entry.1
+    }
+}
+
+pub(crate) struct DecodeCache {
+    arm: Pages<ArmInstr>,
+    thumb: Pages<ThumbInstr>,
+}
+
+impl DecodeCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            arm: Pages::new((CODE_LEN / 4) as usize, CPU::arm_decode(0)),
+            thumb: Pages::new((CODE_LEN / 2) as usize, CPU::thumb_decode(0)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn arm(&mut self, addr: u32, inst: u32) -> ArmInstr {
+        match code_offset(addr) {
+            Some(offset) => self
+                .arm
+                .get_or_decode((offset / 4) as usize, inst, || CPU::arm_decode(inst)),
+            None => CPU::arm_decode(inst),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn thumb(&mut self, addr: u32, inst: u16) -> ThumbInstr {
+        match code_offset(addr) {
+            Some(offset) => self
+                .thumb
+                .get_or_decode((offset / 2) as usize, inst as u32, || CPU::thumb_decode(inst)),
+            None => CPU::thumb_decode(inst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_entry_is_replaced_when_opcode_changes() {
+        let mut cache = DecodeCache::new();
+        let mov_r0 = 0xE3A0_0001;
+        let mov_r1 = 0xE3A0_1001;
+        let first = cache.arm(0x0300_0000, mov_r0);
+        assert_eq!(cache.arm(0x0300_8000, mov_r0), first, "IWRAM mirror shares a slot");
+        let rewritten = cache.arm(0x0300_0000, mov_r1);
+        assert_eq!(rewritten, CPU::arm_decode(mov_r1));
+        assert_ne!(rewritten, first);
+
+        assert_eq!(cache.thumb(0x0000_0100, 0x2001), CPU::thumb_decode(0x2001), "BIOS is not cached");
+        assert!(cache.thumb.pages.iter().all(Option::is_none));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::bus::Bus;
+use crate::{bios::hle, bus::Bus};
 
 use super::{
     cpu::{CPU, PC_INDEX},
@@ -41,6 +41,14 @@ impl Exception {
 }
 
 impl CPU {
+    pub(crate) fn software_interrupt(&mut self, bus: &mut impl Bus, comment: u8, next_address: u32) {
+        bus.notify_swi(comment);
+        if bus.hle_bios() && hle::software_interrupt(self, bus, comment, next_address) {
+            return;
+        }
+        self.enter_exception(bus, Exception::SoftwareInterrupt, next_address);
+    }
+
     pub(crate) fn enter_exception(&mut self, bus: &mut impl Bus, exception: Exception, next_address: u32) {
         if self.setup_exception(exception, next_address) {
             self.flush_pipeline(bus);
@@ -5,6 +5,7 @@ pub mod cpu;
 pub mod psr;
 
 pub mod exception;
+mod decode;
 mod modes;
 
 #[cfg(test)]
@@ -12,113 +12,273 @@ use crate::{
     },
 };
 
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ArmInstr {
+    BX {
+        rn: u8,
+    },
+    B_BL {
+        l: bool,
+        offset: u32,
+    },
+    MUL_MLA {
+        a: bool,
+        s: bool,
+        rd: u8,
+        rn: u8,
+        rs: u8,
+        rm: u8,
+    },
+    UMULL_UMLAL_SMULL_SMLAL {
+        u: bool,
+        a: bool,
+        s: bool,
+        rd_hi: u8,
+        rd_lo: u8,
+        rs: u8,
+        rm: u8,
+    },
+    MRS {
+        p: bool,
+        rd: u8,
+    },
+    MSR {
+        i: bool,
+        p: bool,
+        field_mask: u8,
+        op: u16,
+    },
+    LDM_STM {
+        l: bool,
+        p: bool,
+        u: bool,
+        s: bool,
+        w: bool,
+        rn: u8,
+        r_list: u16,
+    },
+    SWP {
+        b: bool,
+        rn: u8,
+        rd: u8,
+        rm: u8,
+    },
+    LDRH_LDRSB_LDRSH_STRH {
+        p: bool,
+        u: bool,
+        i: bool,
+        w: bool,
+        l: bool,
+        rn: u8,
+        rd: u8,
+        offset_hi: u8,
+        s: bool,
+        h: bool,
+        offset_lo: u8,
+    },
+    DataProc {
+        i: bool,
+        opcode: u8,
+        s: bool,
+        rn: u8,
+        rd: u8,
+        operand2: u16,
+    },
+    LDR_STR {
+        l: bool,
+        i: bool,
+        p: bool,
+        u: bool,
+        b: bool,
+        w: bool,
+        rn: u8,
+        rd: u8,
+        offset: u16,
+    },
+    SWI {
+        comment: u8,
+    },
+    Undefined,
+}
+
 #[allow(non_camel_case_types, clippy::too_many_arguments)]
 impl CPU {
     #[bitmatch]
-    pub(crate) fn arm_opcodes(&mut self, bus: &mut impl Bus, inst: u32) {
-        if !self.condition_check(inst.bit_range(28..32) as usize) {
-            return;
-        }
-
+    pub(crate) fn arm_decode(inst: u32) -> ArmInstr {
         #[bitmatch]
         match inst.bit_range(0..28) {
-            "0001_0010_1111_1111_1111_0001_????" => self.arm_BX(bus, bit_r!(inst, 0..4)),
-            "101?_????_????_????_????_????_????" => {
-                self.arm_B_BL(bus, inst.bit(24), bit_r!(inst, 0..24))
-            }
-            "0000_00??_????_????_????_1001_????" => self.arm_MUL_MLA(
-                bus,
-                inst.bit(21),
-                inst.bit(20),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 12..16),
-                bit_r!(inst, 8..12),
-                bit_r!(inst, 0..4),
-            ),
+            "0001_0010_1111_1111_1111_0001_????" => ArmInstr::BX {
+                rn: bit_r!(inst, 0..4) as u8,
+            },
+            "101?_????_????_????_????_????_????" => ArmInstr::B_BL {
+                l: inst.bit(24),
+                offset: inst.bit_range(0..24),
+            },
+            "0000_00??_????_????_????_1001_????" => ArmInstr::MUL_MLA {
+                a: inst.bit(21),
+                s: inst.bit(20),
+                rd: bit_r!(inst, 16..20) as u8,
+                rn: bit_r!(inst, 12..16) as u8,
+                rs: bit_r!(inst, 8..12) as u8,
+                rm: bit_r!(inst, 0..4) as u8,
+            },
+
+            "0000_1???_????_????_????_1001_????" => ArmInstr::UMULL_UMLAL_SMULL_SMLAL {
+                u: inst.bit(22),
+                a: inst.bit(21),
+                s: inst.bit(20),
+                rd_hi: bit_r!(inst, 16..20) as u8,
+                rd_lo: bit_r!(inst, 12..16) as u8,
+                rs: bit_r!(inst, 8..12) as u8,
+                rm: bit_r!(inst, 0..4) as u8,
+            },
+
+            "0001_0?00_1111_????_0000_0000_0000" => ArmInstr::MRS {
+                p: inst.bit(22),
+                rd: bit_r!(inst, 12..16) as u8,
+            },
+            "00?1_0?10_????_1111_????_????_????" => ArmInstr::MSR {
+                i: inst.bit(25),
+                p: inst.bit(22),
+                field_mask: bit_r!(inst, 16..20) as u8,
+                op: bit_r!(inst, 0..12) as u16,
+            },
+
+            "011?_????_????_????_????_???1_????" => ArmInstr::Undefined,
+            "100?_????_????_????_????_????_????" => ArmInstr::LDM_STM {
+                l: inst.bit(20),
+                p: inst.bit(24),
+                u: inst.bit(23),
+                s: inst.bit(22),
+                w: inst.bit(21),
+                rn: bit_r!(inst, 16..20) as u8,
+                r_list: bit_r!(inst, 0..16) as u16,
+            },
+
+            "0001_0?00_????_????_0000_1001_????" => ArmInstr::SWP {
+                b: inst.bit(22),
+                rn: bit_r!(inst, 16..20) as u8,
+                rd: bit_r!(inst, 12..16) as u8,
+                rm: bit_r!(inst, 0..4) as u8,
+            },
+            "000?_????_????_????_????_1??1_????" => ArmInstr::LDRH_LDRSB_LDRSH_STRH {
+                p: inst.bit(24),
+                u: inst.bit(23),
+                i: inst.bit(22),
+                w: inst.bit(21),
+                l: inst.bit(20),
+                rn: bit_r!(inst, 16..20) as u8,
+                rd: bit_r!(inst, 12..16) as u8,
+                offset_hi: bit_r!(inst, 8..12) as u8,
+                s: inst.bit(6),
+                h: inst.bit(5),
+                offset_lo: bit_r!(inst, 0..4) as u8,
+            },
+
+            "00??_????_????_????_????_????_????" => ArmInstr::DataProc {
+                i: inst.bit(25),
+                opcode: bit_r!(inst, 21..25) as u8,
+                s: inst.bit(20),
+                rn: bit_r!(inst, 16..20) as u8,
+                rd: bit_r!(inst, 12..16) as u8,
+                operand2: bit_r!(inst, 0..12) as u16,
+            },
+            "01??_????_????_????_????_????_????" => ArmInstr::LDR_STR {
+                l: inst.bit(20),
+                i: inst.bit(25),
+                p: inst.bit(24),
+                u: inst.bit(23),
+                b: inst.bit(22),
+                w: inst.bit(21),
+                rn: bit_r!(inst, 16..20) as u8,
+                rd: bit_r!(inst, 12..16) as u8,
+                offset: bit_r!(inst, 0..12) as u16,
+            },
+
+            "1111_????_????_????_????_????_????" => ArmInstr::SWI {
+                comment: bit_r!(inst, 16..24) as u8,
+            },
+            _ => ArmInstr::Undefined,
+        }
+    }
 
-            "0000_1???_????_????_????_1001_????" => self.arm_UMULL_UMLAL_SMULL_SMLAL(
+    pub(crate) fn arm_execute(&mut self, bus: &mut impl Bus, instr: ArmInstr) {
+        match instr {
+            ArmInstr::BX { rn } => self.arm_BX(bus, rn as usize),
+            ArmInstr::B_BL { l, offset } => self.arm_B_BL(bus, l, offset as usize),
+            ArmInstr::MUL_MLA { a, s, rd, rn, rs, rm } => self.arm_MUL_MLA(
                 bus,
-                inst.bit(22),
-                inst.bit(21),
-                inst.bit(20),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 12..16),
-                bit_r!(inst, 8..12),
-                bit_r!(inst, 0..4),
+                a,
+                s,
+                rd as usize,
+                rn as usize,
+                rs as usize,
+                rm as usize,
             ),
-
-            "0001_0?00_1111_????_0000_0000_0000" => {
-                self.arm_MRS(inst.bit(22), bit_r!(inst, 12..16))
+            ArmInstr::UMULL_UMLAL_SMULL_SMLAL { u, a, s, rd_hi, rd_lo, rs, rm } => self
+                .arm_UMULL_UMLAL_SMULL_SMLAL(
+                    bus,
+                    u,
+                    a,
+                    s,
+                    rd_hi as usize,
+                    rd_lo as usize,
+                    rs as usize,
+                    rm as usize,
+                ),
+            ArmInstr::MRS { p, rd } => self.arm_MRS(p, rd as usize),
+            ArmInstr::MSR { i, p, field_mask, op } => {
+                self.arm_MSR(i, p, field_mask as usize, op as usize)
             }
-            "00?1_0?10_????_1111_????_????_????" => self.arm_MSR(
-                inst.bit(25),
-                inst.bit(22),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 0..12),
-            ),
-
-            "011?_????_????_????_????_???1_????" => {
-                self.enter_exception(bus, Exception::Undefined, self.arm_pc().wrapping_add(4))
+            ArmInstr::LDM_STM { l, p, u, s, w, rn, r_list } => {
+                self.arm_LDM_STM(bus, l, p, u, s, w, rn as usize, r_list)
             }
-            "100?_????_????_????_????_????_????" => self.arm_LDM_STM(
-                bus,
-                inst.bit(20),
-                inst.bit(24),
-                inst.bit(23),
-                inst.bit(22),
-                inst.bit(21),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 0..16) as u16,
-            ),
-
-            "0001_0?00_????_????_0000_1001_????" => self.arm_SWP(
-                bus,
-                inst.bit(22),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 12..16),
-                bit_r!(inst, 0..4),
-            ),
-            "000?_????_????_????_????_1??1_????" => self.arm_LDRH_LDRSB_LDRSH_STRH(
-                bus,
-                inst.bit(24),
-                inst.bit(23),
-                inst.bit(22),
-                inst.bit(21),
-                inst.bit(20),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 12..16),
-                bit_r!(inst, 8..12),
-                inst.bit(6),
-                inst.bit(5),
-                bit_r!(inst, 0..4),
-            ),
-
-            "00??_????_????_????_????_????_????" => self.arm_data_proc(
+            ArmInstr::SWP { b, rn, rd, rm } => {
+                self.arm_SWP(bus, b, rn as usize, rd as usize, rm as usize)
+            }
+            ArmInstr::LDRH_LDRSB_LDRSH_STRH { p, u, i, w, l, rn, rd, offset_hi, s, h, offset_lo } => self
+                .arm_LDRH_LDRSB_LDRSH_STRH(
+                    bus,
+                    p,
+                    u,
+                    i,
+                    w,
+                    l,
+                    rn as usize,
+                    rd as usize,
+                    offset_hi as usize,
+                    s,
+                    h,
+                    offset_lo as usize,
+                ),
+            ArmInstr::DataProc { i, opcode, s, rn, rd, operand2 } => self.arm_data_proc(
                 bus,
-                inst.bit(25),
-                bit_r!(inst, 21..25),
-                inst.bit(20),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 12..16),
-                bit_r!(inst, 0..12),
+                i,
+                opcode as usize,
+                s,
+                rn as usize,
+                rd as usize,
+                operand2 as usize,
             ),
-            "01??_????_????_????_????_????_????" => self.arm_LDR_STR(
+            ArmInstr::LDR_STR { l, i, p, u, b, w, rn, rd, offset } => self.arm_LDR_STR(
                 bus,
-                inst.bit(20),
-                inst.bit(25),
-                inst.bit(24),
-                inst.bit(23),
-                inst.bit(22),
-                inst.bit(21),
-                bit_r!(inst, 16..20),
-                bit_r!(inst, 12..16),
-                bit_r!(inst, 0..12),
+                l,
+                i,
+                p,
+                u,
+                b,
+                w,
+                rn as usize,
+                rd as usize,
+                offset as usize,
             ),
-
-            "1111_????_????_????_????_????_????" => {
-                self.enter_exception(bus, Exception::SoftwareInterrupt, self.arm_pc().wrapping_add(4))
+            ArmInstr::SWI { comment } => {
+                self.software_interrupt(bus, comment, self.arm_pc().wrapping_add(4))
+            }
+            ArmInstr::Undefined => {
+                self.enter_exception(bus, Exception::Undefined, self.arm_pc().wrapping_add(4))
             }
-            _ => self.enter_exception(bus, Exception::Undefined, self.arm_pc().wrapping_add(4)),
         }
     }
 
@@ -296,6 +456,7 @@ impl CPU {
         let c = bus.access_cycles(addr, width);
         bus.tick(c);
 
+        bus.notify_access(addr, width, !l);
         if l {
             if t_bit {
                 self.set_mode(OperatingMode::usr);
@@ -379,6 +540,7 @@ impl CPU {
         let width = if h { 2 } else { 1 };
         let c = bus.access_cycles(addr, width);
         bus.tick(c);
+        bus.notify_access(addr, width, !l);
 
         if l {
             let loaded = if h {
@@ -470,6 +632,7 @@ impl CPU {
             if xfer_list.bit(r) {
                 let c = bus.access_cycles(addr, 4);
                 bus.tick(c);
+                bus.notify_access(addr, 4, !l);
                 if l {
                     self.reg[r] = bus.read_word(addr);
                 } else {
@@ -604,6 +767,7 @@ impl CPU {
         let width = if b { 1 } else { 4 };
         let c = bus.access_cycles(swap_address, width);
         bus.tick(c);
+        bus.notify_access(swap_address, width, false);
 
         let loaded = if b {
             bus.read_byte(swap_address) as u32
@@ -614,6 +778,7 @@ impl CPU {
 
         let c = bus.access_cycles(swap_address, width);
         bus.tick(c);
+        bus.notify_access(swap_address, width, true);
 
         if b {
             bus.write_byte(swap_address, rm_val as u8);
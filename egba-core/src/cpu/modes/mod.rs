@@ -8,6 +8,9 @@ use super::cpu::PC_INDEX;
 mod arm;
 mod thumb;
 
+pub(crate) use arm::ArmInstr;
+pub(crate) use thumb::ThumbInstr;
+
 #[macro_export]
 macro_rules! bit_r {
     ($instr:expr, $range:expr) => {
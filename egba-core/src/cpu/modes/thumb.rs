@@ -10,107 +10,189 @@ use crate::{
     },
 };
 
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ThumbInstr {
+    Format1 { op: u8, offset: u8, rs: u8, rd: u8 },
+    Format2 { i: bool, sub: bool, offset: u8, rs: u8, rd: u8 },
+    Format3 { op: u8, rd: u8, offset: u8 },
+    Format4 { opcode: u8, rs: u8, rd: u8 },
+    Format5 { op: u8, h1: bool, h2: bool, rs: u8, rd: u8 },
+    Format6 { rd: u8, offset: u8 },
+    Format7 { l: bool, b: bool, ro: u8, rb: u8, rd: u8 },
+    Format8 { h: bool, s: bool, ro: u8, rb: u8, rd: u8 },
+    Format9 { b: bool, l: bool, offset: u8, rb: u8, rd: u8 },
+    Format10 { l: bool, offset: u8, rb: u8, rd: u8 },
+    Format11 { l: bool, rd: u8, offset: u8 },
+    Format12 { sp: bool, rd: u8, offset: u8 },
+    Format13 { s: bool, offset: u8 },
+    Format14 { l: bool, r: bool, r_list: u8 },
+    Format15 { l: bool, rb: u8, r_list: u8 },
+    Format16 { cond: u8, offset: u8 },
+    Format18 { offset: u16 },
+    Format19 { hi: bool, offset: u16 },
+    SWI { comment: u8 },
+    Undefined,
+}
+
 impl CPU {
     #[bitmatch]
-    pub(crate) fn thumb_opcodes(&mut self, bus: &mut impl Bus, inst: u16) {
+    pub(crate) fn thumb_decode(inst: u16) -> ThumbInstr {
         #[bitmatch]
         match inst.bit_range(0..16) {
-            "0001_1???_????_????" => self.thumb_format2(
-                inst.bit(10),
-                inst.bit(9),
-                bit_r!(inst, 6..9),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-            "000?_????_????_????" => self.thumb_format1(
-                bit_r!(inst, 11..13),
-                bit_r!(inst, 6..11),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-            "001?_????_????_????" => self.thumb_format3(
-                bit_r!(inst, 11..13),
-                bit_r!(inst, 8..11),
-                bit_r!(inst, 0..8) as u8,
-            ),
-
-            "0100_00??_????_????" => {
-                self.thumb_format4(bus, bit_r!(inst, 6..10), bit_r!(inst, 3..6), bit_r!(inst, 0..3))
+            "0001_1???_????_????" => ThumbInstr::Format2 {
+                i: inst.bit(10),
+                sub: inst.bit(9),
+                offset: bit_r!(inst, 6..9) as u8,
+                rs: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "000?_????_????_????" => ThumbInstr::Format1 {
+                op: bit_r!(inst, 11..13) as u8,
+                offset: bit_r!(inst, 6..11) as u8,
+                rs: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "001?_????_????_????" => ThumbInstr::Format3 {
+                op: bit_r!(inst, 11..13) as u8,
+                rd: bit_r!(inst, 8..11) as u8,
+                offset: bit_r!(inst, 0..8) as u8,
+            },
+
+            "0100_00??_????_????" => ThumbInstr::Format4 {
+                opcode: bit_r!(inst, 6..10) as u8,
+                rs: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "0100_01??_????_????" => ThumbInstr::Format5 {
+                op: bit_r!(inst, 8..10) as u8,
+                h1: inst.bit(7),
+                h2: inst.bit(6),
+                rs: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "0100_1???_????_????" => ThumbInstr::Format6 {
+                rd: bit_r!(inst, 8..11) as u8,
+                offset: bit_r!(inst, 0..8) as u8,
+            },
+
+            "0101_??0?_????_????" => ThumbInstr::Format7 {
+                l: inst.bit(11),
+                b: inst.bit(10),
+                ro: bit_r!(inst, 6..9) as u8,
+                rb: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "0101_??1?_????_????" => ThumbInstr::Format8 {
+                h: inst.bit(11),
+                s: inst.bit(10),
+                ro: bit_r!(inst, 6..9) as u8,
+                rb: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "011?_????_????_????" => ThumbInstr::Format9 {
+                b: inst.bit(12),
+                l: inst.bit(11),
+                offset: bit_r!(inst, 6..11) as u8,
+                rb: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+
+            "1000_????_????_????" => ThumbInstr::Format10 {
+                l: inst.bit(11),
+                offset: bit_r!(inst, 6..11) as u8,
+                rb: bit_r!(inst, 3..6) as u8,
+                rd: bit_r!(inst, 0..3) as u8,
+            },
+            "1001_????_????_????" => ThumbInstr::Format11 {
+                l: inst.bit(11),
+                rd: bit_r!(inst, 8..11) as u8,
+                offset: bit_r!(inst, 0..8) as u8,
+            },
+            "1010_????_????_????" => ThumbInstr::Format12 {
+                sp: inst.bit(11),
+                rd: bit_r!(inst, 8..11) as u8,
+                offset: bit_r!(inst, 0..8) as u8,
+            },
+
+            "1011_0000_????_????" => ThumbInstr::Format13 {
+                s: inst.bit(7),
+                offset: bit_r!(inst, 0..7) as u8,
+            },
+            "1011_?10?_????_????" => ThumbInstr::Format14 {
+                l: inst.bit(11),
+                r: inst.bit(8),
+                r_list: bit_r!(inst, 0..8) as u8,
+            },
+
+            "1100_????_????_????" => ThumbInstr::Format15 {
+                l: inst.bit(11),
+                rb: bit_r!(inst, 8..11) as u8,
+                r_list: bit_r!(inst, 0..8) as u8,
+            },
+            "1101_1111_????_????" => ThumbInstr::SWI {
+                comment: bit_r!(inst, 0..8) as u8,
+            },
+            "1101_????_????_????" => ThumbInstr::Format16 {
+                cond: bit_r!(inst, 8..12) as u8,
+                offset: bit_r!(inst, 0..8) as u8,
+            },
+
+            "1110_0???_????_????" => ThumbInstr::Format18 {
+                offset: bit_r!(inst, 0..11) as u16,
+            },
+            "1111_????_????_????" => ThumbInstr::Format19 {
+                hi: inst.bit(11),
+                offset: bit_r!(inst, 0..11) as u16,
+            },
+            _ => ThumbInstr::Undefined,
+        }
+    }
+
+    pub(crate) fn thumb_execute(&mut self, bus: &mut impl Bus, instr: ThumbInstr) {
+        match instr {
+            ThumbInstr::Format1 { op, offset, rs, rd } => {
+                self.thumb_format1(op as usize, offset as usize, rs as usize, rd as usize)
             }
-            "0100_01??_????_????" => self.thumb_format5(
-                bus,
-                bit_r!(inst, 8..10),
-                inst.bit(7),
-                inst.bit(6),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-            "0100_1???_????_????" => {
-                self.thumb_format6(bus, bit_r!(inst, 8..11), bit_r!(inst, 0..8) as u32)
+            ThumbInstr::Format2 { i, sub, offset, rs, rd } => {
+                self.thumb_format2(i, sub, offset as usize, rs as usize, rd as usize)
             }
-
-            "0101_??0?_????_????" => self.thumb_format7(
-                bus,
-                inst.bit(11),
-                inst.bit(10),
-                bit_r!(inst, 6..9),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-            "0101_??1?_????_????" => self.thumb_format8(
-                bus,
-                inst.bit(11),
-                inst.bit(10),
-                bit_r!(inst, 6..9),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-            "011?_????_????_????" => self.thumb_format9(
-                bus,
-                inst.bit(12),
-                inst.bit(11),
-                bit_r!(inst, 6..11),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-
-            "1000_????_????_????" => self.thumb_format10(
-                bus,
-                inst.bit(11),
-                bit_r!(inst, 6..11),
-                bit_r!(inst, 3..6),
-                bit_r!(inst, 0..3),
-            ),
-            "1001_????_????_????" => {
-                self.thumb_format11(bus, inst.bit(11), bit_r!(inst, 8..11), bit_r!(inst, 0..8))
+            ThumbInstr::Format3 { op, rd, offset } => self.thumb_format3(op as usize, rd as usize, offset),
+            ThumbInstr::Format4 { opcode, rs, rd } => {
+                self.thumb_format4(bus, opcode as usize, rs as usize, rd as usize)
             }
-            "1010_????_????_????" => {
-                self.thumb_format12(inst.bit(11), bit_r!(inst, 8..11), bit_r!(inst, 0..8) as u32)
+            ThumbInstr::Format5 { op, h1, h2, rs, rd } => {
+                self.thumb_format5(bus, op as usize, h1, h2, rs as usize, rd as usize)
             }
-
-            "1011_0000_????_????" => self.thumb_format13(inst.bit(7), bit_r!(inst, 0..7) as u32),
-            "1011_?10?_????_????" => {
-                self.thumb_format14(bus, inst.bit(11), inst.bit(8), bit_r!(inst, 0..8) as u16)
+            ThumbInstr::Format6 { rd, offset } => self.thumb_format6(bus, rd as usize, offset as u32),
+            ThumbInstr::Format7 { l, b, ro, rb, rd } => {
+                self.thumb_format7(bus, l, b, ro as usize, rb as usize, rd as usize)
             }
-
-            "1100_????_????_????" => self.thumb_format15(
-                bus,
-                inst.bit(11),
-                bit_r!(inst, 8..11),
-                bit_r!(inst, 0..8) as u8,
-            ),
-            "1101_1111_????_????" => self.enter_exception(
-                bus,
-                Exception::SoftwareInterrupt,
-                self.thumb_pc().wrapping_add(2),
-            ),
-            "1101_????_????_????" => {
-                self.thumb_format16(bus, bit_r!(inst, 8..12), bit_r!(inst, 0..8) as u8)
+            ThumbInstr::Format8 { h, s, ro, rb, rd } => {
+                self.thumb_format8(bus, h, s, ro as usize, rb as usize, rd as usize)
+            }
+            ThumbInstr::Format9 { b, l, offset, rb, rd } => {
+                self.thumb_format9(bus, b, l, offset as usize, rb as usize, rd as usize)
+            }
+            ThumbInstr::Format10 { l, offset, rb, rd } => {
+                self.thumb_format10(bus, l, offset as usize, rb as usize, rd as usize)
+            }
+            ThumbInstr::Format11 { l, rd, offset } => {
+                self.thumb_format11(bus, l, rd as usize, offset as usize)
+            }
+            ThumbInstr::Format12 { sp, rd, offset } => self.thumb_format12(sp, rd as usize, offset as u32),
+            ThumbInstr::Format13 { s, offset } => self.thumb_format13(s, offset as u32),
+            ThumbInstr::Format14 { l, r, r_list } => self.thumb_format14(bus, l, r, r_list as u16),
+            ThumbInstr::Format15 { l, rb, r_list } => self.thumb_format15(bus, l, rb as usize, r_list),
+            ThumbInstr::Format16 { cond, offset } => self.thumb_format16(bus, cond as usize, offset),
+            ThumbInstr::Format18 { offset } => self.thumb_format18(bus, offset as usize),
+            ThumbInstr::Format19 { hi, offset } => self.thumb_format19(bus, hi, offset as usize),
+            ThumbInstr::SWI { comment } => {
+                self.software_interrupt(bus, comment, self.thumb_pc().wrapping_add(2))
+            }
+            ThumbInstr::Undefined => {
+                self.enter_exception(bus, Exception::Undefined, self.thumb_pc().wrapping_add(2))
             }
-
-            "1110_0???_????_????" => self.thumb_format18(bus, bit_r!(inst, 0..11)),
-            "1111_????_????_????" => self.thumb_format19(bus, inst.bit(11), bit_r!(inst, 0..11)),
-            _ => self.enter_exception(bus, Exception::Undefined, self.thumb_pc().wrapping_add(2)),
         }
     }
 
@@ -247,6 +329,7 @@ impl CPU {
         let addr = (self.reg[PC_INDEX] & !0b10).wrapping_add(offset << 2);
         let c = bus.access_cycles(addr, 4);
         bus.tick(c);
+        bus.notify_access(addr, 4, false);
         self.reg[rd] = bus.read_word(addr);
         bus.tick(1);
 
@@ -0,0 +1,182 @@
+#[cfg(feature = "debug-hooks")]
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[cfg(feature = "debug-hooks")]
+impl WatchKind {
+    fn matches(self, write: bool) -> bool {
+        match self {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    Breakpoint(u32),
+    Watchpoint { addr: u32, kind: WatchKind },
+    Irq,
+    SoftwareInterrupt(u8),
+}
+
+#[cfg(feature = "debug-hooks")]
+#[derive(Default)]
+pub struct DebugHooks {
+    breakpoints: Vec<u32>,
+    watchpoints: Vec<(Range<u32>, WatchKind)>,
+    swi_breakpoints: Vec<Option<u8>>,
+    break_on_irq: bool,
+    resume_pc: Option<u32>,
+    pub(crate) hit: Option<StopReason>,
+}
+
+#[cfg(feature = "debug-hooks")]
+impl DebugHooks {
+    pub fn add_breakpoint(&mut self, addr: u32) {
+        if !self.breakpoints.contains(&addr) {
+            self.breakpoints.push(addr);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u32) {
+        self.breakpoints.retain(|&bp| bp != addr);
+    }
+
+    pub fn breakpoints(&self) -> &[u32] {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, range: Range<u32>, kind: WatchKind) {
+        self.watchpoints.push((range, kind));
+    }
+
+    pub fn remove_watchpoint(&mut self, range: Range<u32>, kind: WatchKind) {
+        self.watchpoints.retain(|(r, k)| *r != range || *k != kind);
+    }
+
+    pub fn add_swi_breakpoint(&mut self, comment: Option<u8>) {
+        if !self.swi_breakpoints.contains(&comment) {
+            self.swi_breakpoints.push(comment);
+        }
+    }
+
+    pub fn remove_swi_breakpoint(&mut self, comment: Option<u8>) {
+        self.swi_breakpoints.retain(|&c| c != comment);
+    }
+
+    pub fn set_break_on_irq(&mut self, enabled: bool) {
+        self.break_on_irq = enabled;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(crate) fn check_pc(&mut self, pc: u32) -> bool {
+        if self.resume_pc.take() == Some(pc) || !self.breakpoints.contains(&pc) {
+            return false;
+        }
+        self.resume_pc = Some(pc);
+        self.hit = Some(StopReason::Breakpoint(pc));
+        true
+    }
+
+    pub(crate) fn skip_pc(&mut self, pc: u32) {
+        self.resume_pc = Some(pc);
+    }
+
+    pub(crate) fn on_access(&mut self, addr: u32, width: u32, write: bool) {
+        if self.hit.is_some() {
+            return;
+        }
+        let end = addr.wrapping_add(width);
+        let kind = self
+            .watchpoints
+            .iter()
+            .filter(|(r, k)| k.matches(write) && addr < r.end && r.start < end)
+            .map(|&(_, k)| k)
+            .min_by_key(|&k| k == WatchKind::Access);
+        if let Some(kind) = kind {
+            self.hit = Some(StopReason::Watchpoint { addr, kind });
+        }
+    }
+
+    pub(crate) fn on_swi(&mut self, comment: u8) {
+        if self.hit.is_none()
+            && self
+                .swi_breakpoints
+                .iter()
+                .any(|&c| c.is_none() || c == Some(comment))
+        {
+            self.hit = Some(StopReason::SoftwareInterrupt(comment));
+        }
+    }
+
+    pub(crate) fn on_irq(&mut self) {
+        if self.break_on_irq && self.hit.is_none() {
+            self.hit = Some(StopReason::Irq);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "debug-hooks"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchpoint_matches_overlapping_access_of_its_kind() {
+        let mut hooks = DebugHooks::default();
+        hooks.add_watchpoint(0x0300_0004..0x0300_0008, WatchKind::Write);
+
+        hooks.on_access(0x0300_0004, 4, false);
+        assert_eq!(hooks.hit, None, "reads don't trip a write watchpoint");
+        hooks.on_access(0x0300_0000, 4, true);
+        assert_eq!(hooks.hit, None, "adjacent word doesn't overlap");
+        hooks.on_access(0x0300_0007, 1, true);
+        assert_eq!(
+            hooks.hit,
+            Some(StopReason::Watchpoint { addr: 0x0300_0007, kind: WatchKind::Write })
+        );
+
+        hooks.hit = None;
+        hooks.add_watchpoint(0x0300_0004..0x0300_0008, WatchKind::Access);
+        hooks.on_access(0x0300_0004, 4, false);
+        assert_eq!(
+            hooks.hit,
+            Some(StopReason::Watchpoint { addr: 0x0300_0004, kind: WatchKind::Access }),
+            "reports the kind of the watchpoint that fired"
+        );
+    }
+
+    #[test]
+    fn breakpoint_is_skipped_once_after_it_stops() {
+        let mut hooks = DebugHooks::default();
+        hooks.add_breakpoint(0x0800_0000);
+        assert!(hooks.check_pc(0x0800_0000));
+        assert!(!hooks.check_pc(0x0800_0000), "resuming executes the instruction");
+        assert!(hooks.check_pc(0x0800_0000), "next visit stops again");
+    }
+
+    #[test]
+    fn swi_breakpoint_any_or_specific() {
+        let mut hooks = DebugHooks::default();
+        hooks.add_swi_breakpoint(Some(0x05));
+        hooks.on_swi(0x06);
+        assert_eq!(hooks.hit, None);
+        hooks.on_swi(0x05);
+        assert_eq!(hooks.hit, Some(StopReason::SoftwareInterrupt(0x05)));
+
+        hooks.hit = None;
+        hooks.add_swi_breakpoint(None);
+        hooks.on_swi(0x0B);
+        assert_eq!(hooks.hit, Some(StopReason::SoftwareInterrupt(0x0B)));
+    }
+}
@@ -1,6 +1,10 @@
 use bit::BitIndex;
 
-use crate::{bus::Bus, control::InterruptType};
+use crate::{
+    bus::Bus,
+    control::InterruptType,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 #[derive(Clone, Copy, PartialEq)]
 pub(crate) enum DmaEvent {
@@ -311,6 +315,49 @@ impl Bus for Dma {
     }
 }
 
+pub(crate) trait DmaMemory {
+    fn dma_read_hword(&self, addr: u32) -> u16;
+    fn dma_read_word(&self, addr: u32) -> u32;
+    fn dma_write_hword(&mut self, addr: u32, val: u16);
+    fn dma_write_word(&mut self, addr: u32, val: u32);
+    fn dma_access_cycles(&mut self, addr: u32, width: u32) -> u32 { let _ = (addr, width); 1 }
+    fn dma_tick(&mut self, cycles: u32) { let _ = cycles; }
+}
+
+impl Snapshot for DmaChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.src.save_state(w);
+        self.dst.save_state(w);
+        self.count.save_state(w);
+        self.control.save_state(w);
+        self.internal_src.save_state(w);
+        self.internal_dst.save_state(w);
+        self.internal_count.save_state(w);
+        self.running.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.src.load_state(r)?;
+        self.dst.load_state(r)?;
+        self.count.load_state(r)?;
+        self.control.load_state(r)?;
+        self.internal_src.load_state(r)?;
+        self.internal_dst.load_state(r)?;
+        self.internal_count.load_state(r)?;
+        self.running.load_state(r)
+    }
+}
+
+impl Snapshot for Dma {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.channels.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.channels.load_state(r)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -505,12 +552,3 @@ mod tests {
         );
     }
 }
-
-pub(crate) trait DmaMemory {
-    fn dma_read_hword(&self, addr: u32) -> u16;
-    fn dma_read_word(&self, addr: u32) -> u32;
-    fn dma_write_hword(&mut self, addr: u32, val: u16);
-    fn dma_write_word(&mut self, addr: u32, val: u32);
-    fn dma_access_cycles(&mut self, addr: u32, width: u32) -> u32 { let _ = (addr, width); 1 }
-    fn dma_tick(&mut self, cycles: u32) { let _ = cycles; }
-}
@@ -1,18 +1,31 @@
+use std::ops::Range;
+
+#[cfg(feature = "debug-hooks")]
+use crate::debug::DebugHooks;
 use crate::{
+    apu,
     bios::Bios,
     bus::Bus,
     cartridge::Cartridge,
     control::{InterruptType, PowerMode},
     cpu::{
         cpu::{CPU, PC_INDEX, SP_INDEX},
-        psr::{OperatingMode, OperatingState},
+        psr::{OperatingMode, OperatingState, ProgramStatusRegister},
     },
+    debug::StopReason,
     dma::{Dma, DmaEvent},
+    link::LinkTransport,
     memory::Memory,
+    rewind::RewindBuffer,
+    scheduler::Event,
+    state::{Snapshot, StateError, StateReader, StateWriter},
     video::VideoEvent,
 };
 
+pub const CPU_CLOCK: u32 = 16_777_216;
 pub const CYCLES_PER_FRAME: u32 = 280896;
+pub const AUDIO_SAMPLE_RATE: u32 = apu::SAMPLE_RATE;
+pub const AUDIO_TAP_NAMES: [&str; apu::TAP_COUNT] = apu::TAP_NAMES;
 pub const FB_WIDTH: usize = 240;
 pub const FB_HEIGHT: usize = 160;
 
@@ -23,22 +36,41 @@ pub struct FrameProfile {
     pub halt_steps: u64,
 }
 
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Renderer {
+    #[default]
+    Scanline,
+    Dot,
+}
+
 pub struct GBA {
     cpu: CPU,
     memory: Memory,
+    frame_target: Option<u64>,
+    rewind: Option<RewindBuffer>,
     pub last_profile: FrameProfile,
 }
 
 impl GBA {
     #[must_use]
     pub fn new(bios: Bios, cartridge: Cartridge) -> Self {
+        if bios.is_hle() {
+            return Self::new_skipping_bios(bios, cartridge);
+        }
+
         let mut cpu = CPU::new();
         let mut memory = Memory::new(bios, cartridge);
 
         cpu.pipeline[1] = cpu.fetch(&mut memory);
         cpu.pipeline[2] = cpu.fetch(&mut memory);
 
-        Self { cpu, memory, last_profile: FrameProfile::default() }
+        Self {
+            cpu,
+            memory,
+            frame_target: None,
+            rewind: None,
+            last_profile: FrameProfile::default(),
+        }
     }
 
     #[must_use]
@@ -69,14 +101,80 @@ impl GBA {
         cpu.pipeline[1] = cpu.fetch(&mut memory);
         cpu.pipeline[2] = cpu.fetch(&mut memory);
 
-        Self { cpu, memory, last_profile: FrameProfile::default() }
+        Self {
+            cpu,
+            memory,
+            frame_target: None,
+            rewind: None,
+            last_profile: FrameProfile::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_renderer(mut self, renderer: Renderer) -> Self {
+        self.memory.video.set_renderer(renderer);
+        self
     }
 
     pub fn get_cpu(&self) -> &CPU {
         &self.cpu
     }
 
+    pub fn pc(&self) -> u32 {
+        match self.cpu.cpsr.operating_state {
+            OperatingState::ARM => self.cpu.arm_pc(),
+            OperatingState::THUMB => self.cpu.thumb_pc(),
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.memory.system.get_power_mode() != PowerMode::Active
+    }
+
+    pub fn set_register(&mut self, index: usize, value: u32) {
+        if index == PC_INDEX {
+            self.cpu.reg[PC_INDEX] = value;
+            self.cpu.flush_pipeline(&mut self.memory);
+        } else {
+            self.cpu.reg[index] = value;
+        }
+    }
+
+    pub fn cpsr(&self) -> u32 {
+        self.cpu.cpsr.into()
+    }
+
+    pub fn set_cpsr(&mut self, value: u32) {
+        let pc = self.pc();
+        let psr: ProgramStatusRegister = value.into();
+        let state_changed = psr.operating_state != self.cpu.cpsr.operating_state;
+        self.cpu.set_mode(psr.mode);
+        self.cpu.cpsr = psr;
+        if state_changed {
+            self.set_register(PC_INDEX, pc);
+        }
+    }
+
     fn drain_events(&mut self) {
+        if self.memory.scheduler.next() > self.memory.bus_cycles {
+            return;
+        }
+        while let Some(event) = self.memory.scheduler.pop_due(self.memory.bus_cycles) {
+            match event {
+                Event::Video => self.run_video(),
+                Event::Dma => {
+                    let sound = std::mem::take(&mut self.memory.pending_sound_dma);
+                    if sound != 0 {
+                        self.run_dma(DmaEvent::Special);
+                    }
+                    self.run_dma(DmaEvent::Immediate);
+                }
+                Event::Timers | Event::Apu | Event::Serial => self.memory.sync_peripherals(),
+            }
+        }
+    }
+
+    fn run_video(&mut self) {
         let debt = std::mem::take(&mut self.memory.video_cycle_debt);
         if debt > 0 {
             let mut events = std::mem::take(&mut self.memory.video_events);
@@ -98,46 +196,97 @@ impl GBA {
             }
             self.memory.video_events = events;
         }
-
-        let sound = std::mem::take(&mut self.memory.pending_sound_dma);
-        if sound != 0 {
-            self.run_dma(DmaEvent::Special);
-        }
-
-        self.run_dma(DmaEvent::Immediate);
+        self.memory.schedule_video();
     }
 
-    pub fn step_one_instruction(&mut self) {
+    pub fn step_one_instruction(&mut self) -> Option<StopReason> {
+        #[cfg(feature = "debug-hooks")]
+        self.memory.debug.skip_pc(self.pc());
         let mut prof = FrameProfile::default();
         let cap = self.memory.bus_cycles.wrapping_add(1);
         self.tick_one(&mut prof, cap);
+        self.memory.sync_peripherals();
+        self.take_stop()
     }
 
-    pub fn run_frame(&mut self) {
+    pub fn run_frame(&mut self) -> Option<StopReason> {
         let start_cycles = self.memory.bus_cycles;
-        let target = start_cycles.wrapping_add(CYCLES_PER_FRAME as u64);
+        let target = self
+            .frame_target
+            .take()
+            .unwrap_or(start_cycles.wrapping_add(CYCLES_PER_FRAME as u64));
         let mut prof = FrameProfile::default();
+        let mut stop = None;
         while self.memory.bus_cycles < target {
             self.tick_one(&mut prof, target);
+            stop = self.take_stop();
+            if stop.is_some() {
+                self.frame_target = Some(target);
+                break;
+            }
         }
+        self.memory.sync_peripherals();
         prof.cycles = self.memory.bus_cycles.wrapping_sub(start_cycles);
         self.last_profile = prof;
+        if stop.is_none() && self.rewind.as_mut().is_some_and(RewindBuffer::frame_done) {
+            let state = self.save_state();
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.push(state);
+            }
+        }
+        stop
+    }
+
+    pub fn enable_rewind(&mut self, interval: u32, budget: usize) {
+        self.rewind = (budget > 0).then(|| RewindBuffer::new(interval, budget));
+    }
+
+    pub fn rewind(&mut self) -> bool {
+        let Some(state) = self.rewind.as_mut().and_then(RewindBuffer::pop) else {
+            return false;
+        };
+        self.frame_target = None;
+        self.clear_audio();
+        self.load_state(&state).is_ok()
+    }
+
+    pub fn rewind_snapshots(&self) -> usize {
+        self.rewind.as_ref().map_or(0, RewindBuffer::len)
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    pub fn debug_hooks(&mut self) -> &mut DebugHooks {
+        &mut self.memory.debug
+    }
+
+    #[inline]
+    fn take_stop(&mut self) -> Option<StopReason> {
+        #[cfg(feature = "debug-hooks")]
+        return self.memory.debug.hit.take();
+        #[cfg(not(feature = "debug-hooks"))]
+        None
     }
 
     fn tick_one(&mut self, prof: &mut FrameProfile, halt_batch_target: u64) {
         let power = self.memory.system.get_power_mode();
         if power == PowerMode::Active {
+            #[cfg(feature = "debug-hooks")]
+            if self.memory.debug.check_pc(self.pc()) {
+                return;
+            }
             let pc = self.cpu.reg[crate::cpu::cpu::PC_INDEX];
             self.memory.bios_readable = pc < 0x0000_4000;
             self.cpu.step(&mut self.memory);
             prof.instructions += 1;
         } else if power != PowerMode::Stop {
             let frame_left = halt_batch_target.saturating_sub(self.memory.bus_cycles) as u32;
-            let video_left = self.memory.video.cycles_to_next_event();
-            let mut batch = frame_left.min(video_left);
-            if let Some(t) = self.memory.timers.cycles_to_next_overflow() {
-                batch = batch.min(t);
-            }
+            let event_left = self
+                .memory
+                .scheduler
+                .next()
+                .saturating_sub(self.memory.bus_cycles)
+                .min(u32::MAX as u64) as u32;
+            let batch = frame_left.min(event_left);
             <Memory as Bus>::tick(&mut self.memory, batch.max(1));
             prof.halt_steps += 1;
         }
@@ -152,6 +301,8 @@ impl GBA {
             .step(&mut self.cpu, &mut self.memory.system);
         if irq_accepted {
             self.cpu.flush_pipeline(&mut self.memory);
+            #[cfg(feature = "debug-hooks")]
+            self.memory.debug.on_irq();
         }
     }
 
@@ -167,6 +318,10 @@ impl GBA {
         self.memory.read_word(addr)
     }
 
+    pub fn write_byte(&mut self, addr: u32, value: u8) {
+        self.memory.write_byte(addr, value);
+    }
+
     pub fn framebuffer(&self) -> &[u32] {
         self.memory.video.framebuffer()
     }
@@ -179,10 +334,64 @@ impl GBA {
         }
     }
 
+    pub fn set_light_level(&mut self, level: u8) {
+        self.memory.cartridge.set_light_level(level);
+    }
+
+    pub fn set_tilt(&mut self, x: i16, y: i16) {
+        self.memory.cartridge.set_tilt(x, y);
+    }
+
+    pub fn set_gyro(&mut self, rate: i16) {
+        self.memory.cartridge.set_gyro(rate);
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.memory.cartridge.rumble()
+    }
+
+    pub fn connect_link(&mut self, transport: Box<dyn LinkTransport>) {
+        self.memory.sync_peripherals();
+        self.memory.serial.connect(transport);
+        self.memory.schedule_peripherals();
+    }
+
+    pub fn disconnect_link(&mut self) {
+        self.memory.sync_peripherals();
+        self.memory.serial.disconnect();
+        self.memory.schedule_peripherals();
+    }
+
+    pub fn is_linked(&self) -> bool {
+        self.memory.serial.is_linked()
+    }
+
     pub fn audio_samples(&self) -> &[(i16, i16)] {
         self.memory.apu.samples()
     }
 
+    pub fn audio_sample_rate(&self) -> u32 {
+        self.memory.apu.sample_rate()
+    }
+
+    /// Splits the samples since the last `clear_audio` into runs of one sample rate, so a
+    /// SOUNDBIAS write in the middle of a frame does not relabel the samples before it.
+    pub fn audio_runs(&self) -> impl Iterator<Item = (u32, Range<usize>)> + '_ {
+        let changes = self.memory.apu.rate_changes();
+        let len = self.audio_samples().len();
+        changes.iter().enumerate().map(move |(i, &(start, rate))| {
+            (rate, start..changes.get(i + 1).map_or(len, |next| next.0))
+        })
+    }
+
+    pub fn enable_audio_taps(&mut self, enabled: bool) {
+        self.memory.apu.set_taps(enabled);
+    }
+
+    pub fn audio_taps(&self) -> &[[(i16, i16); apu::TAP_COUNT]] {
+        self.memory.apu.taps()
+    }
+
     pub fn clear_audio(&mut self) {
         self.memory.apu.clear_samples();
     }
@@ -195,6 +404,30 @@ impl GBA {
         self.memory.cartridge.save();
     }
 
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cpu.save_state(&mut w);
+        self.memory.save_state(&mut w);
+        w.finish()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let previous = self.save_state();
+        if let Err(e) = self.apply_state(data) {
+            self.apply_state(&previous)
+                .expect("restoring the pre-load state cannot fail");
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn apply_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(data)?;
+        self.cpu.load_state(&mut r)?;
+        self.memory.load_state(&mut r)?;
+        r.finish()
+    }
+
     fn run_dma(&mut self, event: DmaEvent) {
         if !self.memory.dma.any_running() {
             return;
@@ -272,6 +505,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn halt_sleeps_until_scheduled_timer_overflow() {
+        let mut gba = build_gba();
+        gba.memory.write_hword(0x0400_0100, 0xFF00);
+        gba.memory.write_byte(0x0400_0102, 0xC0);
+        gba.memory.write_byte(0x0400_0200, 0x08);
+        gba.memory.write_byte(0x0400_0208, 0x01);
+        gba.cpu.cpsr.irq_disable_bit = false;
+        gba.memory.write_byte(0x0400_0301, 0x00);
+        let start = gba.bus_cycles();
+        let mut prof = FrameProfile::default();
+        while gba.memory.system.get_power_mode() == PowerMode::Halt {
+            gba.tick_one(&mut prof, start + CYCLES_PER_FRAME as u64);
+        }
+        assert_eq!(
+            gba.bus_cycles() - start,
+            2 + 256 + 3,
+            "start delay, 256 timer ticks, then the IRQ vector refill"
+        );
+        assert_eq!(prof.halt_steps, 1, "one batch straight to the overflow event");
+    }
+
+    #[test]
+    fn save_state_keeps_unsynced_peripheral_cycles() {
+        let mut gba = build_gba();
+        gba.memory.write_byte(0x0400_0102, 0x80);
+        <Memory as Bus>::tick(&mut gba.memory, 100);
+        assert_eq!(gba.read_hword(0x0400_0100), 98);
+
+        let state = gba.save_state();
+        let mut other = build_gba();
+        other.load_state(&state).expect("load");
+        assert_eq!(other.read_hword(0x0400_0100), 98);
+        <Memory as Bus>::tick(&mut other.memory, 10);
+        other.memory.sync_peripherals();
+        assert_eq!(other.memory.timers.read_byte(0x100), 108);
+    }
+
     #[test]
     #[ignore]
     fn diagnose_jsmolka_arm_run_state() {
@@ -437,4 +708,200 @@ mod tests {
             "after IRQ entry: PC=0x18 in BIOS, flush_pipeline must set bios_readable"
         );
     }
+
+    #[test]
+    fn save_state_round_trip_resumes_identically() {
+        let mut gba = build_gba();
+        gba.memory.write_byte(0x0400_0100, 0x00);
+        gba.memory.write_byte(0x0400_0102, 0b1000_0001);
+        gba.memory.write_byte(0x0400_0000, 0x03);
+        gba.memory.write_byte(0x0200_1234, 0x5A);
+        gba.memory.write_byte(0x0400_0084, 0x80);
+        gba.run_frame();
+
+        let state = gba.save_state();
+        gba.run_frame();
+        let expected = gba.save_state();
+
+        gba.memory.write_byte(0x0200_1234, 0x00);
+        gba.memory.write_byte(0x0400_0000, 0x00);
+        gba.load_state(&state).expect("load");
+        assert!(gba.save_state() == state, "load must restore the saved snapshot exactly");
+
+        gba.run_frame();
+        assert!(gba.save_state() == expected, "execution after load must match the original run");
+
+        let mut fresh = build_gba();
+        fresh.load_state(&state).expect("load into fresh instance");
+        fresh.run_frame();
+        assert!(fresh.save_state() == expected, "fresh instance must resume identically");
+        assert_eq!(fresh.memory.read_byte(0x0200_1234), 0x5A, "EWRAM restored");
+        assert_eq!(fresh.memory.read_byte(0x0400_0000), 0x03, "DISPCNT restored");
+    }
+
+    #[test]
+    fn failed_load_leaves_machine_untouched() {
+        let mut gba = build_gba();
+        gba.memory.write_byte(0x0300_0010, 0x77);
+        let state = gba.save_state();
+        let before = gba.save_state();
+
+        let cases: [(&[u8], StateError, &str); 3] = [
+            (&state[..state.len() / 2], StateError::Truncated, "truncated"),
+            (b"garbage!", StateError::BadMagic, "bad magic"),
+            (&[state.as_slice(), &[0]].concat(), StateError::Corrupt("trailing data"), "trailing"),
+        ];
+        for (data, err, label) in cases {
+            assert_eq!(gba.load_state(data), Err(err), "{label}");
+            assert!(gba.save_state() == before, "{label}: state must be rolled back");
+        }
+    }
+
+    #[test]
+    fn load_state_rejects_other_rom() {
+        let gba = build_gba();
+        let state = gba.save_state();
+
+        let mut rom = vec![0u8; 0x1000];
+        rom[0xA0..0xAC].copy_from_slice(b"OTHER GAME  ");
+        let bios = Bios::new(Rom::new(&vec![0u8; 0x4000])).expect("bios");
+        let cart = Cartridge::new(Rom::new(&rom), &PathBuf::from("/nonexistent/no.sav"))
+            .expect("cart");
+        let mut other = GBA::new(bios, cart);
+        assert_eq!(other.load_state(&state), Err(StateError::RomMismatch));
+    }
+
+    #[test]
+    fn hle_vblank_intr_wait_dispatches_to_user_handler() {
+        let rom: Vec<u8> = [0xE321_F09Fu32, 0xE3A0_2077, 0xEF05_0000, 0xE3A0_5055, 0xEAFF_FFFE]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .chain(std::iter::repeat_n(0, 0x1000))
+            .collect();
+        let cart = Cartridge::new(Rom::new(&rom), &PathBuf::from("/nonexistent/no.sav"))
+            .expect("cart");
+        let mut gba = GBA::new(Bios::hle(), cart);
+
+        let handler: [u32; 9] = [
+            0xE3A0_0301, // mov r0, #0x04000000
+            0xE280_0C02, // add r0, r0, #0x200
+            0xE3A0_1001, // mov r1, #1
+            0xE1C0_10B2, // strh r1, [r0, #2]
+            0xE3A0_2403, // mov r2, #0x03000000
+            0xE282_2C7F, // add r2, r2, #0x7F00
+            0xE1D2_3FB8, // ldrh r3, [r2, #0xF8]
+            0xE383_3001, // orr r3, r3, #1
+            0xE1C2_3FB8, // strh r3, [r2, #0xF8]
+        ];
+        for (i, instr) in handler.iter().chain(&[0xE12F_FF1E]).enumerate() {
+            gba.memory.write_word(0x0300_0000 + i as u32 * 4, *instr);
+        }
+        gba.memory.write_word(0x0300_7FFC, 0x0300_0000);
+        gba.memory.write_byte(0x0400_0004, 0x08);
+        gba.memory.write_byte(0x0400_0200, 0x01);
+
+        for _ in 0..8 {
+            gba.step_one_instruction();
+        }
+        assert_eq!(gba.memory.system.get_power_mode(), PowerMode::Halt, "halted waiting for VBlank");
+        assert_ne!(gba.cpu.reg[5], 0x55, "must not return before VBlank");
+
+        gba.run_frame();
+        assert_eq!(gba.cpu.reg[5], 0x55, "returned from VBlankIntrWait after the handler ran");
+        assert_eq!(gba.memory.read_hword(0x0300_7FF8), 0, "IntrWait consumes the BIOS_IF flag");
+        assert_eq!(gba.cpu.cpsr.mode, OperatingMode::sys, "back in the caller's mode");
+        assert!(gba.cpu.cpsr.irq_disable_bit, "caller's IRQ mask restored");
+        assert_eq!(gba.cpu.reg[2], 0x77, "r2 preserved");
+    }
+
+    #[test]
+    fn rewritten_iwram_code_is_decoded_again() {
+        let mut gba = build_gba();
+        gba.memory.write_word(0x0300_0000, 0xE3A0_1001);
+        gba.cpu.cpsr.mode = OperatingMode::sys;
+        gba.set_register(PC_INDEX, 0x0300_0000);
+        gba.step_one_instruction();
+        assert_eq!(gba.cpu.reg[1], 1);
+
+        gba.memory.write_word(0x0300_0000, 0xE3A0_1002);
+        gba.set_register(PC_INDEX, 0x0300_0000);
+        gba.step_one_instruction();
+        assert_eq!(gba.cpu.reg[1], 2, "the cached decode of the old opcode is not reused");
+    }
+
+    #[test]
+    fn rewind_steps_back_through_captured_frames() {
+        let mut gba = build_gba();
+        assert!(!gba.rewind(), "nothing to rewind while disabled");
+        gba.enable_rewind(2, 8 << 20);
+        gba.run_frame();
+        let first = gba.save_state();
+        gba.run_frame();
+        gba.run_frame();
+        let third = gba.save_state();
+        gba.run_frame();
+        assert_eq!(gba.rewind_snapshots(), 2);
+
+        assert!(gba.rewind());
+        assert_eq!(gba.save_state(), third);
+        assert!(gba.rewind());
+        assert_eq!(gba.save_state(), first);
+        assert!(!gba.rewind());
+    }
+
+    #[test]
+    fn debugger_register_writes_refetch_pipeline() {
+        let mut gba = build_gba();
+        gba.set_register(PC_INDEX, 0x0800_0100);
+        assert_eq!(gba.pc(), 0x0800_0100, "PC write redirects execution");
+
+        gba.set_cpsr(gba.cpsr() | 1 << 5);
+        assert_eq!(gba.cpu.cpsr.operating_state, OperatingState::THUMB);
+        assert_eq!(gba.pc(), 0x0800_0100, "state switch keeps the current PC");
+
+        gba.set_cpsr(0x1F);
+        assert_eq!(gba.cpu.cpsr.mode, OperatingMode::sys);
+        assert_eq!(gba.cpsr() & 0x1F, 0x1F);
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    #[test]
+    fn run_frame_stops_on_debug_hooks() {
+        use crate::debug::WatchKind;
+
+        let rom: Vec<u8> = [
+            0xE3A0_0403u32, // mov r0, #0x03000000
+            0xE3A0_1007,    // mov r1, #7
+            0xE580_1000,    // str r1, [r0]
+            0xEF06_0000,    // swi 0x06
+            0xEAFF_FFFE,    // b .
+        ]
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .chain(std::iter::repeat_n(0, 0x100))
+        .collect();
+        let cart = Cartridge::new(Rom::new(&rom), &PathBuf::from("/nonexistent/no.sav"))
+            .expect("cart");
+        let mut gba = GBA::new(Bios::hle(), cart);
+
+        gba.debug_hooks().add_breakpoint(0x0800_0004);
+        gba.debug_hooks().add_watchpoint(0x0300_0000..0x0300_0004, WatchKind::Write);
+        gba.debug_hooks().add_swi_breakpoint(Some(0x06));
+
+        assert_eq!(gba.run_frame(), Some(StopReason::Breakpoint(0x0800_0004)));
+        assert_eq!(gba.pc(), 0x0800_0004, "stops before executing the breakpoint");
+        assert_eq!(
+            gba.run_frame(),
+            Some(StopReason::Watchpoint { addr: 0x0300_0000, kind: WatchKind::Write })
+        );
+        assert_eq!(gba.read_word(0x0300_0000), 7, "the store completes before stopping");
+        assert_eq!(gba.run_frame(), Some(StopReason::SoftwareInterrupt(0x06)));
+
+        let start = gba.bus_cycles();
+        assert_eq!(gba.run_frame(), None);
+        assert!(
+            gba.bus_cycles() - start < CYCLES_PER_FRAME as u64,
+            "a resumed frame only runs to the original frame boundary"
+        );
+    }
 }
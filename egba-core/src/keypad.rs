@@ -1,6 +1,9 @@
 use bit::BitIndex;
 
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 pub(crate) struct Keypad {
     pub(crate) keystate: u16,
@@ -56,3 +59,15 @@ impl Bus for Keypad {
         }
     }
 }
+
+impl Snapshot for Keypad {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.keystate.save_state(w);
+        self.keycnt.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.keystate.load_state(r)?;
+        self.keycnt.load_state(r)
+    }
+}
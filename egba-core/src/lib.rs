@@ -3,15 +3,21 @@
 pub mod bios;
 pub mod cartridge;
 pub mod cpu;
+pub mod debug;
 pub mod gba;
 pub mod keypad;
+pub mod link;
+pub mod movie;
 pub mod rom;
+pub mod state;
 
 mod apu;
 mod bus;
 mod control;
 mod dma;
 mod memory;
+mod rewind;
+mod scheduler;
 mod serial;
 mod timer;
 mod video;
@@ -0,0 +1,103 @@
+pub const PACKET_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinkMode {
+    Normal8,
+    Normal32,
+    Multiplayer,
+    Uart,
+}
+
+impl LinkMode {
+    const ALL: [LinkMode; 4] = [
+        LinkMode::Normal8,
+        LinkMode::Normal32,
+        LinkMode::Multiplayer,
+        LinkMode::Uart,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinkPacket {
+    Sync { cycle: u64 },
+    Start { cycle: u64, mode: LinkMode, data: u32 },
+    Reply { data: u32 },
+    Done { words: [u16; 4] },
+    Byte(u8),
+}
+
+impl LinkPacket {
+    pub fn encode(&self) -> [u8; PACKET_SIZE] {
+        let (tag, mode, data, wide) = match *self {
+            LinkPacket::Sync { cycle } => (0, 0, 0, cycle),
+            LinkPacket::Start { cycle, mode, data } => (1, mode as u8, data, cycle),
+            LinkPacket::Reply { data } => (2, 0, data, 0),
+            LinkPacket::Done { words } => {
+                let packed = words
+                    .iter()
+                    .rev()
+                    .fold(0u64, |acc, &w| (acc << 16) | w as u64);
+                (3, 0, 0, packed)
+            }
+            LinkPacket::Byte(byte) => (4, 0, byte as u32, 0),
+        };
+        let mut out = [0; PACKET_SIZE];
+        out[0] = tag;
+        out[1] = mode;
+        out[4..8].copy_from_slice(&data.to_le_bytes());
+        out[8..16].copy_from_slice(&wide.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8; PACKET_SIZE]) -> Option<Self> {
+        let data = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let wide = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        Some(match bytes[0] {
+            0 => LinkPacket::Sync { cycle: wide },
+            1 => LinkPacket::Start {
+                cycle: wide,
+                mode: *LinkMode::ALL.get(bytes[1] as usize)?,
+                data,
+            },
+            2 => LinkPacket::Reply { data },
+            3 => LinkPacket::Done {
+                words: [0, 16, 32, 48].map(|shift| (wide >> shift) as u16),
+            },
+            4 => LinkPacket::Byte(data as u8),
+            _ => return None,
+        })
+    }
+}
+
+pub trait LinkTransport: Send {
+    fn player(&self) -> usize;
+    fn players(&self) -> usize;
+    fn send(&mut self, packet: LinkPacket);
+    fn recv(&mut self, wait: bool) -> Option<(usize, LinkPacket)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_round_trip() {
+        let packets = [
+            LinkPacket::Sync { cycle: 0x1234_5678_9ABC },
+            LinkPacket::Start {
+                cycle: 42,
+                mode: LinkMode::Multiplayer,
+                data: 0xBEEF,
+            },
+            LinkPacket::Reply { data: 0xDEAD_BEEF },
+            LinkPacket::Done {
+                words: [0x1111, 0x2222, 0xFFFF, 0x0001],
+            },
+            LinkPacket::Byte(0x7F),
+        ];
+        for packet in packets {
+            assert_eq!(LinkPacket::decode(&packet.encode()), Some(packet));
+        }
+        assert_eq!(LinkPacket::decode(&[9; PACKET_SIZE]), None);
+    }
+}
@@ -1,16 +1,22 @@
+#[cfg(feature = "debug-hooks")]
+use crate::debug::DebugHooks;
 use crate::{
     apu::Apu,
     bios::Bios,
     bus::Bus,
-    cartridge::Cartridge,
+    cartridge::{gpio::Gpio, Cartridge},
     control::{InterruptControl, InterruptType, SystemControl},
     dma::{Dma, DmaMemory},
     keypad::Keypad,
+    scheduler::{Event, Scheduler},
     serial::Serial,
+    state::{Snapshot, StateError, StateReader, StateWriter},
     timer::Timers,
     video::{Video, VideoEvent},
 };
 
+const PERIPHERAL_IO: std::ops::RangeInclusive<u32> = 0x060..=0x12F;
+
 pub(crate) struct Memory {
     pub(crate) bios: Bios,
     pub(crate) ewram: Box<[u8]>,
@@ -39,12 +45,20 @@ pub(crate) struct Memory {
     pub(crate) video_events: Vec<(VideoEvent, Option<InterruptType>)>,
 
     last_rom_access: u32,
+    rom_bus_cycles: u32,
+
+    pub(crate) scheduler: Scheduler,
+    peripherals_synced: u64,
+    sync_pending: bool,
+
+    #[cfg(feature = "debug-hooks")]
+    pub(crate) debug: DebugHooks,
 }
 
 impl Memory {
     #[must_use]
     pub(crate) fn new(bios: Bios, cartridge: Cartridge) -> Self {
-        Self {
+        let mut memory = Self {
             bios,
             ewram: vec![0; 0x40000].into_boxed_slice(),
             iwram: vec![0; 0x8000].into_boxed_slice(),
@@ -65,7 +79,15 @@ impl Memory {
             bus_cycles: 0,
             video_events: Vec::with_capacity(256),
             last_rom_access: !0,
-        }
+            rom_bus_cycles: 0,
+            scheduler: Scheduler::default(),
+            peripherals_synced: 0,
+            sync_pending: false,
+            #[cfg(feature = "debug-hooks")]
+            debug: DebugHooks::default(),
+        };
+        memory.reschedule();
+        memory
     }
 }
 
@@ -88,9 +110,12 @@ impl Bus for Memory {
                 let offset = addr & 0x3FF;
                 match offset {
                     0x000..=0x056 => self.video.read_byte(offset),
-                    0x060..=0x089 | 0x0A0..=0x0A7 => self.apu.read_byte(offset),
+                    0x060..=0x089 | 0x090..=0x09F | 0x0A0..=0x0A7 => self.apu.read_byte(offset),
                     0x0B0..=0x0DF => self.dma.read_byte(offset),
-                    0x100..=0x10F => self.timers.read_byte(offset),
+                    0x100..=0x10F => {
+                        let elapsed = self.bus_cycles - self.peripherals_synced;
+                        self.timers.read_byte_after(offset, elapsed as u32)
+                    }
                     0x120..=0x12F => self.serial.read_byte(offset),
                     0x130..=0x133 => self.keypad.read_byte(offset),
                     0x200..=0x203 | 0x208..=0x209 => self.interrupt.read_byte(offset),
@@ -120,15 +145,20 @@ impl Bus for Memory {
     #[inline]
     fn write_byte(&mut self, addr: u32, value: u8) {
         self.last_bus_value.set(value);
+        self.sync_video(addr);
         match addr {
             0x0200_0000..=0x02FF_FFFF => self.ewram.write_byte(addr & 0x3_FFFF, value),
             0x0300_0000..=0x03FF_FFFF => self.iwram.write_byte(addr & 0x7FFF, value),
 
             0x0400_0000..=0x0400_03FE => {
                 let offset = addr & 0x3FF;
+                let peripheral = PERIPHERAL_IO.contains(&offset);
+                if peripheral {
+                    self.sync_peripherals();
+                }
                 match offset {
                     0x000..=0x056 => self.video.write_byte(offset, value),
-                    0x060..=0x089 | 0x0A0..=0x0A7 => self.apu.write_byte(offset, value),
+                    0x060..=0x089 | 0x090..=0x09F | 0x0A0..=0x0A7 => self.apu.write_byte(offset, value),
                     0x0B0..=0x0DF => self.dma.write_byte(offset, value),
                     0x100..=0x10F => self.timers.write_byte(offset, value),
                     0x120..=0x12F => self.serial.write_byte(offset, value),
@@ -142,6 +172,10 @@ impl Bus for Memory {
                     0x204..=0x205 | 0x300 | 0x301 => self.system.write_byte(offset, value),
                     _ => {}
                 }
+                if peripheral {
+                    self.schedule_peripherals();
+                    self.schedule_dma();
+                }
             }
             0x0500_0000..=0x05FF_FFFF => {
                 let pal_addr = (addr & 0x3FE) as usize;
@@ -167,7 +201,12 @@ impl Bus for Memory {
                 }
             }
             0x0700_0000..=0x07FF_FFFF => {}
-            0x0800_0000..=0x0FFF_FFFF => self.cartridge.write_byte(addr, value),
+            0x0800_0000..=0x0FFF_FFFF => {
+                if Gpio::contains(addr) {
+                    self.cartridge.sync_rtc(self.bus_cycles);
+                }
+                self.cartridge.write_byte(addr, value)
+            }
             _ => {}
         }
     }
@@ -300,12 +339,12 @@ impl Bus for Memory {
     }
 
     fn write_hword(&mut self, addr: u32, value: u16) {
+        self.sync_video(addr);
         match addr {
             0x0E00_0000..=0x0FFF_FFFF => {
                 let rot = (addr & 1) * 8;
                 let byte = (value.rotate_right(rot) & 0xFF) as u8;
                 self.write_byte(addr, byte);
-                return;
             }
             0x0500_0000..=0x05FF_FFFF => {
                 let pal_addr = (addr & 0x3FE) as usize;
@@ -366,7 +405,13 @@ impl Bus for Memory {
         let region = (addr >> 24) & 0xF;
         let word = width >= 4;
         match region {
-            0x0 | 0x3 | 0x4 | 0x7 => 1,
+            0x0 | 0x3 => 1,
+            0x4 => {
+                if PERIPHERAL_IO.contains(&(addr.wrapping_sub(0x0400_0000))) {
+                    self.sync_pending = true;
+                }
+                1
+            }
             0x2 => {
                 if word {
                     6
@@ -374,14 +419,18 @@ impl Bus for Memory {
                     3
                 }
             }
+            // Approximates contention with the PPU by doubling the cost while it fetches from the
+            // region, rather than stalling until the next free slot in its fetch schedule.
             0x5 | 0x6 => {
-                if word {
-                    2
+                let cycles = if word { 2 } else { 1 };
+                if self.video.memory_busy(region, self.video_cycle_debt) {
+                    cycles * 2
                 } else {
-                    1
+                    cycles
                 }
             }
-            0x8 | 0x9 | 0xA | 0xB | 0xC | 0xD => {
+            0x7 => 1 + self.video.memory_busy(region, self.video_cycle_debt) as u32,
+            0x8..=0xD => {
                 let seq = addr == self.last_rom_access;
                 let cycles = if seq {
                     self.system.rom_seq_cycles(addr, width)
@@ -389,15 +438,36 @@ impl Bus for Memory {
                     self.system.rom_access_cycles(addr, width)
                 };
                 self.last_rom_access = addr.wrapping_add(width);
+                self.system.prefetch_stop();
+                self.rom_bus_cycles = cycles;
+                cycles
+            }
+            0xE | 0xF => {
+                let cycles = self.system.sram_access_cycles();
+                self.rom_bus_cycles = cycles;
                 cycles
             }
-            0xE | 0xF => self.system.sram_access_cycles(),
             _ => 1,
         }
     }
 
+    fn fetch_cycles(&mut self, addr: u32, width: u32) -> u32 {
+        if !(0x0800_0000..0x0E00_0000).contains(&addr) || !self.system.prefetch_enabled() {
+            return self.access_cycles(addr, width);
+        }
+        if let Some(cycles) = self.system.prefetch_read(addr, width) {
+            self.last_rom_access = addr.wrapping_add(width);
+            self.rom_bus_cycles = if cycles > 1 { cycles } else { 0 };
+            return cycles;
+        }
+        let cycles = self.access_cycles(addr, width);
+        self.system.prefetch_start(addr.wrapping_add(width));
+        cycles
+    }
+
     fn invalidate_rom_seq(&mut self) {
         self.last_rom_access = u32::MAX;
+        self.system.prefetch_stop();
     }
 
     #[inline]
@@ -405,6 +475,21 @@ impl Bus for Memory {
         self.bios_readable = pc < 0x0000_4000;
     }
 
+    #[cfg(feature = "debug-hooks")]
+    fn notify_access(&mut self, addr: u32, width: u32, write: bool) {
+        self.debug.on_access(addr, width, write);
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    fn notify_swi(&mut self, comment: u8) {
+        self.debug.on_swi(comment);
+    }
+
+    #[inline]
+    fn hle_bios(&self) -> bool {
+        self.bios.is_hle()
+    }
+
     #[inline]
     fn tick(&mut self, n: u32) {
         if n == 0 {
@@ -414,11 +499,76 @@ impl Bus for Memory {
         self.bus_cycles = self.bus_cycles.wrapping_add(n as u64);
         self.video_cycle_debt = self.video_cycle_debt.saturating_add(n);
 
-        self.advance_timers_and_apu(n);
+        let idle = n.saturating_sub(self.rom_bus_cycles);
+        self.rom_bus_cycles = self.rom_bus_cycles.saturating_sub(n);
+        if idle > 0 {
+            self.system.prefetch_idle(idle);
+        }
+
+        if self.sync_pending {
+            self.sync_pending = false;
+            self.sync_peripherals();
+        } else if self.bus_cycles >= self.scheduler.next() {
+            let now = self.bus_cycles;
+            if [Event::Timers, Event::Apu, Event::Serial]
+                .into_iter()
+                .any(|event| self.scheduler.is_due(event, now))
+            {
+                self.sync_peripherals();
+            }
+        }
     }
 }
 
 impl Memory {
+    pub(crate) fn sync_peripherals(&mut self) {
+        let mut elapsed = self.bus_cycles.wrapping_sub(self.peripherals_synced);
+        self.peripherals_synced = self.bus_cycles;
+        while elapsed > 0 {
+            let n = elapsed.min(u32::MAX as u64) as u32;
+            self.advance_timers_and_apu(n);
+            elapsed -= n as u64;
+        }
+        self.schedule_peripherals();
+    }
+
+    pub(crate) fn schedule_peripherals(&mut self) {
+        let now = self.peripherals_synced;
+        self.scheduler
+            .schedule_in(Event::Timers, now, self.timers.cycles_to_next_overflow());
+        self.scheduler
+            .schedule_in(Event::Apu, now, self.apu.cycles_to_next_batch());
+        self.scheduler
+            .schedule_in(Event::Serial, now, self.serial.cycles_to_next_event());
+    }
+
+    pub(crate) fn schedule_video(&mut self) {
+        let synced = self.bus_cycles - self.video_cycle_debt as u64;
+        self.scheduler.schedule(
+            Event::Video,
+            synced + self.video.cycles_to_next_event() as u64,
+        );
+    }
+
+    fn schedule_dma(&mut self) {
+        if self.pending_sound_dma != 0 || self.dma.any_running() {
+            self.scheduler.schedule(Event::Dma, self.bus_cycles);
+        }
+    }
+
+    fn reschedule(&mut self) {
+        self.schedule_peripherals();
+        self.schedule_video();
+        self.schedule_dma();
+    }
+
+    #[inline]
+    fn sync_video(&mut self, addr: u32) {
+        if (0x0400_0000..=0x0400_0056).contains(&addr) || (0x0500_0000..0x0800_0000).contains(&addr) {
+            self.video.catch_up(self.video_cycle_debt);
+        }
+    }
+
     #[inline]
     fn advance_timers_and_apu(&mut self, n: u32) {
         self.apu.step(n);
@@ -441,12 +591,92 @@ impl Memory {
                 let refill = self.apu.on_timer_overflow(timer_id);
                 if refill != 0 {
                     self.pending_sound_dma |= refill;
+                    self.scheduler.schedule(Event::Dma, self.bus_cycles);
                 }
             }
         }
     }
 }
 
+impl DmaMemory for Memory {
+    fn dma_read_hword(&self, addr: u32) -> u16 {
+        self.read_hword(addr)
+    }
+
+    fn dma_read_word(&self, addr: u32) -> u32 {
+        self.read_word(addr)
+    }
+
+    fn dma_write_hword(&mut self, addr: u32, val: u16) {
+        self.write_hword(addr, val);
+    }
+
+    fn dma_write_word(&mut self, addr: u32, val: u32) {
+        self.write_word(addr, val);
+    }
+
+    fn dma_access_cycles(&mut self, addr: u32, width: u32) -> u32 {
+        <Memory as Bus>::access_cycles(self, addr, width)
+    }
+
+    fn dma_tick(&mut self, cycles: u32) {
+        <Memory as Bus>::tick(self, cycles);
+    }
+}
+
+impl Snapshot for Memory {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.ewram.save_state(w);
+        self.iwram.save_state(w);
+        self.interrupt.save_state(w);
+        self.system.save_state(w);
+        self.keypad.save_state(w);
+        self.video.save_state(w);
+        self.timers.save_state(w);
+        self.dma.save_state(w);
+        self.apu.save_state(w);
+        self.serial.save_state(w);
+        self.cartridge.save_state(w);
+        self.bios_readable.save_state(w);
+        self.last_bios_value.save_state(w);
+        self.last_bus_value.save_state(w);
+        self.video_cycle_debt.save_state(w);
+        self.pending_sound_dma.save_state(w);
+        self.bus_cycles.save_state(w);
+        self.last_rom_access.save_state(w);
+        self.peripherals_synced.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ewram.load_state(r)?;
+        self.iwram.load_state(r)?;
+        self.interrupt.load_state(r)?;
+        self.system.load_state(r)?;
+        self.keypad.load_state(r)?;
+        self.video.load_state(r)?;
+        self.timers.load_state(r)?;
+        self.dma.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.serial.load_state(r)?;
+        self.cartridge.load_state(r)?;
+        self.bios_readable.load_state(r)?;
+        self.last_bios_value.load_state(r)?;
+        self.last_bus_value.load_state(r)?;
+        self.video_cycle_debt.load_state(r)?;
+        self.pending_sound_dma.load_state(r)?;
+        self.bus_cycles.load_state(r)?;
+        self.last_rom_access.load_state(r)?;
+        self.peripherals_synced.load_state(r)?;
+        if self.peripherals_synced > self.bus_cycles {
+            return Err(StateError::Corrupt("peripheral clock"));
+        }
+        self.video_events.clear();
+        self.sync_pending = false;
+        self.reschedule();
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -542,6 +772,7 @@ mod tests {
     #[test]
     fn access_cycles_per_region() {
         let mut m = build_memory();
+        m.write_byte(0x0400_0000, 0x80);
         assert_eq!(m.access_cycles(0x0000_0000, 4), 1);
         assert_eq!(m.access_cycles(0x0300_0000, 4), 1);
         assert_eq!(m.access_cycles(0x0400_0000, 4), 1);
@@ -562,6 +793,42 @@ mod tests {
         assert_eq!(m.access_cycles(0x0E00_0000, 1), 5);
     }
 
+    #[test]
+    fn video_writes_catch_up_dot_renderer() {
+        let mut m = build_memory();
+        m.video.set_renderer(crate::gba::Renderer::Dot);
+        m.write_hword(0x0500_0000, 0x001F);
+        m.tick(120);
+        m.write_hword(0x0500_0000, 0x7C00);
+        m.tick(840);
+        let debt = std::mem::take(&mut m.video_cycle_debt);
+        m.video.step_n(debt, |_, _| {});
+        assert_eq!(m.video.framebuffer()[29], 0xF80000);
+        assert_eq!(m.video.framebuffer()[30], 0x0000F8);
+    }
+
+    #[test]
+    fn video_memory_stalls_while_ppu_draws() {
+        let mut m = build_memory();
+        m.write_hword(0x0400_0000, 0x1100);
+        assert_eq!(m.access_cycles(0x0500_0000, 2), 2);
+        assert_eq!(m.access_cycles(0x0500_0000, 4), 4);
+        assert_eq!(m.access_cycles(0x0600_0000, 2), 2);
+        assert_eq!(m.access_cycles(0x0600_0000, 4), 4);
+        assert_eq!(m.access_cycles(0x0700_0000, 4), 2);
+
+        m.tick(960);
+        assert_eq!(m.access_cycles(0x0500_0000, 4), 2, "hblank frees palette");
+        assert_eq!(m.access_cycles(0x0600_0000, 4), 2, "hblank frees vram");
+        assert_eq!(m.access_cycles(0x0700_0000, 4), 2, "sprites still read oam in hblank");
+        m.write_hword(0x0400_0000, 0x1120);
+        assert_eq!(m.access_cycles(0x0700_0000, 4), 1, "h-blank interval free");
+
+        m.tick(272 + 1232 * 159);
+        assert_eq!(m.access_cycles(0x0500_0000, 4), 2, "vblank");
+        assert_eq!(m.access_cycles(0x0700_0000, 4), 1, "vblank");
+    }
+
     #[test]
     fn waitcnt_ws0_changes_rom_cycles() {
         let mut m = build_memory();
@@ -587,7 +854,7 @@ mod tests {
     }
 
     #[test]
-    fn rom_seq_cycles_independent_of_prefetch_flag() {
+    fn rom_data_cycles_independent_of_prefetch_flag() {
         for (prefetch_byte, label) in [(0x00u8, "prefetch off"), (0x40u8, "prefetch on")] {
             let mut m = build_memory();
             m.write_byte(0x0400_0205, prefetch_byte);
@@ -596,12 +863,72 @@ mod tests {
             assert_eq!(
                 m.access_cycles(0x0800_0002, 2),
                 3,
-                "{label}: sequential = S regardless of prefetch flag"
+                "{label}: sequential data = S regardless of prefetch flag"
             );
             assert_eq!(m.access_cycles(0x0800_1000, 2), 5, "{label}: non-seq jump = N");
         }
     }
 
+    #[test]
+    fn prefetch_off_fetches_pay_full_rom_timing() {
+        let mut m = build_memory();
+        assert_eq!(m.fetch_cycles(0x0800_0000, 2), 5);
+        m.tick(5);
+        m.tick(30);
+        assert_eq!(m.fetch_cycles(0x0800_0002, 2), 3);
+        assert_eq!(m.fetch_cycles(0x0800_0004, 4), 6);
+    }
+
+    #[test]
+    fn prefetch_fills_during_idle_cycles() {
+        let mut m = build_memory();
+        m.write_byte(0x0400_0205, 0x40);
+
+        assert_eq!(m.fetch_cycles(0x0800_0000, 2), 5);
+        m.tick(5);
+        m.tick(7);
+        assert_eq!(m.fetch_cycles(0x0800_0002, 2), 1, "buffered halfword");
+        m.tick(1);
+        assert_eq!(m.fetch_cycles(0x0800_0004, 2), 1, "second slot filled by cycle 6");
+        m.tick(1);
+        assert_eq!(m.fetch_cycles(0x0800_0006, 2), 3 - 2, "in-flight slot finishes early");
+        m.tick(1);
+
+        m.tick(100);
+        for addr in (0x0800_0008..0x0800_0018).step_by(4) {
+            assert_eq!(m.fetch_cycles(addr, 4), 1, "word takes two buffered slots");
+        }
+        assert_eq!(m.fetch_cycles(0x0800_0018, 4), 3 + 3, "buffer holds eight halfwords");
+    }
+
+    #[test]
+    fn prefetch_flushed_by_branches_and_rom_data_reads() {
+        let mut m = build_memory();
+        m.write_byte(0x0400_0205, 0x40);
+
+        assert_eq!(m.fetch_cycles(0x0800_0000, 2), 5);
+        m.tick(5);
+        m.tick(30);
+        m.invalidate_rom_seq();
+        assert_eq!(m.fetch_cycles(0x0800_0002, 2), 5, "branch discards the buffer");
+        m.tick(5);
+        m.tick(30);
+        assert_eq!(m.access_cycles(0x0800_1000, 4), 8);
+        m.tick(8);
+        m.tick(30);
+        assert_eq!(m.fetch_cycles(0x0800_0004, 2), 5, "data access stopped the prefetcher");
+    }
+
+    #[test]
+    fn prefetch_stalls_while_cartridge_bus_is_busy() {
+        let mut m = build_memory();
+        m.write_byte(0x0400_0205, 0x40);
+
+        assert_eq!(m.fetch_cycles(0x0800_0000, 2), 5);
+        m.tick(5);
+        assert_eq!(m.fetch_cycles(0x0800_0002, 2), 3, "no idle cycles, nothing buffered");
+    }
+
     #[test]
     fn waitcnt_sram_cycles() {
         let mut m = build_memory();
@@ -753,29 +1080,3 @@ mod tests {
         assert_eq!(counter, 48, "50 cycles - 2-cycle hardware startup delay");
     }
 }
-
-impl DmaMemory for Memory {
-    fn dma_read_hword(&self, addr: u32) -> u16 {
-        self.read_hword(addr)
-    }
-
-    fn dma_read_word(&self, addr: u32) -> u32 {
-        self.read_word(addr)
-    }
-
-    fn dma_write_hword(&mut self, addr: u32, val: u16) {
-        self.write_hword(addr, val);
-    }
-
-    fn dma_write_word(&mut self, addr: u32, val: u32) {
-        self.write_word(addr, val);
-    }
-
-    fn dma_access_cycles(&mut self, addr: u32, width: u32) -> u32 {
-        <Memory as Bus>::access_cycles(self, addr, width)
-    }
-
-    fn dma_tick(&mut self, cycles: u32) {
-        <Memory as Bus>::tick(self, cycles);
-    }
-}
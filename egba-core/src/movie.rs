@@ -0,0 +1,355 @@
+use std::{error::Error, fmt};
+
+use crate::gba::GBA;
+
+const MOVIE_MAGIC: &[u8; 8] = b"EGBAMOVI";
+pub const MOVIE_VERSION: u32 = 2;
+pub const CHECK_INTERVAL: usize = 60;
+const INPUT_BYTES: usize = 9;
+
+#[derive(Debug, PartialEq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    RomMismatch,
+    BiosMismatch,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "Not an EGBA movie"),
+            MovieError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported movie version {} (expected {})", v, MOVIE_VERSION)
+            }
+            MovieError::Truncated => write!(f, "Movie is truncated"),
+            MovieError::RomMismatch => write!(f, "Movie was recorded with a different ROM"),
+            MovieError::BiosMismatch => write!(f, "Movie was recorded with a different BIOS"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+#[derive(Debug, PartialEq)]
+pub enum Desync {
+    Framebuffer(usize),
+    State(usize),
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Desync::Framebuffer(frame) => write!(f, "framebuffer diverged at frame {}", frame),
+            Desync::State(frame) => write!(f, "machine state diverged at frame {}", frame),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameInput {
+    pub keys: u16,
+    pub tilt: (i16, i16),
+    pub gyro: i16,
+    pub light: u8,
+}
+
+impl Default for FrameInput {
+    fn default() -> Self {
+        Self {
+            keys: 0x3FF,
+            tilt: (0, 0),
+            gyro: 0,
+            light: 0,
+        }
+    }
+}
+
+impl FrameInput {
+    pub fn apply(&self, gba: &mut GBA) {
+        gba.update_keypad(self.keys);
+        gba.set_tilt(self.tilt.0, self.tilt.1);
+        gba.set_gyro(self.gyro);
+        gba.set_light_level(self.light);
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.keys.to_le_bytes());
+        out.extend_from_slice(&self.tilt.0.to_le_bytes());
+        out.extend_from_slice(&self.tilt.1.to_le_bytes());
+        out.extend_from_slice(&self.gyro.to_le_bytes());
+        out.push(self.light);
+    }
+
+    fn decode(b: &[u8]) -> Self {
+        Self {
+            keys: u16::from_le_bytes([b[0], b[1]]),
+            tilt: (i16::from_le_bytes([b[2], b[3]]), i16::from_le_bytes([b[4], b[5]])),
+            gyro: i16::from_le_bytes([b[6], b[7]]),
+            light: b[8],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Checkpoint {
+    framebuffer: u64,
+    state: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub bios_hash: u64,
+    pub skip_bios: bool,
+    pub rtc_time: i64,
+    pub rtc_state: Option<Vec<u8>>,
+    pub save: Option<Vec<u8>>,
+    check_interval: usize,
+    inputs: Vec<FrameInput>,
+    checks: Vec<Checkpoint>,
+}
+
+pub fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+fn framebuffer_hash(gba: &GBA) -> u64 {
+    let bytes: Vec<u8> = gba.framebuffer().iter().flat_map(|px| px.to_le_bytes()).collect();
+    content_hash(&bytes)
+}
+
+impl Movie {
+    pub fn new(
+        rom_hash: u64,
+        bios_hash: u64,
+        skip_bios: bool,
+        rtc_time: i64,
+        save: Option<Vec<u8>>,
+    ) -> Self {
+        Self {
+            rom_hash,
+            bios_hash,
+            skip_bios,
+            rtc_time,
+            rtc_state: None,
+            save,
+            check_interval: CHECK_INTERVAL,
+            inputs: Vec::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    pub fn input(&self, frame: usize) -> Option<FrameInput> {
+        self.inputs.get(frame).copied()
+    }
+
+    pub fn check_media(&self, rom_hash: u64, bios_hash: u64) -> Result<(), MovieError> {
+        if rom_hash != self.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+        if bios_hash != self.bios_hash {
+            return Err(MovieError::BiosMismatch);
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, input: FrameInput) {
+        self.inputs.push(FrameInput {
+            keys: input.keys & 0x3FF,
+            ..input
+        });
+    }
+
+    pub fn record_check(&mut self, gba: &GBA) {
+        if self.inputs.len() / self.check_interval > self.checks.len() {
+            self.checks.push(Checkpoint {
+                framebuffer: framebuffer_hash(gba),
+                state: content_hash(&gba.save_state()),
+            });
+        }
+    }
+
+    pub fn verify(&self, frames: usize, gba: &GBA) -> Result<(), Desync> {
+        let (count, rest) = (frames / self.check_interval, frames % self.check_interval);
+        if count == 0 || rest != 0 {
+            return Ok(());
+        }
+        let Some(check) = self.checks.get(count - 1) else {
+            return Ok(());
+        };
+        if framebuffer_hash(gba) != check.framebuffer {
+            return Err(Desync::Framebuffer(frames));
+        }
+        if content_hash(&gba.save_state()) != check.state {
+            return Err(Desync::State(frames));
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out =
+            Vec::with_capacity(64 + self.inputs.len() * INPUT_BYTES + self.checks.len() * 16);
+        out.extend_from_slice(MOVIE_MAGIC);
+        out.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.extend_from_slice(&self.bios_hash.to_le_bytes());
+        out.push(self.skip_bios as u8);
+        out.extend_from_slice(&self.rtc_time.to_le_bytes());
+        for blob in [&self.rtc_state, &self.save] {
+            match blob {
+                Some(data) => {
+                    out.push(1);
+                    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    out.extend_from_slice(data);
+                }
+                None => out.push(0),
+            }
+        }
+        out.extend_from_slice(&(self.check_interval as u32).to_le_bytes());
+        out.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        for input in &self.inputs {
+            input.encode(&mut out);
+        }
+        out.extend_from_slice(&(self.checks.len() as u32).to_le_bytes());
+        for check in &self.checks {
+            out.extend_from_slice(&check.framebuffer.to_le_bytes());
+            out.extend_from_slice(&check.state.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, MovieError> {
+        let mut pos = 0;
+        let mut take = |n: usize| -> Result<&[u8], MovieError> {
+            let bytes = data.get(pos..pos + n).ok_or(MovieError::Truncated)?;
+            pos += n;
+            Ok(bytes)
+        };
+        if take(MOVIE_MAGIC.len()).map_err(|_| MovieError::BadMagic)? != MOVIE_MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_hash = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let bios_hash = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let skip_bios = take(1)?[0] != 0;
+        let rtc_time = i64::from_le_bytes(take(8)?.try_into().unwrap());
+        let mut blob = || -> Result<Option<Vec<u8>>, MovieError> {
+            Ok(match take(1)?[0] {
+                0 => None,
+                _ => {
+                    let len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+                    Some(take(len)?.to_vec())
+                }
+            })
+        };
+        let rtc_state = blob()?;
+        let save = blob()?;
+        let check_interval = u32::from_le_bytes(take(4)?.try_into().unwrap()).max(1) as usize;
+        let count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        let inputs = take(count.checked_mul(INPUT_BYTES).ok_or(MovieError::Truncated)?)?
+            .chunks_exact(INPUT_BYTES)
+            .map(FrameInput::decode)
+            .collect();
+        let count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        let checks = take(count.checked_mul(16).ok_or(MovieError::Truncated)?)?
+            .chunks_exact(16)
+            .map(|b| Checkpoint {
+                framebuffer: u64::from_le_bytes(b[..8].try_into().unwrap()),
+                state: u64::from_le_bytes(b[8..].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Self {
+            rom_hash,
+            bios_hash,
+            skip_bios,
+            rtc_time,
+            rtc_state,
+            save,
+            check_interval,
+            inputs,
+            checks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bios::Bios, cartridge::Cartridge, rom::Rom};
+    use std::path::PathBuf;
+
+    fn build_gba() -> GBA {
+        let cart = Cartridge::new(
+            Rom::new(&vec![0u8; 0x1000]),
+            &PathBuf::from("/nonexistent/no.sav"),
+        )
+        .expect("cart");
+        GBA::new(Bios::hle(), cart)
+    }
+
+    #[test]
+    fn movie_round_trips_and_rejects_other_media() {
+        let mut movie = Movie::new(1, 2, true, 1_700_000_000, Some(vec![0xAA; 4]));
+        movie.rtc_state = Some(vec![0x40, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let tilted = FrameInput {
+            keys: 0xFFFE,
+            tilt: (-300, 120),
+            gyro: -7,
+            light: 0x60,
+        };
+        movie.record(tilted);
+        movie.record(FrameInput::default());
+        let decoded = Movie::decode(&movie.encode()).expect("decode");
+        assert_eq!(decoded, movie);
+        assert_eq!(decoded.input(0), Some(FrameInput { keys: 0x3FE, ..tilted }));
+        assert_eq!(decoded.input(2), None);
+
+        assert_eq!(movie.check_media(1, 2), Ok(()));
+        assert_eq!(movie.check_media(9, 2), Err(MovieError::RomMismatch));
+        assert_eq!(Movie::decode(b"EGBASAVE"), Err(MovieError::BadMagic));
+        let encoded = movie.encode();
+        assert_eq!(Movie::decode(&encoded[..encoded.len() - 1]), Err(MovieError::Truncated));
+    }
+
+    #[test]
+    fn replay_flags_divergence_at_checkpoints() {
+        let mut movie = Movie::new(0, 0, false, 0, None);
+        movie.check_interval = 4;
+        let mut gba = build_gba();
+        for _ in 0..8 {
+            movie.record(FrameInput::default());
+            FrameInput::default().apply(&mut gba);
+            gba.run_frame();
+            movie.record_check(&gba);
+        }
+
+        let mut replay = build_gba();
+        for frame in 0..movie.len() {
+            movie.input(frame).unwrap().apply(&mut replay);
+            replay.run_frame();
+            assert_eq!(movie.verify(frame + 1, &replay), Ok(()));
+        }
+
+        let mut diverged = build_gba();
+        diverged.write_byte(0x0300_0000, 1);
+        for _ in 0..4 {
+            diverged.run_frame();
+        }
+        assert_eq!(movie.verify(4, &diverged), Err(Desync::State(4)));
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+pub(crate) struct RewindBuffer {
+    interval: u32,
+    budget: usize,
+    countdown: u32,
+    latest: Option<Vec<u8>>,
+    older: VecDeque<Vec<u8>>,
+    used: usize,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(interval: u32, budget: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            budget,
+            countdown: 0,
+            latest: None,
+            older: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    pub(crate) fn frame_done(&mut self) -> bool {
+        if self.countdown == 0 {
+            self.countdown = self.interval - 1;
+            true
+        } else {
+            self.countdown -= 1;
+            false
+        }
+    }
+
+    pub(crate) fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.latest.take() {
+            let delta = encode_delta(&previous, &state);
+            self.used = self.used - previous.len() + delta.len();
+            self.older.push_back(delta);
+        }
+        self.used += state.len();
+        self.latest = Some(state);
+        while self.used > self.budget {
+            match self.older.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Vec<u8>> {
+        let latest = self.latest.take()?;
+        self.used -= latest.len();
+        if let Some(delta) = self.older.pop_back() {
+            self.used -= delta.len();
+            let previous = apply_delta(&latest, &delta);
+            self.used += previous.len();
+            self.latest = Some(previous);
+        }
+        self.countdown = self.interval - 1;
+        Some(latest)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.latest.is_some() as usize + self.older.len()
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn get_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some(&byte) = data.get(*pos) {
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| older[i] ^ newer.get(i).copied().unwrap_or(0);
+    let mut out = Vec::new();
+    put_varint(&mut out, older.len());
+    let mut i = 0;
+    while i < older.len() {
+        let zeros = (i..older.len()).take_while(|&j| xor(j) == 0).count();
+        i += zeros;
+        let literal = (i..older.len()).take_while(|&j| xor(j) != 0).count();
+        put_varint(&mut out, zeros);
+        put_varint(&mut out, literal);
+        out.extend((i..i + literal).map(xor));
+        i += literal;
+    }
+    out
+}
+
+fn apply_delta(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = get_varint(delta, &mut pos);
+    let mut out: Vec<u8> = (0..len).map(|i| newer.get(i).copied().unwrap_or(0)).collect();
+    let mut i = 0;
+    while pos < delta.len() && i < len {
+        i += get_varint(delta, &mut pos);
+        let literal = get_varint(delta, &mut pos);
+        for (byte, x) in out[i..i + literal].iter_mut().zip(&delta[pos..pos + literal]) {
+            *byte ^= x;
+        }
+        pos += literal;
+        i += literal;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_restores_older_snapshot() {
+        let newer = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        for older in [vec![1, 2, 9, 4, 5, 6, 0, 8], vec![1, 2, 3], vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]] {
+            let delta = encode_delta(&older, &newer);
+            assert_eq!(apply_delta(&newer, &delta), older);
+        }
+        assert_eq!(encode_delta(&newer, &newer).len(), 3, "identical snapshots cost a single run");
+    }
+
+    #[test]
+    fn budget_drops_oldest_snapshots() {
+        let mut buffer = RewindBuffer::new(2, 64);
+        assert!(buffer.frame_done());
+        assert!(!buffer.frame_done());
+        assert!(buffer.frame_done());
+
+        for frame in 0..10u8 {
+            let mut state = vec![0; 40];
+            state[frame as usize] = frame + 1;
+            buffer.push(state);
+        }
+        assert!(buffer.used <= 64);
+        assert!((2..10).contains(&buffer.len()));
+
+        let kept = buffer.len();
+        for frame in (10 - kept..10).rev() {
+            let state = buffer.pop().expect("snapshot");
+            assert_eq!(state[frame], frame as u8 + 1);
+        }
+        assert_eq!(buffer.pop(), None);
+        assert_eq!(buffer.used, 0);
+    }
+}
@@ -15,6 +15,40 @@ impl fmt::Display for InvalidROM {
 
 impl Error for InvalidROM {}
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomHeader {
+    pub title: String,
+    pub game_code: String,
+    pub maker_code: String,
+    pub version: u8,
+    pub checksum: u8,
+    pub checksum_valid: bool,
+}
+
+impl RomHeader {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let header = data.get(0xA0..0xC0)?;
+        let text = |bytes: &[u8]| {
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches(['\0', ' '])
+                .to_string()
+        };
+        let computed = header[..0x1D]
+            .iter()
+            .fold(0u8, |acc, &b| acc.wrapping_sub(b))
+            .wrapping_sub(0x19);
+
+        Some(Self {
+            title: text(&header[0x00..0x0C]),
+            game_code: text(&header[0x0C..0x10]),
+            maker_code: text(&header[0x10..0x12]),
+            version: header[0x1C],
+            checksum: header[0x1D],
+            checksum_valid: computed == header[0x1D],
+        })
+    }
+}
+
 impl Rom {
     pub fn new(data: &[u8]) -> Self {
         Self(data.to_vec().into_boxed_slice())
@@ -32,6 +66,10 @@ impl Rom {
         &self.0
     }
 
+    pub fn header(&self) -> Option<RomHeader> {
+        RomHeader::parse(&self.0)
+    }
+
     pub fn get_backup_type(&self) -> BackupType {
         const IDS: [(&[u8], BackupType); 5] = [
             (b"EEPROM_V", BackupType::Eeprom8KB),
@@ -58,3 +96,36 @@ impl Bus for Rom {
         self.0[addr as usize]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_bytes(checksum_fix: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 0x200];
+        data[0xA0..0xAA].copy_from_slice(b"POKEMON EM");
+        data[0xAC..0xB0].copy_from_slice(b"BPEE");
+        data[0xB0..0xB2].copy_from_slice(b"01");
+        data[0xB2] = 0x96;
+        data[0xBC] = 1;
+        let sum = data[0xA0..0xBD].iter().fold(0u8, |acc, &b| acc.wrapping_sub(b));
+        data[0xBD] = sum.wrapping_sub(0x19).wrapping_add(checksum_fix);
+        data
+    }
+
+    #[test]
+    fn parses_header_fields() {
+        let header = Rom::new(&header_bytes(0)).header().expect("header");
+        assert_eq!(header.title, "POKEMON EM");
+        assert_eq!(header.game_code, "BPEE");
+        assert_eq!(header.maker_code, "01");
+        assert_eq!(header.version, 1);
+        assert!(header.checksum_valid);
+    }
+
+    #[test]
+    fn detects_bad_checksum_and_short_roms() {
+        assert!(!Rom::new(&header_bytes(1)).header().unwrap().checksum_valid);
+        assert_eq!(Rom::new(&[0u8; 0xBF]).header(), None);
+    }
+}
@@ -0,0 +1,102 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Event {
+    Video,
+    Timers,
+    Apu,
+    Serial,
+    Dma,
+}
+
+const EVENT_COUNT: usize = 5;
+const NEVER: u64 = u64::MAX;
+
+pub(crate) struct Scheduler {
+    deadlines: [u64; EVENT_COUNT],
+    next: u64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            deadlines: [NEVER; EVENT_COUNT],
+            next: NEVER,
+        }
+    }
+}
+
+impl Scheduler {
+    #[inline]
+    pub(crate) fn next(&self) -> u64 {
+        self.next
+    }
+
+    #[inline]
+    pub(crate) fn is_due(&self, event: Event, now: u64) -> bool {
+        self.deadlines[event as usize] <= now
+    }
+
+    pub(crate) fn schedule(&mut self, event: Event, at: u64) {
+        self.deadlines[event as usize] = at;
+        self.next = self.next.min(at);
+        if at > self.next {
+            self.refresh();
+        }
+    }
+
+    pub(crate) fn schedule_in(&mut self, event: Event, now: u64, cycles: Option<u32>) {
+        match cycles {
+            Some(cycles) => self.schedule(event, now.saturating_add(cycles as u64)),
+            None => self.cancel(event),
+        }
+    }
+
+    pub(crate) fn cancel(&mut self, event: Event) {
+        self.deadlines[event as usize] = NEVER;
+        self.refresh();
+    }
+
+    pub(crate) fn pop_due(&mut self, now: u64) -> Option<Event> {
+        const EVENTS: [Event; EVENT_COUNT] = [
+            Event::Video,
+            Event::Timers,
+            Event::Apu,
+            Event::Serial,
+            Event::Dma,
+        ];
+        let event = EVENTS
+            .into_iter()
+            .filter(|&e| self.is_due(e, now))
+            .min_by_key(|&e| self.deadlines[e as usize])?;
+        self.cancel(event);
+        Some(event)
+    }
+
+    fn refresh(&mut self) {
+        self.next = self.deadlines.iter().copied().min().unwrap_or(NEVER);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_pop_in_timestamp_order() {
+        let mut s = Scheduler::default();
+        s.schedule(Event::Timers, 300);
+        s.schedule(Event::Video, 100);
+        s.schedule_in(Event::Apu, 100, Some(100));
+        s.schedule_in(Event::Serial, 100, None);
+        assert_eq!(s.next(), 100);
+
+        assert_eq!(s.pop_due(250), Some(Event::Video));
+        assert_eq!(s.pop_due(250), Some(Event::Apu));
+        assert_eq!(s.pop_due(250), None);
+        assert_eq!(s.next(), 300);
+
+        s.schedule(Event::Timers, 500);
+        assert_eq!(s.next(), 500, "rescheduling later moves the deadline back");
+        s.cancel(Event::Timers);
+        assert_eq!(s.next(), u64::MAX);
+    }
+}
@@ -1,8 +1,36 @@
+use std::cell::Cell;
+
 use bit::BitIndex;
 
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    link::{LinkMode, LinkPacket, LinkTransport},
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 const SIO_TRANSFER_CYCLES: u32 = 256;
+const LINK_SYNC_CYCLES: u64 = 8192;
+const CPU_CLOCK: u64 = 16_777_216;
+const MULTIPLAYER_BAUD: [u64; 4] = [9600, 38400, 57600, 115200];
+const UART_FIFO_LEN: u8 = 4;
+
+struct Link {
+    transport: Box<dyn LinkTransport>,
+    peer_cycles: [u64; 4],
+    replies: [Option<u32>; 4],
+    next_sync: u64,
+    initiated: Option<LinkMode>,
+}
+
+impl Link {
+    fn peers(&self) -> Vec<usize> {
+        if self.transport.player() == 0 {
+            (1..self.transport.players()).collect()
+        } else {
+            vec![0]
+        }
+    }
+}
 
 #[derive(Default)]
 pub(crate) struct Serial {
@@ -12,22 +40,292 @@ pub(crate) struct Serial {
     siomlt_send: u16,
     rcnt: u16,
     pending_cycles: u32,
+    rx: Cell<u32>,
+    rx_len: Cell<u8>,
+    cycles: u64,
+    link: Option<Link>,
 }
 
 impl Serial {
+    pub(crate) fn connect(&mut self, transport: Box<dyn LinkTransport>) {
+        self.link = Some(Link {
+            transport,
+            peer_cycles: [self.cycles; 4],
+            replies: [None; 4],
+            next_sync: self.cycles + LINK_SYNC_CYCLES,
+            initiated: None,
+        });
+    }
+
+    pub(crate) fn disconnect(&mut self) {
+        self.link = None;
+    }
+
+    pub(crate) fn is_linked(&self) -> bool {
+        self.link.is_some()
+    }
+
+    fn mode(&self) -> LinkMode {
+        match self.siocnt.bit_range(12..14) {
+            0 => LinkMode::Normal8,
+            1 => LinkMode::Normal32,
+            2 => LinkMode::Multiplayer,
+            _ => LinkMode::Uart,
+        }
+    }
+
+    fn player(&self) -> usize {
+        self.link.as_ref().map_or(0, |link| link.transport.player())
+    }
+
+    fn outgoing(&self, mode: LinkMode) -> u32 {
+        match mode {
+            LinkMode::Normal32 => self.siodata32,
+            _ => self.siomlt_send as u32,
+        }
+    }
+
+    fn transfer_cycles(&self, mode: LinkMode, players: usize) -> u32 {
+        let bit_cycles = if self.siocnt.bit(1) { 8 } else { 64 };
+        match mode {
+            LinkMode::Normal8 => bit_cycles * 8,
+            LinkMode::Normal32 => bit_cycles * 32,
+            _ => {
+                let baud = MULTIPLAYER_BAUD[self.siocnt.bit_range(0..2) as usize];
+                (CPU_CLOCK * 18 * players as u64 / baud) as u32
+            }
+        }
+    }
+
+    fn start_transfer(&mut self) {
+        let mode = self.mode();
+        let Some(link) = self.link.as_ref() else {
+            self.pending_cycles = SIO_TRANSFER_CYCLES;
+            return;
+        };
+        let player = link.transport.player();
+        let players = link.transport.players();
+        let initiates = match mode {
+            LinkMode::Multiplayer => player == 0,
+            LinkMode::Normal8 | LinkMode::Normal32 => self.siocnt.bit(0),
+            LinkMode::Uart => return,
+        };
+        if !initiates {
+            if mode == LinkMode::Multiplayer {
+                self.siocnt.set_bit(7, false);
+            }
+            return;
+        }
+
+        let packet = LinkPacket::Start {
+            cycle: self.cycles,
+            mode,
+            data: self.outgoing(mode),
+        };
+        self.pending_cycles = self.transfer_cycles(mode, players);
+        if let Some(link) = self.link.as_mut() {
+            link.replies = [None; 4];
+            link.initiated = Some(mode);
+            link.transport.send(packet);
+        }
+    }
+
+    fn finish_transfer(&mut self) -> bool {
+        let Some(mode) = self.link.as_mut().and_then(|link| link.initiated.take()) else {
+            self.siocnt.set_bit(7, false);
+            return self.siocnt.bit(14);
+        };
+
+        let peers = self.link.as_ref().map(Link::peers).unwrap_or_default();
+        let mut irq = self.wait_for(|link| peers.iter().all(|&p| link.replies[p].is_some()));
+        let mut words = [0xFFFF_FFFF; 4];
+        words[self.player()] = self.outgoing(mode);
+        if let Some(link) = self.link.as_ref() {
+            for &p in &peers {
+                words[p] = link.replies[p].unwrap_or(0xFFFF_FFFF);
+            }
+        }
+
+        match mode {
+            LinkMode::Multiplayer => {
+                let words = words.map(|w| w as u16);
+                if let Some(link) = self.link.as_mut() {
+                    link.transport.send(LinkPacket::Done { words });
+                }
+                irq |= self.latch_multiplayer(words);
+            }
+            _ => {
+                let peer = peers.first().copied().unwrap_or(0);
+                irq |= self.latch_normal(mode, words[peer]);
+            }
+        }
+        irq
+    }
+
+    fn latch_normal(&mut self, mode: LinkMode, data: u32) -> bool {
+        match mode {
+            LinkMode::Normal32 => self.siodata32 = data,
+            _ => {
+                self.siomlt_send.set_bit_range(0..8, data as u16 & 0xFF);
+            }
+        }
+        self.siocnt.set_bit(7, false);
+        self.siocnt.bit(14)
+    }
+
+    fn latch_multiplayer(&mut self, words: [u16; 4]) -> bool {
+        self.siodata32 = words[0] as u32 | (words[1] as u32) << 16;
+        self.siomulti = words;
+        self.siocnt.set_bit_range(4..6, self.player() as u16);
+        self.siocnt.set_bit(6, false);
+        self.siocnt.set_bit(7, false);
+        self.siocnt.bit(14)
+    }
+
+    fn receive_byte(&mut self, byte: u8) -> bool {
+        let capacity = if self.siocnt.bit(8) { UART_FIFO_LEN } else { 1 };
+        let len = self.rx_len.get();
+        if len >= capacity {
+            self.siocnt.set_bit(6, true);
+            return false;
+        }
+        self.rx.set(self.rx.get() | (byte as u32) << (len * 8));
+        self.rx_len.set(len + 1);
+        self.siocnt.bit(11) && self.siocnt.bit(14)
+    }
+
+    fn pop_byte(&self) -> u8 {
+        let len = self.rx_len.get();
+        if len == 0 {
+            return self.siomlt_send as u8;
+        }
+        let rx = self.rx.get();
+        self.rx.set(rx >> 8);
+        self.rx_len.set(len - 1);
+        rx as u8
+    }
+
+    fn handle_packet(&mut self, from: usize, packet: LinkPacket) -> bool {
+        let Some(link) = self.link.as_mut() else {
+            return false;
+        };
+        match packet {
+            LinkPacket::Sync { cycle } => {
+                link.peer_cycles[from] = cycle;
+                false
+            }
+            LinkPacket::Start { cycle, mode, data } => {
+                link.peer_cycles[from] = link.peer_cycles[from].max(cycle);
+                let ready = self.mode() == mode && self.siocnt.bit(7) && !self.siocnt.bit(0);
+                let reply = match mode {
+                    LinkMode::Multiplayer => self.siomlt_send as u32,
+                    _ if ready => self.outgoing(mode),
+                    _ => 0xFFFF_FFFF,
+                };
+                if let Some(link) = self.link.as_mut() {
+                    link.transport.send(LinkPacket::Reply { data: reply });
+                }
+                match mode {
+                    LinkMode::Multiplayer => {
+                        self.siocnt.set_bit(7, true);
+                        false
+                    }
+                    _ if ready => self.latch_normal(mode, data),
+                    _ => false,
+                }
+            }
+            LinkPacket::Reply { data } => {
+                link.replies[from] = Some(data);
+                false
+            }
+            LinkPacket::Done { words } => self.latch_multiplayer(words),
+            LinkPacket::Byte(byte) => self.receive_byte(byte),
+        }
+    }
+
+    fn wait_for(&mut self, done: impl Fn(&Link) -> bool) -> bool {
+        let mut irq = false;
+        loop {
+            let packet = match self.link.as_mut() {
+                Some(link) if !done(link) => link.transport.recv(true),
+                _ => return irq,
+            };
+            match packet {
+                Some((from, packet)) => irq |= self.handle_packet(from, packet),
+                None => {
+                    self.link = None;
+                    return irq;
+                }
+            }
+        }
+    }
+
+    fn sync(&mut self) -> bool {
+        let mut irq = false;
+        let cycles = self.cycles;
+        let Some(link) = self.link.as_mut() else {
+            return false;
+        };
+        if cycles < link.next_sync {
+            return false;
+        }
+        while link.next_sync <= cycles {
+            link.next_sync += LINK_SYNC_CYCLES;
+        }
+        link.transport.send(LinkPacket::Sync { cycle: cycles });
+        while let Some((from, packet)) = self.link.as_mut().and_then(|l| l.transport.recv(false)) {
+            irq |= self.handle_packet(from, packet);
+        }
+        let peers = self.link.as_ref().map(Link::peers).unwrap_or_default();
+        irq | self.wait_for(|link| {
+            peers
+                .iter()
+                .all(|&p| link.peer_cycles[p] + LINK_SYNC_CYCLES >= cycles)
+        })
+    }
+
     pub(crate) fn step(&mut self, cycles: u32) -> bool {
+        self.cycles += cycles as u64;
+        let mut irq = self.sync();
         if self.pending_cycles == 0 {
-            return false;
+            return irq;
         }
         if cycles >= self.pending_cycles {
             self.pending_cycles = 0;
-            self.siocnt.set_bit(7, false);
-            self.siocnt.bit(14)
+            irq |= self.finish_transfer();
         } else {
             self.pending_cycles -= cycles;
-            false
+        }
+        irq
+    }
+
+    pub(crate) fn cycles_to_next_event(&self) -> Option<u32> {
+        let transfer = (self.pending_cycles > 0).then_some(self.pending_cycles);
+        let sync = self
+            .link
+            .as_ref()
+            .map(|link| link.next_sync.saturating_sub(self.cycles).max(1) as u32);
+        match (transfer, sync) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
         }
     }
+
+    fn siocnt_read(&self) -> u16 {
+        let mut value = self.siocnt;
+        match (self.link.as_ref(), self.mode()) {
+            (Some(link), LinkMode::Multiplayer) => {
+                value.set_bit(2, link.transport.player() != 0);
+                value.set_bit(3, true);
+            }
+            (Some(_), LinkMode::Uart) => {
+                value.set_bit(4, false);
+                value.set_bit(5, self.rx_len.get() == 0);
+            }
+            _ => {}
+        }
+        value
+    }
 }
 
 impl Bus for Serial {
@@ -41,8 +339,9 @@ impl Bus for Serial {
             0x125 => (self.siomulti[2] >> 8) as u8,
             0x126 => self.siomulti[3] as u8,
             0x127 => (self.siomulti[3] >> 8) as u8,
-            0x128 => self.siocnt as u8,
-            0x129 => (self.siocnt >> 8) as u8,
+            0x128 => self.siocnt_read() as u8,
+            0x129 => (self.siocnt_read() >> 8) as u8,
+            0x12A if self.link.is_some() && self.mode() == LinkMode::Uart => self.pop_byte(),
             0x12A => self.siomlt_send as u8,
             0x12B => (self.siomlt_send >> 8) as u8,
             0x12C => self.rcnt as u8,
@@ -65,13 +364,20 @@ impl Bus for Serial {
                 let was_busy = self.siocnt.bit(7);
                 self.siocnt.set_bit_range(0..8, value as u16);
                 if !was_busy && self.siocnt.bit(7) {
-                    self.pending_cycles = SIO_TRANSFER_CYCLES;
+                    self.start_transfer();
                 }
             }
             0x129 => {
                 self.siocnt.set_bit_range(8..16, value as u16);
             }
-            0x12A => { self.siomlt_send.set_bit_range(0..8, value as u16); }
+            0x12A => {
+                self.siomlt_send.set_bit_range(0..8, value as u16);
+                if self.mode() == LinkMode::Uart && self.siocnt.bit(10) {
+                    if let Some(link) = self.link.as_mut() {
+                        link.transport.send(LinkPacket::Byte(value));
+                    }
+                }
+            }
             0x12B => { self.siomlt_send.set_bit_range(8..16, value as u16); }
             0x12C => { self.rcnt.set_bit_range(0..8, value as u16); }
             0x12D => { self.rcnt.set_bit_range(8..16, value as u16); }
@@ -80,8 +386,45 @@ impl Bus for Serial {
     }
 }
 
+impl Snapshot for Serial {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.siodata32.save_state(w);
+        self.siomulti.save_state(w);
+        self.siocnt.save_state(w);
+        self.siomlt_send.save_state(w);
+        self.rcnt.save_state(w);
+        self.pending_cycles.save_state(w);
+        self.rx.save_state(w);
+        self.rx_len.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.siodata32.load_state(r)?;
+        self.siomulti.load_state(r)?;
+        self.siocnt.load_state(r)?;
+        self.siomlt_send.load_state(r)?;
+        self.rcnt.load_state(r)?;
+        self.pending_cycles.load_state(r)?;
+        self.rx.load_state(r)?;
+        self.rx_len.load_state(r)?;
+        if self.rx_len.get() > UART_FIFO_LEN {
+            return Err(StateError::Corrupt("uart fifo"));
+        }
+        if let Some(link) = self.link.as_mut() {
+            link.initiated = None;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::mpsc::{channel, Receiver, Sender},
+        thread,
+        time::Duration,
+    };
+
     use super::*;
 
     #[test]
@@ -111,4 +454,144 @@ mod tests {
         s.write_byte(0x128, 0x80);
         assert_eq!(s.pending_cycles, before, "double-write of start while busy must not reset");
     }
+
+    struct ChannelLink {
+        player: usize,
+        players: usize,
+        tx: Vec<(usize, Sender<(usize, LinkPacket)>)>,
+        rx: Receiver<(usize, LinkPacket)>,
+    }
+
+    impl LinkTransport for ChannelLink {
+        fn player(&self) -> usize {
+            self.player
+        }
+
+        fn players(&self) -> usize {
+            self.players
+        }
+
+        fn send(&mut self, packet: LinkPacket) {
+            for (_, tx) in &self.tx {
+                let _ = tx.send((self.player, packet));
+            }
+        }
+
+        fn recv(&mut self, wait: bool) -> Option<(usize, LinkPacket)> {
+            if wait {
+                self.rx.recv_timeout(Duration::from_secs(5)).ok()
+            } else {
+                self.rx.try_recv().ok()
+            }
+        }
+    }
+
+    fn star(players: usize) -> Vec<ChannelLink> {
+        let channels: Vec<_> = (0..players).map(|_| channel()).collect();
+        let senders: Vec<_> = channels.iter().map(|(tx, _)| tx.clone()).collect();
+        channels
+            .into_iter()
+            .enumerate()
+            .map(|(player, (_, rx))| ChannelLink {
+                player,
+                players,
+                tx: if player == 0 {
+                    (1..players).map(|p| (p, senders[p].clone())).collect()
+                } else {
+                    vec![(0, senders[0].clone())]
+                },
+                rx,
+            })
+            .collect()
+    }
+
+    fn write_hword(s: &mut Serial, addr: u32, value: u16) {
+        s.write_byte(addr, value as u8);
+        s.write_byte(addr + 1, (value >> 8) as u8);
+    }
+
+    fn run_linked(
+        mut s: Serial,
+        setup: impl FnOnce(&mut Serial) + Send + 'static,
+    ) -> thread::JoinHandle<Serial> {
+        thread::spawn(move || {
+            setup(&mut s);
+            let mut irq = false;
+            for _ in 0..640 {
+                irq |= s.step(64);
+            }
+            assert!(irq, "serial irq");
+            s
+        })
+    }
+
+    fn linked(transport: ChannelLink) -> Serial {
+        let mut s = Serial::default();
+        s.connect(Box::new(transport));
+        s
+    }
+
+    #[test]
+    fn multiplayer_exchanges_words_between_three_consoles() {
+        let handles: Vec<_> = star(3)
+            .into_iter()
+            .map(|transport| {
+                let player = transport.player;
+                run_linked(linked(transport), move |s| {
+                    write_hword(s, 0x128, 0x6003);
+                    write_hword(s, 0x12A, 0x1000 + player as u16);
+                    if player == 0 {
+                        write_hword(s, 0x128, 0x6083);
+                    }
+                })
+            })
+            .collect();
+
+        for (player, handle) in handles.into_iter().enumerate() {
+            let s = handle.join().unwrap();
+            assert_eq!(s.siomulti[..3], [0x1000, 0x1001, 0x1002], "player {player}");
+            assert_eq!(s.siomulti[3], 0xFFFF);
+            assert_eq!(s.siocnt.bit_range(4..6) as usize, player);
+            assert!(!s.siocnt.bit(7));
+            assert_eq!(s.siocnt_read().bit(2), player != 0);
+        }
+    }
+
+    #[test]
+    fn normal32_swaps_data_with_external_clock_peer() {
+        let mut links = star(2);
+        let slave = links.pop().unwrap();
+        let master = links.pop().unwrap();
+        let master = run_linked(linked(master), |s| {
+            s.siodata32 = 0xAAAA_5555;
+            write_hword(s, 0x128, 0x5009);
+            write_hword(s, 0x128, 0x5089);
+        });
+        let slave = run_linked(linked(slave), |s| {
+            s.siodata32 = 0x1234_5678;
+            write_hword(s, 0x128, 0x5008);
+            write_hword(s, 0x128, 0x5088);
+        });
+        assert_eq!(master.join().unwrap().siodata32, 0x1234_5678);
+        assert_eq!(slave.join().unwrap().siodata32, 0xAAAA_5555);
+    }
+
+    #[test]
+    fn uart_bytes_land_in_receive_fifo() {
+        let mut links = star(2);
+        let mut b = linked(links.pop().unwrap());
+        let mut a = linked(links.pop().unwrap());
+        assert_eq!(a.player(), 0);
+        write_hword(&mut a, 0x128, 0x3400);
+        write_hword(&mut b, 0x128, 0x7900);
+        a.write_byte(0x12A, b'h');
+        a.write_byte(0x12A, b'i');
+
+        assert!(b.siocnt_read().bit(5), "receive FIFO empty before sync");
+        b.cycles = LINK_SYNC_CYCLES - 1;
+        assert!(b.step(1), "receive irq");
+        assert_eq!(b.read_byte(0x12A), b'h');
+        assert_eq!(b.read_byte(0x12A), b'i');
+        assert!(b.siocnt_read().bit(5));
+    }
 }
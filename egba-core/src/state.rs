@@ -0,0 +1,267 @@
+use std::{cell::Cell, error::Error, fmt};
+
+const STATE_MAGIC: &[u8; 8] = b"EGBASAVE";
+pub const STATE_VERSION: u32 = 7;
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    RomMismatch,
+    Truncated,
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "Not an EGBA save state"),
+            StateError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported save state version {} (expected {})", v, STATE_VERSION)
+            }
+            StateError::RomMismatch => write!(f, "Save state was created with a different ROM"),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::Corrupt(what) => write!(f, "Save state is corrupt: {}", what),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+pub(crate) struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new() -> Self {
+        let mut buf = Vec::with_capacity(0x6_0000);
+        buf.extend_from_slice(STATE_MAGIC);
+        buf.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        Self { buf }
+    }
+
+    pub(crate) fn put(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Result<Self, StateError> {
+        if data.len() < STATE_MAGIC.len() || &data[..STATE_MAGIC.len()] != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let mut reader = Self {
+            data,
+            pos: STATE_MAGIC.len(),
+        };
+        let mut version = 0u32;
+        version.load_state(&mut reader)?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        Ok(reader)
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], StateError> {
+        let end = self.pos.checked_add(n).ok_or(StateError::Truncated)?;
+        if end > self.data.len() {
+            return Err(StateError::Truncated);
+        }
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn finish(self) -> Result<(), StateError> {
+        if self.pos != self.data.len() {
+            return Err(StateError::Corrupt("trailing data"));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) trait Snapshot {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError>;
+}
+
+macro_rules! snapshot_int {
+    ($($t:ty),*) => {
+        $(
+            impl Snapshot for $t {
+                fn save_state(&self, w: &mut StateWriter) {
+                    w.put(&self.to_le_bytes());
+                }
+
+                fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+                    let bytes = r.take(std::mem::size_of::<$t>())?;
+                    *self = <$t>::from_le_bytes(bytes.try_into().unwrap());
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+snapshot_int!(u8, i8, u16, u32, i32, u64);
+
+impl Snapshot for bool {
+    fn save_state(&self, w: &mut StateWriter) {
+        (*self as u8).save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let mut v = 0u8;
+        v.load_state(r)?;
+        *self = match v {
+            0 => false,
+            1 => true,
+            _ => return Err(StateError::Corrupt("bool")),
+        };
+        Ok(())
+    }
+}
+
+impl Snapshot for usize {
+    fn save_state(&self, w: &mut StateWriter) {
+        (*self as u64).save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let mut v = 0u64;
+        v.load_state(r)?;
+        *self = usize::try_from(v).map_err(|_| StateError::Corrupt("usize"))?;
+        Ok(())
+    }
+}
+
+impl<T: Snapshot + Copy> Snapshot for Cell<T> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.get().save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.get_mut().load_state(r)
+    }
+}
+
+impl<T: Snapshot, const N: usize> Snapshot for [T; N] {
+    fn save_state(&self, w: &mut StateWriter) {
+        for v in self.iter() {
+            v.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        for v in self.iter_mut() {
+            v.load_state(r)?;
+        }
+        Ok(())
+    }
+}
+
+impl Snapshot for Box<[u8]> {
+    fn save_state(&self, w: &mut StateWriter) {
+        (self.len() as u32).save_state(w);
+        w.put(self);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let mut len = 0u32;
+        len.load_state(r)?;
+        if len as usize != self.len() {
+            return Err(StateError::Corrupt("buffer size"));
+        }
+        self.copy_from_slice(r.take(len as usize)?);
+        Ok(())
+    }
+}
+
+impl Snapshot for Box<[u32]> {
+    fn save_state(&self, w: &mut StateWriter) {
+        (self.len() as u32).save_state(w);
+        for v in self.iter() {
+            w.put(&v.to_le_bytes());
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        let mut len = 0u32;
+        len.load_state(r)?;
+        if len as usize != self.len() {
+            return Err(StateError::Corrupt("buffer size"));
+        }
+        let bytes = r.take(len as usize * 4)?;
+        for (v, chunk) in self.iter_mut().zip(bytes.chunks_exact(4)) {
+            *v = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_round_trip() {
+        let mut w = StateWriter::new();
+        0xABu8.save_state(&mut w);
+        (-5i32).save_state(&mut w);
+        true.save_state(&mut w);
+        [1u16, 2, 3].save_state(&mut w);
+        let data = w.finish();
+
+        let mut r = StateReader::new(&data).expect("header");
+        let (mut a, mut b, mut c, mut d) = (0u8, 0i32, false, [0u16; 3]);
+        a.load_state(&mut r).unwrap();
+        b.load_state(&mut r).unwrap();
+        c.load_state(&mut r).unwrap();
+        d.load_state(&mut r).unwrap();
+        r.finish().unwrap();
+        assert_eq!((a, b, c, d), (0xAB, -5, true, [1, 2, 3]));
+    }
+
+    #[test]
+    fn reader_rejects_bad_header() {
+        assert_eq!(StateReader::new(b"NOTSTATE").err(), Some(StateError::BadMagic));
+
+        let mut data = STATE_MAGIC.to_vec();
+        data.extend_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            StateReader::new(&data).err(),
+            Some(StateError::UnsupportedVersion(STATE_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn buffer_size_mismatch_is_corrupt() {
+        let mut w = StateWriter::new();
+        vec![0u8; 4].into_boxed_slice().save_state(&mut w);
+        let data = w.finish();
+
+        let mut r = StateReader::new(&data).unwrap();
+        let mut buf = vec![0u8; 8].into_boxed_slice();
+        assert_eq!(buf.load_state(&mut r), Err(StateError::Corrupt("buffer size")));
+    }
+
+    #[test]
+    fn short_input_is_truncated() {
+        let mut w = StateWriter::new();
+        7u16.save_state(&mut w);
+        let data = w.finish();
+
+        let mut r = StateReader::new(&data).unwrap();
+        let mut v = 0u32;
+        assert_eq!(v.load_state(&mut r), Err(StateError::Truncated));
+    }
+}
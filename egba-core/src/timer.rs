@@ -1,6 +1,10 @@
 use bit::BitIndex;
 
-use crate::{bus::Bus, control::InterruptType};
+use crate::{
+    bus::Bus,
+    control::InterruptType,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
 const PRESCALER_DIVS: [u32; 4] = [1, 64, 256, 1024];
 const TIMER_START_DELAY: u8 = 2;
@@ -71,20 +75,21 @@ impl Timers {
 
             self.timers[i].internal_counter += remaining;
             let prescaler = self.timers[i].prescaler();
+            let mut ticks = self.timers[i].internal_counter / prescaler;
+            self.timers[i].internal_counter %= prescaler;
+
+            while ticks > 0 {
+                let to_overflow = 0x10000 - self.timers[i].counter as u32;
+                if ticks < to_overflow {
+                    self.timers[i].counter += ticks as u16;
+                    break;
+                }
+                ticks -= to_overflow;
+                self.timers[i].counter = self.timers[i].reload;
+                overflow_flags |= 1 << i;
 
-            while self.timers[i].internal_counter >= prescaler {
-                self.timers[i].internal_counter -= prescaler;
-
-                let (new_val, overflowed) = self.timers[i].counter.overflowing_add(1);
-                if overflowed {
-                    self.timers[i].counter = self.timers[i].reload;
-                    overflow_flags |= 1 << i;
-
-                    if i < 3 {
-                        self.cascade_overflow(i + 1, &mut overflow_flags);
-                    }
-                } else {
-                    self.timers[i].counter = new_val;
+                if i < 3 {
+                    self.cascade_overflow(i + 1, &mut overflow_flags);
                 }
             }
         }
@@ -146,6 +151,119 @@ impl Timers {
     }
 }
 
+impl Timers {
+    pub(crate) fn read_byte_after(&self, addr: u32, elapsed: u32) -> u8 {
+        let timer_idx = ((addr - 0x100) / 4) as usize;
+        if timer_idx >= 4 || addr & 2 != 0 {
+            return self.read_byte(addr);
+        }
+        let t = &self.timers[timer_idx];
+        let mut counter = t.counter;
+        if t.enabled() && !(timer_idx > 0 && t.cascade()) {
+            let running = elapsed.saturating_sub(t.start_delay as u32);
+            let ticks = (t.internal_counter + running) / t.prescaler();
+            counter = counter.wrapping_add(ticks as u16);
+        }
+        if addr & 1 == 0 {
+            counter as u8
+        } else {
+            (counter >> 8) as u8
+        }
+    }
+}
+
+impl Bus for Timers {
+    fn read_byte(&self, addr: u32) -> u8 {
+        let timer_idx = ((addr - 0x100) / 4) as usize;
+        let reg_offset = (addr - 0x100) % 4;
+
+        if timer_idx >= 4 {
+            return 0;
+        }
+
+        match reg_offset {
+            0 => self.timers[timer_idx].counter as u8,
+            1 => (self.timers[timer_idx].counter >> 8) as u8,
+            2 => self.timers[timer_idx].control as u8,
+            3 => (self.timers[timer_idx].control >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    fn write_byte(&mut self, addr: u32, value: u8) {
+        let timer_idx = ((addr - 0x100) / 4) as usize;
+        let reg_offset = (addr - 0x100) % 4;
+
+        if timer_idx >= 4 {
+            return;
+        }
+
+        match reg_offset {
+            0 => {
+                self.timers[timer_idx]
+                    .reload
+                    .set_bit_range(0..8, value as u16);
+            }
+            1 => {
+                self.timers[timer_idx]
+                    .reload
+                    .set_bit_range(8..16, value as u16);
+            }
+            2 => {
+                let was_enabled = self.timers[timer_idx].enabled();
+                self.timers[timer_idx]
+                    .control
+                    .set_bit_range(0..8, value as u16);
+                let now_enabled = self.timers[timer_idx].enabled();
+
+                if !was_enabled && now_enabled {
+                    self.timers[timer_idx].counter = self.timers[timer_idx].reload;
+                    self.timers[timer_idx].internal_counter = 0;
+                    self.timers[timer_idx].start_delay = TIMER_START_DELAY;
+                }
+                self.refresh_any_active();
+            }
+            3 => {
+                self.timers[timer_idx]
+                    .control
+                    .set_bit_range(8..16, value as u16);
+                self.refresh_any_active();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Snapshot for Timer {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.counter.save_state(w);
+        self.reload.save_state(w);
+        self.control.save_state(w);
+        self.internal_counter.save_state(w);
+        self.start_delay.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.counter.load_state(r)?;
+        self.reload.load_state(r)?;
+        self.control.load_state(r)?;
+        self.internal_counter.load_state(r)?;
+        self.start_delay.load_state(r)
+    }
+}
+
+impl Snapshot for Timers {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.timers.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.timers.load_state(r)?;
+        self.refresh_any_active();
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,65 +341,3 @@ mod tests {
         }
     }
 }
-
-impl Bus for Timers {
-    fn read_byte(&self, addr: u32) -> u8 {
-        let timer_idx = ((addr - 0x100) / 4) as usize;
-        let reg_offset = (addr - 0x100) % 4;
-
-        if timer_idx >= 4 {
-            return 0;
-        }
-
-        match reg_offset {
-            0 => self.timers[timer_idx].counter as u8,
-            1 => (self.timers[timer_idx].counter >> 8) as u8,
-            2 => self.timers[timer_idx].control as u8,
-            3 => (self.timers[timer_idx].control >> 8) as u8,
-            _ => 0,
-        }
-    }
-
-    fn write_byte(&mut self, addr: u32, value: u8) {
-        let timer_idx = ((addr - 0x100) / 4) as usize;
-        let reg_offset = (addr - 0x100) % 4;
-
-        if timer_idx >= 4 {
-            return;
-        }
-
-        match reg_offset {
-            0 => {
-                self.timers[timer_idx]
-                    .reload
-                    .set_bit_range(0..8, value as u16);
-            }
-            1 => {
-                self.timers[timer_idx]
-                    .reload
-                    .set_bit_range(8..16, value as u16);
-            }
-            2 => {
-                let was_enabled = self.timers[timer_idx].enabled();
-                self.timers[timer_idx]
-                    .control
-                    .set_bit_range(0..8, value as u16);
-                let now_enabled = self.timers[timer_idx].enabled();
-
-                if !was_enabled && now_enabled {
-                    self.timers[timer_idx].counter = self.timers[timer_idx].reload;
-                    self.timers[timer_idx].internal_counter = 0;
-                    self.timers[timer_idx].start_delay = TIMER_START_DELAY;
-                }
-                self.refresh_any_active();
-            }
-            3 => {
-                self.timers[timer_idx]
-                    .control
-                    .set_bit_range(8..16, value as u16);
-                self.refresh_any_active();
-            }
-            _ => {}
-        }
-    }
-}
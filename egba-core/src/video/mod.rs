@@ -2,12 +2,15 @@ use bit::BitIndex;
 
 use crate::bus::Bus;
 use crate::control::InterruptType;
+use crate::gba::Renderer;
+use crate::state::{Snapshot, StateError, StateReader, StateWriter};
 
 mod background;
 mod render;
 mod sprite;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
 pub(crate) const WIDTH: usize = 240;
@@ -29,6 +32,8 @@ pub(crate) enum VideoEvent {
 
 pub(crate) struct Video {
     frame_buffer: Box<[u32]>,
+    renderer: Renderer,
+    line_x: usize,
 
     dot_cycle: u32,
     vcount: u16,
@@ -62,6 +67,8 @@ impl Video {
     pub(crate) fn new() -> Self {
         Self {
             frame_buffer: vec![0; WIDTH * HEIGHT].into_boxed_slice(),
+            renderer: Renderer::default(),
+            line_x: 0,
             dot_cycle: 0,
             vcount: 0,
             dispcnt: 0,
@@ -97,7 +104,13 @@ impl Video {
             self.dispstat.set_bit(1, true);
 
             if self.vcount < HEIGHT as u16 {
-                self.render_scanline();
+                match self.renderer {
+                    Renderer::Scanline => self.render_scanline(),
+                    Renderer::Dot => {
+                        self.render_span(self.line_x, WIDTH);
+                        self.line_x = WIDTH;
+                    }
+                }
 
                 for i in 0..2 {
                     let pb = self.bgaffine[i][1] as i16 as i32;
@@ -118,6 +131,7 @@ impl Video {
 
         if self.dot_cycle >= SCANLINE_CYCLES {
             self.dot_cycle = 0;
+            self.line_x = 0;
             self.dispstat.set_bit(1, false);
 
             self.vcount += 1;
@@ -198,6 +212,47 @@ impl Video {
         }
     }
 
+    pub(crate) fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    pub(crate) fn catch_up(&mut self, elapsed: u32) {
+        if self.renderer != Renderer::Dot || self.vcount >= HEIGHT as u16 {
+            return;
+        }
+        let dot = self.dot_cycle.saturating_add(elapsed);
+        let x = if dot >= HDRAW_CYCLES {
+            WIDTH
+        } else {
+            (dot / 4) as usize
+        };
+        if x > self.line_x {
+            self.render_span(self.line_x, x);
+            self.line_x = x;
+        }
+    }
+
+    /// Whether the PPU reads from `region` at the current dot. This is a coarse approximation:
+    /// it reports the whole HDraw (or OAM fetch window) as busy instead of following the
+    /// per-dot fetch schedule of each layer, so callers charge a flat penalty per access.
+    pub(crate) fn memory_busy(&self, region: u32, elapsed: u32) -> bool {
+        if self.forced_blank() {
+            return false;
+        }
+        let dot = self.dot_cycle + elapsed;
+        let line = (self.vcount as u32 + dot / SCANLINE_CYCLES) % TOTAL_LINES as u32;
+        if line >= HEIGHT as u32 {
+            return false;
+        }
+        let hdraw = dot % SCANLINE_CYCLES < HDRAW_CYCLES;
+        match region {
+            0x5 => hdraw,
+            0x6 => hdraw && self.dispcnt.bit_range(8..13) != 0,
+            0x7 => self.dispcnt.bit(12) && (hdraw || !self.dispcnt.bit(5)),
+            _ => false,
+        }
+    }
+
     pub(crate) fn framebuffer(&self) -> &[u32] {
         &self.frame_buffer
     }
@@ -444,3 +499,65 @@ impl Bus for Video {
         }
     }
 }
+
+impl Snapshot for Video {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.frame_buffer.save_state(w);
+        self.dot_cycle.save_state(w);
+        self.line_x.save_state(w);
+        self.vcount.save_state(w);
+        self.dispcnt.save_state(w);
+        self.dispstat.save_state(w);
+        self.bgcnt.save_state(w);
+        self.bgofs_x.save_state(w);
+        self.bgofs_y.save_state(w);
+        self.bgref_x.save_state(w);
+        self.bgref_y.save_state(w);
+        self.bgaffine.save_state(w);
+        self.internal_ref_x.save_state(w);
+        self.internal_ref_y.save_state(w);
+        self.win_h.save_state(w);
+        self.win_v.save_state(w);
+        self.winin.save_state(w);
+        self.winout.save_state(w);
+        self.mosaic.save_state(w);
+        self.bldcnt.save_state(w);
+        self.bldalpha.save_state(w);
+        self.bldy.save_state(w);
+        self.vram.save_state(w);
+        self.palette.save_state(w);
+        self.oam.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.frame_buffer.load_state(r)?;
+        self.dot_cycle.load_state(r)?;
+        self.line_x.load_state(r)?;
+        self.vcount.load_state(r)?;
+        self.dispcnt.load_state(r)?;
+        self.dispstat.load_state(r)?;
+        self.bgcnt.load_state(r)?;
+        self.bgofs_x.load_state(r)?;
+        self.bgofs_y.load_state(r)?;
+        self.bgref_x.load_state(r)?;
+        self.bgref_y.load_state(r)?;
+        self.bgaffine.load_state(r)?;
+        self.internal_ref_x.load_state(r)?;
+        self.internal_ref_y.load_state(r)?;
+        self.win_h.load_state(r)?;
+        self.win_v.load_state(r)?;
+        self.winin.load_state(r)?;
+        self.winout.load_state(r)?;
+        self.mosaic.load_state(r)?;
+        self.bldcnt.load_state(r)?;
+        self.bldalpha.load_state(r)?;
+        self.bldy.load_state(r)?;
+        self.vram.load_state(r)?;
+        self.palette.load_state(r)?;
+        self.oam.load_state(r)?;
+        if self.dot_cycle >= SCANLINE_CYCLES || self.vcount >= TOTAL_LINES || self.line_x > WIDTH {
+            return Err(StateError::Corrupt("video timing"));
+        }
+        Ok(())
+    }
+}
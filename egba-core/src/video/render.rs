@@ -1,5 +1,7 @@
 #![allow(clippy::needless_range_loop, clippy::too_many_arguments)]
 
+use std::ops::Range;
+
 use crate::video::sprite::Sprite;
 
 use super::{Video, HEIGHT, WIDTH};
@@ -38,13 +40,23 @@ impl Default for PixelInfo {
 
 impl Video {
     pub(crate) fn render_scanline(&mut self) {
+        self.render_pixels(0..WIDTH);
+    }
+
+    pub(crate) fn render_span(&mut self, from: usize, to: usize) {
+        if from < to {
+            self.render_pixels(from..to);
+        }
+    }
+
+    fn render_pixels(&mut self, span: Range<usize>) {
         let y = self.vcount as usize;
         if y >= HEIGHT {
             return;
         }
 
         if self.forced_blank() {
-            for x in 0..WIDTH {
+            for x in span {
                 self.frame_buffer[y * WIDTH + x] = 0x00FFFFFF;
             }
             return;
@@ -63,36 +75,37 @@ impl Video {
         let mut objwin_mask = [false; WIDTH];
         let any_window_enabled = self.win0_enabled() || self.win1_enabled() || self.objwin_enabled();
         if any_window_enabled && self.objwin_enabled() && self.dispcnt.bit(12) {
-            self.render_objwin_mask(y, &mut objwin_mask);
+            self.render_objwin_mask(y, &mut objwin_mask, &span);
         }
 
-        let win_mask = self.compute_window_mask(y, &objwin_mask);
+        let win_mask = self.compute_window_mask(y, &objwin_mask, &span);
 
         let mode = self.bg_mode();
 
         match mode {
             0 => {
-                self.render_tiled_bgs(y, &mut top, &mut second, &[0, 1, 2, 3], &[false; 4], &win_mask);
+                self.render_tiled_bgs(y, &mut top, &mut second, &[0, 1, 2, 3], &[false; 4], &win_mask, &span);
             }
             1 => {
-                self.render_tiled_bgs(y, &mut top, &mut second, &[0, 1, 2], &[false, false, true], &win_mask);
+                let affine = [false, false, true];
+                self.render_tiled_bgs(y, &mut top, &mut second, &[0, 1, 2], &affine, &win_mask, &span);
             }
             2 => {
-                self.render_tiled_bgs(y, &mut top, &mut second, &[2, 3], &[true, true], &win_mask);
+                self.render_tiled_bgs(y, &mut top, &mut second, &[2, 3], &[true, true], &win_mask, &span);
             }
-            3 => self.render_mode3(y, &mut top, &win_mask),
-            4 => self.render_mode4(y, &mut top, &win_mask),
-            5 => self.render_mode5(y, &mut top, &win_mask),
+            3 => self.render_mode3(y, &mut top, &win_mask, &span),
+            4 => self.render_mode4(y, &mut top, &win_mask, &span),
+            5 => self.render_mode5(y, &mut top, &win_mask, &span),
             _ => {}
         }
 
         if self.dispcnt.bit(12) {
             for prio in (0..=3).rev() {
-                self.render_sprites_layered(y, &mut top, &mut second, prio, &win_mask);
+                self.render_sprites_layered(y, &mut top, &mut second, prio, &win_mask, &span);
             }
         }
 
-        self.apply_blending(y, &top, &second, &win_mask);
+        self.apply_blending(y, &top, &second, &win_mask, span);
     }
 
     fn render_tiled_bgs(
@@ -103,6 +116,7 @@ impl Video {
         bgs: &[usize],
         is_affine: &[bool],
         win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
     ) {
         for prio in (0..=3u8).rev() {
             for (idx, &bg) in bgs.iter().enumerate().rev() {
@@ -115,16 +129,22 @@ impl Video {
                 }
 
                 if is_affine.get(idx).copied().unwrap_or(false) {
-                    self.render_affine_bg(bg, y, top, second, prio, win_mask);
+                    self.render_affine_bg(bg, y, top, second, prio, win_mask, span);
                 } else {
-                    self.render_text_bg(bg, y, top, second, prio, win_mask);
+                    self.render_text_bg(bg, y, top, second, prio, win_mask, span);
                 }
             }
         }
     }
 
-    fn render_mode3(&self, y: usize, top: &mut [PixelInfo; WIDTH], win_mask: &[u8; WIDTH]) {
-        for x in 0..WIDTH {
+    fn render_mode3(
+        &self,
+        y: usize,
+        top: &mut [PixelInfo; WIDTH],
+        win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
+    ) {
+        for x in span.clone() {
             if win_mask[x] & WIN_BG2 == 0 {
                 continue;
             }
@@ -141,9 +161,15 @@ impl Video {
         }
     }
 
-    fn render_mode4(&self, y: usize, top: &mut [PixelInfo; WIDTH], win_mask: &[u8; WIDTH]) {
+    fn render_mode4(
+        &self,
+        y: usize,
+        top: &mut [PixelInfo; WIDTH],
+        win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
+    ) {
         let base = if self.frame_select() { 0xA000 } else { 0 };
-        for x in 0..WIDTH {
+        for x in span.clone() {
             if win_mask[x] & WIN_BG2 == 0 {
                 continue;
             }
@@ -160,15 +186,18 @@ impl Video {
         }
     }
 
-    fn render_mode5(&self, y: usize, top: &mut [PixelInfo; WIDTH], win_mask: &[u8; WIDTH]) {
+    fn render_mode5(
+        &self,
+        y: usize,
+        top: &mut [PixelInfo; WIDTH],
+        win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
+    ) {
         let base = if self.frame_select() { 0xA000 } else { 0 };
         if y >= 128 {
             return;
         }
-        for x in 0..WIDTH {
-            if x >= 160 {
-                continue;
-            }
+        for x in span.start..span.end.min(160) {
             if win_mask[x] & WIN_BG2 == 0 {
                 continue;
             }
@@ -193,6 +222,7 @@ impl Video {
         second: &mut [PixelInfo; WIDTH],
         prio: u8,
         win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
     ) {
         let bgcnt = self.bgcnt[bg];
         let char_base = ((bgcnt >> 2) & 3) as usize * 0x4000;
@@ -224,7 +254,7 @@ impl Video {
         let pixel_y = map_y % 8;
 
         let bg_win_bit = 1u8 << bg;
-        for x in 0..WIDTH {
+        for x in span.clone() {
             if win_mask[x] & bg_win_bit == 0 {
                 continue;
             }
@@ -318,6 +348,7 @@ impl Video {
         second: &mut [PixelInfo; WIDTH],
         prio: u8,
         win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
     ) {
         let bgcnt = self.bgcnt[bg];
         let char_base = ((bgcnt >> 2) & 3) as usize * 0x4000;
@@ -345,7 +376,7 @@ impl Video {
         let mh = if mosaic_on { self.bg_mosaic_h() as i32 } else { 1 };
 
         let bg_win_bit = 1u8 << bg;
-        for x in 0..WIDTH {
+        for x in span.clone() {
             if win_mask[x] & bg_win_bit == 0 {
                 continue;
             }
@@ -410,6 +441,7 @@ impl Video {
         second: &mut [PixelInfo; WIDTH],
         prio: u8,
         win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
     ) {
         let is_1d_mapping = self.dispcnt.bit(6);
         for i in (0..128).rev() {
@@ -466,6 +498,7 @@ impl Video {
                     bound_h,
                     is_semi_transparent,
                     win_mask,
+                    span,
                 );
             } else {
                 self.render_normal_sprite_layered(
@@ -479,6 +512,7 @@ impl Video {
                     ly,
                     is_semi_transparent,
                     win_mask,
+                    span,
                 );
             }
         }
@@ -496,6 +530,7 @@ impl Video {
         ly: i16,
         semi_transparent: bool,
         win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
     ) {
         let (mh, mv) = if sprite.mosaic {
             (self.obj_mosaic_h() as i16, self.obj_mosaic_v() as i16)
@@ -507,7 +542,7 @@ impl Video {
 
         for lx in 0..w {
             let screen_x = sprite.x + lx;
-            if screen_x < 0 || screen_x >= WIDTH as i16 {
+            if screen_x < 0 || !span.contains(&(screen_x as usize)) {
                 continue;
             }
             if win_mask[screen_x as usize] & WIN_OBJ == 0 {
@@ -549,6 +584,7 @@ impl Video {
         bound_h: i16,
         semi_transparent: bool,
         win_mask: &[u8; WIDTH],
+        span: &Range<usize>,
     ) {
         let group = sprite.affine_param as usize;
         let pa = self.read_oam_affine_param(group, 0);
@@ -566,7 +602,7 @@ impl Video {
 
         for lx in 0..bound_w {
             let screen_x = sprite.x + lx;
-            if screen_x < 0 || screen_x >= WIDTH as i16 {
+            if screen_x < 0 || !span.contains(&(screen_x as usize)) {
                 continue;
             }
             if win_mask[screen_x as usize] & WIN_OBJ == 0 {
@@ -626,6 +662,7 @@ impl Video {
         top: &[PixelInfo; WIDTH],
         second: &[PixelInfo; WIDTH],
         win_mask: &[u8; WIDTH],
+        span: Range<usize>,
     ) {
         let blend_mode = (self.bldcnt >> 6) & 3;
         let first_targets = self.bldcnt & 0x3F;
@@ -635,7 +672,7 @@ impl Video {
         let evb = ((self.bldalpha >> 8) & 0x1F).min(16) as u32;
         let evy = (self.bldy & 0x1F).min(16) as u32;
 
-        for x in 0..WIDTH {
+        for x in span {
             let tp = top[x];
             let sp = second[x];
 
@@ -786,7 +823,12 @@ impl Video {
         self.dispcnt.bit(15)
     }
 
-    fn compute_window_mask(&self, y: usize, objwin_mask: &[bool; WIDTH]) -> [u8; WIDTH] {
+    fn compute_window_mask(
+        &self,
+        y: usize,
+        objwin_mask: &[bool; WIDTH],
+        span: &Range<usize>,
+    ) -> [u8; WIDTH] {
         let win0 = self.win0_enabled();
         let win1 = self.win1_enabled();
         let objwin = self.objwin_enabled();
@@ -824,7 +866,7 @@ impl Video {
 
         let mut mask = [0u8; WIDTH];
 
-        for x in 0..WIDTH {
+        for x in span.clone() {
             if win0 && win0_in_y {
                 let in_x = if win0_x1 <= win0_x2 {
                     x >= win0_x1 && x < win0_x2
@@ -860,7 +902,7 @@ impl Video {
         mask
     }
 
-    fn render_objwin_mask(&self, y: usize, mask: &mut [bool; WIDTH]) {
+    fn render_objwin_mask(&self, y: usize, mask: &mut [bool; WIDTH], span: &Range<usize>) {
         let is_1d_mapping = self.dispcnt.bit(6);
 
         for i in (0..128).rev() {
@@ -910,7 +952,7 @@ impl Video {
 
                 for lx in 0..bound_w {
                     let screen_x = sprite.x + lx;
-                    if screen_x < 0 || screen_x >= WIDTH as i16 {
+                    if screen_x < 0 || !span.contains(&(screen_x as usize)) {
                         continue;
                     }
 
@@ -931,7 +973,7 @@ impl Video {
 
                 for lx in 0..orig_w {
                     let screen_x = sprite.x + lx;
-                    if screen_x < 0 || screen_x >= WIDTH as i16 {
+                    if screen_x < 0 || !span.contains(&(screen_x as usize)) {
                         continue;
                     }
 
@@ -2,6 +2,7 @@
 mod tests {
     use bit::BitIndex;
     use crate::bus::Bus;
+    use crate::gba::Renderer;
     use crate::video::{Video, WIDTH};
 
     fn make_video() -> Video {
@@ -332,4 +333,76 @@ mod tests {
         assert_eq!(hblank_in_vblanks, 68, "one HBlankInVBlank per VBlank line");
         assert_eq!(vblanks, 1, "exactly one VBlank per frame, never dropped");
     }
+
+    #[test]
+    fn memory_busy_follows_enabled_layers() {
+        let mut v = make_video();
+        assert!(v.memory_busy(0x5, 0), "backdrop is always read");
+        assert!(!v.memory_busy(0x6, 0), "no layers, no vram fetches");
+        assert!(!v.memory_busy(0x7, 0));
+
+        v.write_byte(0x001, 0x04);
+        assert!(v.memory_busy(0x6, 0));
+        assert!(!v.memory_busy(0x6, 960), "pending cycles move the beam into hblank");
+        assert!(v.memory_busy(0x6, 1232), "next line");
+
+        v.write_byte(0x000, 0x80);
+        assert!(!v.memory_busy(0x5, 0), "forced blank");
+    }
+
+    fn backdrop_split(renderer: Renderer) -> (u32, u32) {
+        let mut v = make_video();
+        v.set_renderer(renderer);
+        v.palette[0] = 0x1F;
+        for _ in 0..400 {
+            v.step();
+        }
+        v.catch_up(0);
+        v.palette[0] = 0xE0;
+        v.palette[1] = 0x03;
+        for _ in 400..960 {
+            v.step();
+        }
+        (v.framebuffer()[99], v.framebuffer()[100])
+    }
+
+    #[test]
+    fn spans_compose_to_the_full_scanline() {
+        let mut v = make_video();
+        for (i, byte) in v.vram.iter_mut().enumerate() {
+            *byte = (i * 7 + i / 64) as u8;
+        }
+        for (i, byte) in v.palette.iter_mut().enumerate() {
+            *byte = (i * 29) as u8;
+        }
+        v.oam[..6].copy_from_slice(&[0x00, 0x00, 50, 0x80, 0x00, 0x02]);
+        let regs = [
+            (0x000, 0x40), (0x001, 0x31), (0x009, 0x08),
+            (0x040, 120), (0x041, 30), (0x044, 160),
+            (0x048, 0x3F), (0x04A, 0x11),
+            (0x050, 0x41), (0x051, 0x3F), (0x052, 8), (0x053, 8),
+        ];
+        for (addr, value) in regs {
+            v.write_byte(addr, value);
+        }
+
+        v.render_scanline();
+        let full = v.framebuffer()[..WIDTH].to_vec();
+        v.frame_buffer.fill(0);
+        for from in (0..WIDTH).step_by(7) {
+            v.render_span(from, (from + 7).min(WIDTH));
+        }
+        assert_eq!(v.framebuffer()[..WIDTH], full);
+        assert!(full.iter().any(|&px| px != full[0]), "line has detail to compare");
+    }
+
+    #[test]
+    fn dot_renderer_shows_mid_line_palette_write() {
+        assert_eq!(backdrop_split(Renderer::Dot), (0xF80000, 0x00F800));
+        assert_eq!(
+            backdrop_split(Renderer::Scanline),
+            (0x00F800, 0x00F800),
+            "scanline renderer latches the line at hblank"
+        );
+    }
 }
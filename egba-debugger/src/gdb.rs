@@ -0,0 +1,502 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+};
+
+use egba_core::{
+    cpu::cpu::PC_INDEX,
+    debug::{StopReason, WatchKind},
+    gba::{CYCLES_PER_FRAME, GBA},
+};
+
+const CPSR_REGISTER: usize = 25;
+const FPS_REGISTER: usize = 24;
+const FPA_REGISTER_HEX: usize = 24;
+const G_PACKET_HEX: usize = (16 + 2) * 8 + 8 * FPA_REGISTER_HEX;
+const PACKET_SIZE: u32 = 0x1000;
+
+const INTERRUPTED: &str = "S02";
+const TRAPPED: &str = "S05";
+
+fn stop_reply(reason: StopReason) -> String {
+    match reason {
+        StopReason::Watchpoint { addr, kind } => {
+            let name = match kind {
+                WatchKind::Write => "watch",
+                WatchKind::Read => "rwatch",
+                WatchKind::Access => "awatch",
+            };
+            format!("T05{}:{:08x};", name, addr)
+        }
+        StopReason::Breakpoint(_) | StopReason::Irq | StopReason::SoftwareInterrupt(_) => {
+            TRAPPED.to_string()
+        }
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    inbox: Vec<u8>,
+    no_ack: bool,
+}
+
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<Client>,
+    running: bool,
+}
+
+impl GdbStub {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        Ok(Self {
+            listener,
+            client: None,
+            running: false,
+        })
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn wait_for_client(&mut self) -> io::Result<()> {
+        self.listener.set_nonblocking(false)?;
+        let (stream, _) = self.listener.accept()?;
+        self.attach(stream)
+    }
+
+    fn attach(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(false)?;
+        self.client = Some(Client { stream, inbox: Vec::new(), no_ack: false });
+        self.running = false;
+        Ok(())
+    }
+
+    fn detach(&mut self, gba: &mut GBA) {
+        self.client = None;
+        self.running = true;
+        gba.debug_hooks().clear();
+    }
+
+    pub fn run_frame(&mut self, gba: &mut GBA) {
+        if self.client.is_none() {
+            if let Err(err) = self.accept_pending() {
+                eprintln!("GDB/: Failed to accept connection due to err: {err}");
+            }
+        }
+        if self.client.is_none() {
+            gba.run_frame();
+            return;
+        }
+
+        if let Err(err) = self.poll(gba) {
+            eprintln!("GDB/: Connection dropped due to err: {err}");
+            self.detach(gba);
+            return;
+        }
+
+        if self.running {
+            if let Some(reason) = gba.run_frame() {
+                self.running = false;
+                if let Err(err) = self.send(&stop_reply(reason)) {
+                    eprintln!("GDB/: Connection dropped due to err: {err}");
+                    self.detach(gba);
+                }
+            }
+        }
+    }
+
+    fn accept_pending(&mut self) -> io::Result<()> {
+        self.listener.set_nonblocking(true)?;
+        match self.listener.accept() {
+            Ok((stream, _)) => self.attach(stream),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn poll(&mut self, gba: &mut GBA) -> io::Result<()> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+        let mut buf = [0u8; 4096];
+        client.stream.set_nonblocking(true)?;
+        let read = client.stream.read(&mut buf);
+        client.stream.set_nonblocking(false)?;
+        match read {
+            Ok(0) => {
+                self.detach(gba);
+                return Ok(());
+            }
+            Ok(n) => client.inbox.extend_from_slice(&buf[..n]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+            Err(err) => return Err(err),
+        }
+
+        while let Some(packet) = self.next_packet()? {
+            if let Some(reply) = self.handle(gba, &packet) {
+                self.send(&reply)?;
+            }
+            if packet == "QStartNoAckMode" {
+                if let Some(client) = self.client.as_mut() {
+                    client.no_ack = true;
+                }
+            }
+            if packet == "D" || packet == "k" {
+                self.detach(gba);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(None);
+        };
+        loop {
+            match client.inbox.first() {
+                None => return Ok(None),
+                Some(b'+') | Some(b'-') => {
+                    client.inbox.remove(0);
+                }
+                Some(0x03) => {
+                    client.inbox.remove(0);
+                    return Ok(Some("\x03".to_string()));
+                }
+                Some(b'$') => {
+                    let Some(end) = client.inbox.iter().position(|&b| b == b'#') else {
+                        return Ok(None);
+                    };
+                    if client.inbox.len() < end + 3 {
+                        return Ok(None);
+                    }
+                    let frame: Vec<u8> = client.inbox.drain(..end + 3).collect();
+                    let payload = &frame[1..end];
+                    let expected = std::str::from_utf8(&frame[end + 1..])
+                        .ok()
+                        .and_then(|s| u8::from_str_radix(s, 16).ok());
+                    let valid = expected == Some(checksum(payload));
+                    if !client.no_ack {
+                        client.stream.write_all(if valid { b"+" } else { b"-" })?;
+                    }
+                    if valid {
+                        return Ok(Some(String::from_utf8_lossy(payload).into_owned()));
+                    }
+                }
+                Some(_) => {
+                    client.inbox.remove(0);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, payload: &str) -> io::Result<()> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+        let frame = format!("${}#{:02x}", payload, checksum(payload.as_bytes()));
+        client.stream.write_all(frame.as_bytes())
+    }
+
+    fn handle(&mut self, gba: &mut GBA, packet: &str) -> Option<String> {
+        let (cmd, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match cmd {
+            "\x03" => {
+                if !self.running {
+                    return None;
+                }
+                self.running = false;
+                INTERRUPTED.to_string()
+            }
+            "?" => TRAPPED.to_string(),
+            "g" => read_registers(gba),
+            "G" => match write_registers(gba, args) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) => read_register(gba, n).unwrap_or_else(|| "E01".to_string()),
+                Err(_) => "E01".to_string(),
+            },
+            "P" => match parse_register_write(args).and_then(|(n, v)| write_register(gba, n, v)) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            "m" => match parse_addr_len(args) {
+                Some((_, len)) if len > PACKET_SIZE / 2 => "E01".to_string(),
+                Some((addr, len)) => (0..len)
+                    .map(|i| format!("{:02x}", gba.read_byte(addr.wrapping_add(i))))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => match write_memory(gba, args) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            "c" | "s" => {
+                if !args.is_empty() {
+                    match u32::from_str_radix(args, 16) {
+                        Ok(addr) => gba.set_register(PC_INDEX, addr),
+                        Err(_) => return Some("E01".to_string()),
+                    }
+                }
+                if cmd == "c" {
+                    self.running = true;
+                    return None;
+                }
+                single_step(gba)
+            }
+            "Z" | "z" => update_stop_point(gba, cmd == "Z", args),
+            "H" | "T" => "OK".to_string(),
+            "D" => "OK".to_string(),
+            "k" => return None,
+            "q" | "Q" => query(packet),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+}
+
+fn update_stop_point(gba: &mut GBA, insert: bool, args: &str) -> String {
+    let mut parts = args.splitn(2, ',');
+    let kind = parts.next().unwrap_or("");
+    let Some((addr, len)) = parts.next().and_then(parse_addr_len) else {
+        return "E01".to_string();
+    };
+    let watch = match kind {
+        "0" | "1" => {
+            let hooks = gba.debug_hooks();
+            if insert {
+                hooks.add_breakpoint(addr);
+            } else {
+                hooks.remove_breakpoint(addr);
+            }
+            return "OK".to_string();
+        }
+        "2" => WatchKind::Write,
+        "3" => WatchKind::Read,
+        "4" => WatchKind::Access,
+        _ => return String::new(),
+    };
+    let range = addr..addr.wrapping_add(len.max(1));
+    if insert {
+        gba.debug_hooks().add_watchpoint(range, watch);
+    } else {
+        gba.debug_hooks().remove_watchpoint(range, watch);
+    }
+    "OK".to_string()
+}
+
+fn single_step(gba: &mut GBA) -> String {
+    let target = gba.bus_cycles() + CYCLES_PER_FRAME as u64;
+    let pc = gba.pc();
+    loop {
+        let halted = gba.is_halted();
+        if let Some(reason) = gba.step_one_instruction() {
+            return stop_reply(reason);
+        }
+        if !halted || gba.pc() != pc || gba.bus_cycles() >= target {
+            return TRAPPED.to_string();
+        }
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn query(packet: &str) -> String {
+    match packet.split(':').next().unwrap_or("") {
+        "qSupported" => format!("PacketSize={:x};QStartNoAckMode+", PACKET_SIZE),
+        "QStartNoAckMode" => "OK".to_string(),
+        "qAttached" => "1".to_string(),
+        "qC" => "QC1".to_string(),
+        "qfThreadInfo" => "m1".to_string(),
+        "qsThreadInfo" => "l".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn hex_u32(value: u32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn parse_hex_u32(hex: &str) -> Option<u32> {
+    if hex.len() != 8 {
+        return None;
+    }
+    let mut bytes = [0u8; 4];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn parse_addr_len(args: &str) -> Option<(u32, u32)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u32::from_str_radix(addr, 16).ok()?,
+        u32::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn parse_register_write(args: &str) -> Option<(usize, u32)> {
+    let (n, value) = args.split_once('=')?;
+    Some((usize::from_str_radix(n, 16).ok()?, parse_hex_u32(value)?))
+}
+
+fn register_value(gba: &GBA, n: usize) -> u32 {
+    if n == PC_INDEX {
+        gba.pc()
+    } else {
+        gba.get_cpu().reg[n]
+    }
+}
+
+fn read_register(gba: &GBA, n: usize) -> Option<String> {
+    match n {
+        0..=15 => Some(hex_u32(register_value(gba, n))),
+        16..=23 => Some("0".repeat(FPA_REGISTER_HEX)),
+        FPS_REGISTER => Some(hex_u32(0)),
+        CPSR_REGISTER => Some(hex_u32(gba.cpsr())),
+        _ => None,
+    }
+}
+
+fn write_register(gba: &mut GBA, n: usize, value: u32) -> Option<()> {
+    match n {
+        0..=15 => gba.set_register(n, value),
+        16..=FPS_REGISTER => {}
+        CPSR_REGISTER => gba.set_cpsr(value),
+        _ => return None,
+    }
+    Some(())
+}
+
+fn read_registers(gba: &GBA) -> String {
+    let mut out: String = (0..16).map(|n| hex_u32(register_value(gba, n))).collect();
+    out.push_str(&"0".repeat(8 * FPA_REGISTER_HEX));
+    out.push_str(&hex_u32(0));
+    out.push_str(&hex_u32(gba.cpsr()));
+    out
+}
+
+fn write_registers(gba: &mut GBA, data: &str) -> Option<()> {
+    if data.len() != G_PACKET_HEX {
+        return None;
+    }
+    let regs: Vec<u32> = (0..16)
+        .map(|n| parse_hex_u32(&data[n * 8..n * 8 + 8]))
+        .collect::<Option<_>>()?;
+    let cpsr = parse_hex_u32(&data[G_PACKET_HEX - 8..])?;
+
+    gba.set_cpsr(cpsr);
+    for (n, value) in regs.into_iter().enumerate() {
+        gba.set_register(n, value);
+    }
+    Some(())
+}
+
+fn write_memory(gba: &mut GBA, args: &str) -> Option<()> {
+    let (range, data) = args.split_once(':')?;
+    let (addr, len) = parse_addr_len(range)?;
+    if data.len() != len as usize * 2 {
+        return None;
+    }
+    for i in 0..len {
+        let byte = u8::from_str_radix(data.get(i as usize * 2..i as usize * 2 + 2)?, 16).ok()?;
+        gba.write_byte(addr.wrapping_add(i), byte);
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egba_core::{bios::Bios, cartridge::Cartridge, rom::Rom};
+    use std::path::Path;
+
+    fn setup(program: &[u32]) -> (GdbStub, GBA) {
+        let rom: Vec<u8> = program
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .chain(std::iter::repeat_n(0, 0x100))
+            .collect();
+        let cart = Cartridge::new(Rom::new(&rom), Path::new("/nonexistent/no.sav")).expect("cart");
+        let gba = GBA::new(Bios::hle(), cart);
+        (GdbStub::bind(0).expect("bind"), gba)
+    }
+
+    fn reply(stub: &mut GdbStub, gba: &mut GBA, packet: &str) -> String {
+        stub.handle(gba, packet).expect("reply")
+    }
+
+    #[test]
+    fn register_packets_report_pc_and_cpsr() {
+        let (mut stub, mut gba) = setup(&[0xE3A0_0042]);
+        let regs = reply(&mut stub, &mut gba, "g");
+        assert_eq!(regs.len(), G_PACKET_HEX);
+        assert_eq!(&regs[15 * 8..16 * 8], "00000008", "r15 is the executing address");
+        assert_eq!(parse_hex_u32(&regs[G_PACKET_HEX - 8..]), Some(gba.cpsr()));
+
+        assert_eq!(reply(&mut stub, &mut gba, "s"), "S05");
+        assert_eq!(reply(&mut stub, &mut gba, "p0"), "42000000");
+        assert_eq!(reply(&mut stub, &mut gba, "p19"), hex_u32(gba.cpsr()));
+
+        assert_eq!(reply(&mut stub, &mut gba, "P3=efbeadde"), "OK");
+        assert_eq!(gba.get_cpu().reg[3], 0xDEAD_BEEF);
+        let thumb = hex_u32(gba.cpsr() | 1 << 5);
+        assert_eq!(reply(&mut stub, &mut gba, &format!("P19={thumb}")), "OK");
+        assert_eq!(gba.cpsr() & 1 << 5, 1 << 5, "CPSR write switches to THUMB");
+    }
+
+    #[test]
+    fn memory_packets_round_trip() {
+        let (mut stub, mut gba) = setup(&[]);
+        assert_eq!(reply(&mut stub, &mut gba, "M3000010,4:01020304"), "OK");
+        assert_eq!(reply(&mut stub, &mut gba, "m3000010,4"), "01020304");
+        assert_eq!(reply(&mut stub, &mut gba, "M3000010,4:0102"), "E01");
+        assert_eq!(reply(&mut stub, &mut gba, "m0,800").len(), PACKET_SIZE as usize);
+        assert_eq!(reply(&mut stub, &mut gba, "m0,801"), "E01");
+        assert_eq!(reply(&mut stub, &mut gba, "m0,ffffffff"), "E01");
+    }
+
+    #[test]
+    fn breakpoint_and_watchpoint_stop_execution() {
+        let (mut stub, mut gba) = setup(&[
+            0xE3A0_0403, // mov r0, #0x03000000
+            0xE3A0_1007, // mov r1, #7
+            0xE580_1000, // str r1, [r0]
+            0xE590_2000, // ldr r2, [r0]
+            0xEAFF_FFFE, // b .
+        ]);
+        assert_eq!(reply(&mut stub, &mut gba, "Z0,8000004,4"), "OK");
+        assert_eq!(stub.handle(&mut gba, "c"), None);
+        assert_eq!(gba.run_frame().map(stop_reply).as_deref(), Some("S05"));
+        assert_eq!(gba.pc(), 0x0800_0004);
+
+        assert_eq!(reply(&mut stub, &mut gba, "z0,8000004,4"), "OK");
+        assert_eq!(reply(&mut stub, &mut gba, "Z2,3000000,4"), "OK");
+        assert_eq!(reply(&mut stub, &mut gba, "Z3,3000000,4"), "OK");
+        assert_eq!(reply(&mut stub, &mut gba, "s"), "S05");
+        assert_eq!(reply(&mut stub, &mut gba, "s"), "T05watch:03000000;");
+        assert_eq!(reply(&mut stub, &mut gba, "s"), "T05rwatch:03000000;");
+
+        assert_eq!(reply(&mut stub, &mut gba, "z2,3000000,4"), "OK");
+        assert_eq!(reply(&mut stub, &mut gba, "z3,3000000,4"), "OK");
+        assert_eq!(reply(&mut stub, &mut gba, "Z4,3000000,4"), "OK");
+        gba.set_register(PC_INDEX, 0x0800_0008);
+        assert_eq!(reply(&mut stub, &mut gba, "s"), "T05awatch:03000000;");
+    }
+}
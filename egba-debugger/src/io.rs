@@ -0,0 +1,122 @@
+use bit::BitIndex;
+use egba_core::gba::GBA;
+
+const IRQ_NAMES: [&str; 14] = [
+    "VBL", "HBL", "VCT", "TM0", "TM1", "TM2", "TM3", "SIO", "DMA0", "DMA1", "DMA2", "DMA3",
+    "KEY", "PAK",
+];
+
+const TIMER_PRESCALERS: [u32; 4] = [1, 64, 256, 1024];
+
+const DMA_ADDR_CONTROL: [&str; 4] = ["inc", "dec", "fixed", "reload"];
+
+const DMA_TIMING: [&str; 4] = ["now", "vblank", "hblank", "special"];
+
+fn flags(value: u16, names: &[&str]) -> String {
+    let set: Vec<&str> = names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| value.bit(*i))
+        .map(|(_, name)| *name)
+        .collect();
+    if set.is_empty() {
+        "-".to_string()
+    } else {
+        set.join(" ")
+    }
+}
+
+fn dispcnt(value: u16) -> String {
+    let layers = flags(value >> 8, &["BG0", "BG1", "BG2", "BG3", "OBJ", "WIN0", "WIN1", "OBJWIN"]);
+    let mut text = format!("DISPCNT  {:04x} mode {} {}", value, value & 0b111, layers);
+    if value.bit(4) {
+        text.push_str(" frame1");
+    }
+    if value.bit(6) {
+        text.push_str(" obj1d");
+    }
+    if value.bit(7) {
+        text.push_str(" blank");
+    }
+    text
+}
+
+fn dispstat(value: u16, vcount: u16) -> String {
+    format!(
+        "DISPSTAT {:04x} {} irq:{} lyc {} | VCOUNT {}",
+        value,
+        flags(value, &["VBL", "HBL", "VCT"]),
+        flags(value >> 3, &["V", "H", "C"]),
+        value >> 8,
+        vcount & 0xFF
+    )
+}
+
+fn timer(index: usize, counter: u16, control: u16) -> String {
+    let clock = if index > 0 && control.bit(2) {
+        "cascade".to_string()
+    } else {
+        format!("/{}", TIMER_PRESCALERS[(control & 0b11) as usize])
+    };
+    format!(
+        "TM{}  {:04x} {:>7} {}{}",
+        index,
+        counter,
+        clock,
+        if control.bit(7) { "on" } else { "off" },
+        if control.bit(6) { " irq" } else { "" }
+    )
+}
+
+fn dma(index: usize, control: u16) -> String {
+    format!(
+        "DMA{} {:04x} dst {} src {} {} {}{}{} {}",
+        index,
+        control,
+        DMA_ADDR_CONTROL[control.bit_range(5..7) as usize],
+        DMA_ADDR_CONTROL[control.bit_range(7..9) as usize],
+        if control.bit(10) { "32b" } else { "16b" },
+        DMA_TIMING[control.bit_range(12..14) as usize],
+        if control.bit(9) { " repeat" } else { "" },
+        if control.bit(14) { " irq" } else { "" },
+        if control.bit(15) { "on" } else { "off" }
+    )
+}
+
+pub(crate) fn io_lines(gba: &GBA) -> Vec<String> {
+    let mut lines = vec![
+        dispcnt(gba.read_hword(0x0400_0000)),
+        dispstat(gba.read_hword(0x0400_0004), gba.read_hword(0x0400_0006)),
+        format!("IE  {:04x} {}", gba.read_hword(0x0400_0200), flags(gba.read_hword(0x0400_0200), &IRQ_NAMES)),
+        format!("IF  {:04x} {}", gba.read_hword(0x0400_0202), flags(gba.read_hword(0x0400_0202), &IRQ_NAMES)),
+        format!("IME {}", gba.read_hword(0x0400_0208) & 1),
+    ];
+    for i in 0..4 {
+        let base = 0x0400_0100 + i as u32 * 4;
+        lines.push(timer(i, gba.read_hword(base), gba.read_hword(base + 2)));
+    }
+    for i in 0..4 {
+        lines.push(dma(i, gba.read_hword(0x0400_00BA + i as u32 * 12)));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_display_registers() {
+        assert_eq!(dispcnt(0x1403), "DISPCNT  1403 mode 3 BG2 OBJ");
+        assert_eq!(dispcnt(0x0080), "DISPCNT  0080 mode 0 - blank");
+        assert_eq!(dispstat(0xA019, 0xA0), "DISPSTAT a019 VBL irq:V H lyc 160 | VCOUNT 160");
+    }
+
+    #[test]
+    fn decodes_timers_and_dma() {
+        assert_eq!(timer(0, 0xFF00, 0x00C2), "TM0  ff00    /256 on irq");
+        assert_eq!(timer(1, 0x0001, 0x0084), "TM1  0001 cascade on");
+        assert_eq!(dma(3, 0x8400), "DMA3 8400 dst inc src inc 32b now on");
+        assert_eq!(dma(1, 0x3240), "DMA1 3240 dst fixed src inc 16b special repeat off");
+    }
+}
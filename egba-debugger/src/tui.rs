@@ -0,0 +1,380 @@
+use std::{
+    io::{self, stdout, Stdout},
+    time::Duration,
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use egba_core::{
+    cpu::psr::OperatingState,
+    debug::{StopReason, WatchKind},
+    gba::GBA,
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    prelude::CrosstermBackend,
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::{
+    decoder::{arm::arm_decode, thumb::thumb_decode},
+    io::io_lines,
+};
+
+const MEMORY_REGIONS: [(char, u32); 9] = [
+    ('0', 0x0000_0000),
+    ('2', 0x0200_0000),
+    ('3', 0x0300_0000),
+    ('4', 0x0400_0000),
+    ('5', 0x0500_0000),
+    ('6', 0x0600_0000),
+    ('7', 0x0700_0000),
+    ('8', 0x0800_0000),
+    ('e', 0x0E00_0000),
+];
+const MEMORY_ROW_BYTES: u32 = 16;
+const MEMORY_PAGE: u32 = MEMORY_ROW_BYTES * 8;
+
+const HELP: &str = "space pause/resume  s step  n step over  r run to cursor  b breakpoint  \
+                    up/down cursor  f follow PC  0-8/e memory region  PgUp/PgDn scroll";
+
+pub struct Debugger {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    paused: bool,
+    cursor: Option<u32>,
+    memory_addr: u32,
+    temp_breakpoint: Option<u32>,
+    status: String,
+}
+
+impl Debugger {
+    pub fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        Ok(Self {
+            terminal: Terminal::new(CrosstermBackend::new(stdout()))?,
+            paused: true,
+            cursor: None,
+            memory_addr: 0x0300_0000,
+            temp_breakpoint: None,
+            status: "Paused".to_string(),
+        })
+    }
+
+    pub fn run_frame(&mut self, gba: &mut GBA) {
+        if let Err(err) = self.handle_input(gba) {
+            eprintln!("DB/: Failed to read input due to err: {err}");
+        }
+        if !self.paused {
+            if let Some(reason) = gba.run_frame() {
+                self.stop(gba, describe(reason));
+            }
+        }
+        if let Err(err) = self.draw(gba) {
+            eprintln!("DB/: Failed to draw debugger due to err: {err}");
+        }
+    }
+
+    fn handle_input(&mut self, gba: &mut GBA) -> io::Result<()> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char(' ') => {
+                    if self.paused {
+                        self.resume();
+                    } else {
+                        self.stop(gba, "Paused".to_string());
+                    }
+                }
+                KeyCode::Char('s') => self.step(gba),
+                KeyCode::Char('n') => {
+                    let state = gba.get_cpu().cpsr.operating_state;
+                    let pc = gba.pc();
+                    match step_over_target(state, pc, read_instruction(gba, state, pc)) {
+                        Some(target) => self.run_to(gba, target),
+                        None => self.step(gba),
+                    }
+                }
+                KeyCode::Char('r') => {
+                    if let Some(cursor) = self.cursor {
+                        self.run_to(gba, cursor);
+                    }
+                }
+                KeyCode::Char('b') => {
+                    let addr = self.cursor.unwrap_or(gba.pc());
+                    let hooks = gba.debug_hooks();
+                    if hooks.breakpoints().contains(&addr) {
+                        hooks.remove_breakpoint(addr);
+                    } else {
+                        hooks.add_breakpoint(addr);
+                    }
+                }
+                KeyCode::Char('f') => self.cursor = None,
+                KeyCode::Up | KeyCode::Down => {
+                    let width = instruction_width(gba.get_cpu().cpsr.operating_state);
+                    let cursor = self.cursor.unwrap_or(gba.pc());
+                    self.cursor = Some(if key.code == KeyCode::Up {
+                        cursor.wrapping_sub(width)
+                    } else {
+                        cursor.wrapping_add(width)
+                    });
+                }
+                KeyCode::PageUp => self.memory_addr = self.memory_addr.wrapping_sub(MEMORY_PAGE),
+                KeyCode::PageDown => self.memory_addr = self.memory_addr.wrapping_add(MEMORY_PAGE),
+                KeyCode::Char(c) => {
+                    if let Some(&(_, base)) = MEMORY_REGIONS.iter().find(|(key, _)| *key == c) {
+                        self.memory_addr = base;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+        self.cursor = None;
+        self.status = "Running".to_string();
+    }
+
+    fn stop(&mut self, gba: &mut GBA, status: String) {
+        if let Some(addr) = self.temp_breakpoint.take() {
+            gba.debug_hooks().remove_breakpoint(addr);
+        }
+        self.paused = true;
+        self.cursor = None;
+        self.status = status;
+    }
+
+    fn step(&mut self, gba: &mut GBA) {
+        let status = match gba.step_one_instruction() {
+            Some(reason) => describe(reason),
+            None => "Stepped".to_string(),
+        };
+        self.stop(gba, status);
+    }
+
+    fn run_to(&mut self, gba: &mut GBA, addr: u32) {
+        let hooks = gba.debug_hooks();
+        if !hooks.breakpoints().contains(&addr) {
+            hooks.add_breakpoint(addr);
+            self.temp_breakpoint = Some(addr);
+        }
+        self.resume();
+    }
+
+    fn draw(&mut self, gba: &mut GBA) -> io::Result<()> {
+        let breakpoints = gba.debug_hooks().breakpoints().to_vec();
+        let gba = &*gba;
+        let status = format!(
+            "{} | {}",
+            if self.paused { &self.status } else { "Running" },
+            HELP
+        );
+        let cursor = self.cursor;
+        let memory_addr = self.memory_addr;
+        self.terminal.draw(|f| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Fill(1), Constraint::Length(1)])
+                .split(f.area());
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(24),
+                    Constraint::Fill(1),
+                    Constraint::Length(78),
+                ])
+                .split(rows[0]);
+            let right = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(15), Constraint::Fill(1)])
+                .split(columns[2]);
+
+            draw_registers(f, columns[0], gba);
+            draw_disassembly(f, columns[1], gba, cursor, &breakpoints);
+            f.render_widget(
+                Paragraph::new(io_lines(gba).into_iter().map(Line::from).collect::<Vec<_>>())
+                    .block(Block::default().title("I/O").borders(Borders::ALL)),
+                right[0],
+            );
+            draw_memory(f, right[1], gba, memory_addr);
+            f.render_widget(Paragraph::new(status), rows[1]);
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        let _ = stdout().execute(LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw_registers(f: &mut Frame, area: Rect, gba: &GBA) {
+    let cpu = gba.get_cpu();
+    let mut lines: Vec<Line> = cpu
+        .reg
+        .iter()
+        .enumerate()
+        .map(|(i, r)| Line::from(format!("R{:02}: {:08x}", i, r)))
+        .collect();
+    let cpsr = cpu.cpsr;
+    lines.push(Line::from(format!("PC:  {:08x}", gba.pc())));
+    lines.push(Line::from(format!("CPSR {:08x}", gba.cpsr())));
+    lines.push(Line::from(format!(
+        "{}{}{}{} {}{} {:?}",
+        if cpsr.n_condition_bit { 'N' } else { '-' },
+        if cpsr.z_condition_bit { 'Z' } else { '-' },
+        if cpsr.c_condition_bit { 'C' } else { '-' },
+        if cpsr.v_condition_bit { 'V' } else { '-' },
+        if cpsr.irq_disable_bit { 'I' } else { '-' },
+        if cpsr.fiq_disable_bit { 'F' } else { '-' },
+        cpsr.operating_state
+    )));
+    lines.push(Line::from(format!("{:?}", cpsr.mode)));
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().title("Registers").borders(Borders::ALL)),
+        area,
+    );
+}
+
+fn draw_disassembly(f: &mut Frame, area: Rect, gba: &GBA, cursor: Option<u32>, breakpoints: &[u32]) {
+    let state = gba.get_cpu().cpsr.operating_state;
+    let width = instruction_width(state);
+    let pc = gba.pc();
+    let focus = cursor.unwrap_or(pc);
+    let rows = area.height.saturating_sub(2) as u32;
+    let start = focus.wrapping_sub(rows / 2 * width);
+
+    let lines: Vec<Line> = (0..rows)
+        .map(|i| {
+            let addr = start.wrapping_add(i * width);
+            let instr = read_instruction(gba, state, addr);
+            let decoded = match state {
+                OperatingState::ARM => arm_decode(instr),
+                OperatingState::THUMB => thumb_decode(instr),
+            };
+            let text = format!(
+                "{}{} {:08x}  {:0digits$x}  {}",
+                if addr == pc { '>' } else { ' ' },
+                if breakpoints.contains(&addr) { '*' } else { ' ' },
+                addr,
+                instr,
+                decoded,
+                digits = width as usize * 2
+            );
+            if addr == focus && cursor.is_some() {
+                Line::styled(text, Style::default().add_modifier(Modifier::REVERSED))
+            } else if addr == pc {
+                Line::styled(text, Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                Line::from(text)
+            }
+        })
+        .collect();
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().title("Disassembly").borders(Borders::ALL)),
+        area,
+    );
+}
+
+fn draw_memory(f: &mut Frame, area: Rect, gba: &GBA, addr: u32) {
+    let rows = area.height.saturating_sub(2) as u32;
+    let lines: Vec<Line> = (0..rows)
+        .map(|row| {
+            let base = addr.wrapping_add(row * MEMORY_ROW_BYTES);
+            let bytes: Vec<u8> = (0..MEMORY_ROW_BYTES)
+                .map(|i| gba.read_byte(base.wrapping_add(i)))
+                .collect();
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            Line::from(format!("{:08x}  {}  {}", base, hex.join(" "), ascii))
+        })
+        .collect();
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().title("Memory").borders(Borders::ALL)),
+        area,
+    );
+}
+
+fn instruction_width(state: OperatingState) -> u32 {
+    match state {
+        OperatingState::ARM => 4,
+        OperatingState::THUMB => 2,
+    }
+}
+
+fn read_instruction(gba: &GBA, state: OperatingState, addr: u32) -> u32 {
+    match state {
+        OperatingState::ARM => gba.read_word(addr),
+        OperatingState::THUMB => gba.read_hword(addr) as u32,
+    }
+}
+
+fn step_over_target(state: OperatingState, pc: u32, instr: u32) -> Option<u32> {
+    match state {
+        OperatingState::ARM => {
+            let op = (instr >> 24) & 0xF;
+            (op == 0xB || op == 0xF).then(|| pc.wrapping_add(4))
+        }
+        OperatingState::THUMB => {
+            if instr >> 11 == 0b11110 {
+                Some(pc.wrapping_add(4))
+            } else if instr >> 8 == 0xDF {
+                Some(pc.wrapping_add(2))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn describe(reason: StopReason) -> String {
+    match reason {
+        StopReason::Breakpoint(addr) => format!("Breakpoint at {:08x}", addr),
+        StopReason::Watchpoint { addr, kind } => {
+            let access = match kind {
+                WatchKind::Write => "Write",
+                WatchKind::Read => "Read",
+                WatchKind::Access => "Access",
+            };
+            format!("{} watchpoint at {:08x}", access, addr)
+        }
+        StopReason::Irq => "IRQ entry".to_string(),
+        StopReason::SoftwareInterrupt(comment) => format!("SWI {:02x}", comment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_over_targets_calls_only() {
+        let arm = OperatingState::ARM;
+        let thumb = OperatingState::THUMB;
+        assert_eq!(step_over_target(arm, 0x100, 0xEB00_0010), Some(0x104), "bl");
+        assert_eq!(step_over_target(arm, 0x100, 0xEF05_0000), Some(0x104), "swi");
+        assert_eq!(step_over_target(arm, 0x100, 0xEA00_0010), None, "b");
+        assert_eq!(step_over_target(thumb, 0x100, 0xF000), Some(0x104), "bl prefix");
+        assert_eq!(step_over_target(thumb, 0x100, 0xDF05), Some(0x102), "swi");
+        assert_eq!(step_over_target(thumb, 0x100, 0xE7FE), None, "b");
+    }
+}
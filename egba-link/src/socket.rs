@@ -0,0 +1,184 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use egba_core::link::{LinkPacket, LinkTransport, PACKET_SIZE};
+
+const MAX_PLAYERS: usize = 4;
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_micros(50);
+
+struct Peer {
+    player: usize,
+    stream: UnixStream,
+    inbox: Vec<u8>,
+}
+
+impl Peer {
+    fn new(player: usize, stream: UnixStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            player,
+            stream,
+            inbox: Vec::new(),
+        })
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut written = 0;
+        while written < bytes.len() {
+            match self.stream.write(&bytes[written..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::yield_now(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> io::Result<Option<LinkPacket>> {
+        if self.inbox.len() < PACKET_SIZE {
+            let mut buf = [0; 256];
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.inbox.extend_from_slice(&buf[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if self.inbox.len() < PACKET_SIZE {
+            return Ok(None);
+        }
+        let frame: [u8; PACKET_SIZE] = self.inbox[..PACKET_SIZE].try_into().unwrap();
+        self.inbox.drain(..PACKET_SIZE);
+        LinkPacket::decode(&frame)
+            .map(Some)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "bad link packet"))
+    }
+}
+
+pub struct SocketLink {
+    player: usize,
+    players: usize,
+    peers: Vec<Peer>,
+}
+
+impl SocketLink {
+    pub fn host(path: &Path, players: usize) -> io::Result<Self> {
+        if !(2..=MAX_PLAYERS).contains(&players) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("link needs 2-{} players, got {}", MAX_PLAYERS, players),
+            ));
+        }
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let listener = UnixListener::bind(path)?;
+        let mut peers = Vec::with_capacity(players - 1);
+        for player in 1..players {
+            let (mut stream, _) = listener.accept()?;
+            stream.write_all(&[player as u8, players as u8])?;
+            peers.push(Peer::new(player, stream)?);
+        }
+        let _ = fs::remove_file(path);
+        Ok(Self {
+            player: 0,
+            players,
+            peers,
+        })
+    }
+
+    pub fn join(path: &Path) -> io::Result<Self> {
+        let mut stream = UnixStream::connect(path)?;
+        let mut hello = [0; 2];
+        stream.read_exact(&mut hello)?;
+        let (player, players) = (hello[0] as usize, hello[1] as usize);
+        if player == 0 || player >= players || players > MAX_PLAYERS {
+            return Err(io::Error::new(ErrorKind::InvalidData, "bad link handshake"));
+        }
+        Ok(Self {
+            player,
+            players,
+            peers: vec![Peer::new(0, stream)?],
+        })
+    }
+}
+
+impl LinkTransport for SocketLink {
+    fn player(&self) -> usize {
+        self.player
+    }
+
+    fn players(&self) -> usize {
+        self.players
+    }
+
+    fn send(&mut self, packet: LinkPacket) {
+        let frame = packet.encode();
+        self.peers.retain_mut(|peer| peer.send(&frame).is_ok());
+    }
+
+    fn recv(&mut self, wait: bool) -> Option<(usize, LinkPacket)> {
+        let deadline = Instant::now() + RECV_TIMEOUT;
+        loop {
+            for peer in self.peers.iter_mut() {
+                match peer.poll() {
+                    Ok(Some(packet)) => return Some((peer.player, packet)),
+                    Ok(None) => {}
+                    Err(_) => return None,
+                }
+            }
+            if !wait || Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_and_client_exchange_packets() {
+        let path = std::env::temp_dir().join(format!("egba-link-test-{}.sock", std::process::id()));
+        let host_path = path.clone();
+        let host = thread::spawn(move || {
+            let mut host = SocketLink::host(&host_path, 2).expect("host");
+            host.send(LinkPacket::Sync { cycle: 8192 });
+            let reply = host.recv(true);
+            (host.player(), reply)
+        });
+
+        let mut client = loop {
+            match SocketLink::join(&path) {
+                Ok(client) => break client,
+                Err(_) => thread::sleep(Duration::from_millis(5)),
+            }
+        };
+        assert_eq!((client.player(), client.players()), (1, 2));
+        assert_eq!(client.recv(true), Some((0, LinkPacket::Sync { cycle: 8192 })));
+        assert_eq!(client.recv(false), None);
+        client.send(LinkPacket::Reply { data: 0xCAFE });
+
+        assert_eq!(host.join().unwrap(), (0, Some((1, LinkPacket::Reply { data: 0xCAFE }))));
+        assert!(!path.exists(), "socket file is removed once everyone joined");
+    }
+
+    #[test]
+    fn rejects_bad_player_counts() {
+        let path = std::env::temp_dir().join("egba-link-unused.sock");
+        assert_eq!(SocketLink::host(&path, 1).err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+        assert_eq!(SocketLink::host(&path, 5).err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+    }
+}
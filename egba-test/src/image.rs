@@ -0,0 +1,74 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use egba_core::gba::{FB_HEIGHT, FB_WIDTH};
+
+pub fn rgb(framebuffer: &[u32]) -> Vec<u8> {
+    framebuffer
+        .iter()
+        .flat_map(|px| [(px >> 16) as u8, (px >> 8) as u8, *px as u8])
+        .collect()
+}
+
+pub fn read_png(path: &Path) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if (info.width as usize, info.height as usize) != (FB_WIDTH, FB_HEIGHT) {
+        return Err(format!(
+            "{} is {}x{}, expected {}x{}",
+            path.display(),
+            info.width,
+            info.height,
+            FB_WIDTH,
+            FB_HEIGHT
+        ));
+    }
+    let pixels = &buf[..info.buffer_size()];
+    match info.color_type {
+        png::ColorType::Rgb => Ok(pixels.to_vec()),
+        png::ColorType::Rgba => {
+            Ok(pixels.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect())
+        }
+        png::ColorType::Grayscale => Ok(pixels.iter().flat_map(|&v| [v, v, v]).collect()),
+        png::ColorType::GrayscaleAlpha => {
+            Ok(pixels.chunks_exact(2).flat_map(|px| [px[0], px[0], px[0]]).collect())
+        }
+        png::ColorType::Indexed => Err(format!("{}: unexpanded palette", path.display())),
+    }
+}
+
+pub fn write_png(path: &Path, rgb: &[u8]) -> Result<(), String> {
+    let write = || -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, FB_WIDTH as u32, FB_HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(rgb)?;
+        Ok(())
+    };
+    write().map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trips_framebuffer() {
+        let framebuffer: Vec<u32> =
+            (0..FB_WIDTH * FB_HEIGHT).map(|i| i as u32 * 0x010203).collect();
+        let path = std::env::temp_dir().join(format!("egba-test-{}.png", std::process::id()));
+        write_png(&path, &rgb(&framebuffer)).expect("write");
+        let decoded = read_png(&path).expect("read");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(decoded, rgb(&framebuffer));
+    }
+}
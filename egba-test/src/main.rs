@@ -0,0 +1,78 @@
+mod image;
+mod manifest;
+mod report;
+mod runner;
+
+use std::{fs, path::PathBuf};
+
+use clap::{command, Arg};
+use runner::{Options, Outcome};
+
+fn main() {
+    let args = command!()
+        .arg(
+            Arg::new("manifest")
+                .help("TOML manifest listing the test ROMs and their expected framebuffers")
+                .value_parser(clap::value_parser!(PathBuf))
+                .required(true),
+        )
+        .arg(
+            Arg::new("filter")
+                .help("Only run tests whose name or suite contains this string")
+                .long("filter")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("bless")
+                .help("Run every test for its full frame budget and write its reference image")
+                .long("bless")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("actual")
+                .help("Write the final framebuffer of each failing test as <name>.png in this directory")
+                .long("actual")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("markdown")
+                .help("Write a compatibility table in Markdown to this path, or replace the table between the egba-test markers if the file has them")
+                .long("markdown")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .get_matches();
+
+    let manifest_path = args.get_one::<PathBuf>("manifest").expect("manifest is required");
+    let mut tests = manifest::load(manifest_path).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    });
+    if let Some(filter) = args.get_one::<String>("filter") {
+        tests.retain(|test| test.name.contains(filter) || test.suite.contains(filter));
+    }
+
+    let options = Options {
+        bless: args.get_flag("bless"),
+        actual_dir: args.get_one::<PathBuf>("actual").map(PathBuf::as_path),
+    };
+    let outcomes = runner::run_all(&tests, &options);
+    print!("{}", report::summary(&tests, &outcomes));
+
+    if let Some(path) = args.get_one::<PathBuf>("markdown") {
+        let table = report::markdown(&tests, &outcomes);
+        let document = fs::read_to_string(path)
+            .ok()
+            .and_then(|document| report::splice(&document, &table))
+            .unwrap_or(table);
+        if let Err(err) = fs::write(path, document) {
+            eprintln!("Error: failed to write {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+    if outcomes
+        .iter()
+        .any(|outcome| matches!(outcome, Outcome::Fail { .. } | Outcome::Error(_)))
+    {
+        std::process::exit(1);
+    }
+}
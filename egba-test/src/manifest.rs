@@ -0,0 +1,223 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+const BUTTON_NAMES: [&str; 10] = [
+    "a", "b", "select", "start", "right", "left", "up", "down", "r", "l",
+];
+const DEFAULT_FRAMES: u32 = 600;
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Read(PathBuf, String),
+    Parse(String),
+    UnknownButton(String, String),
+    BadHash(String, String),
+    NoExpectation(String),
+    UnorderedInput(String),
+    DuplicateName(String),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Read(path, e) => {
+                write!(f, "Failed to read {}: {}", path.display(), e)
+            }
+            ManifestError::Parse(e) => write!(f, "Failed to parse manifest: {}", e),
+            ManifestError::UnknownButton(test, name) => {
+                write!(f, "Test {} presses unknown button {}", test, name)
+            }
+            ManifestError::BadHash(test, hash) => {
+                write!(f, "Test {} has malformed hash {}", test, hash)
+            }
+            ManifestError::NoExpectation(test) => {
+                write!(f, "Test {} has neither a hash nor a reference image", test)
+            }
+            ManifestError::UnorderedInput(test) => {
+                write!(f, "Test {} has input steps out of frame order", test)
+            }
+            ManifestError::DuplicateName(test) => {
+                write!(f, "Test name {} is used more than once", test)
+            }
+        }
+    }
+}
+
+impl Error for ManifestError {}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Defaults {
+    bios: Option<PathBuf>,
+    frames: Option<u32>,
+    skip_bios: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InputStep {
+    frame: u32,
+    keys: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestEntry {
+    name: String,
+    suite: Option<String>,
+    rom: PathBuf,
+    bios: Option<PathBuf>,
+    frames: Option<u32>,
+    skip_bios: Option<bool>,
+    #[serde(default)]
+    input: Vec<InputStep>,
+    hash: Option<String>,
+    reference: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    #[serde(default)]
+    defaults: Defaults,
+    #[serde(default)]
+    test: Vec<TestEntry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub suite: String,
+    pub rom: PathBuf,
+    pub bios: Option<PathBuf>,
+    pub frames: u32,
+    pub skip_bios: bool,
+    pub input: Vec<(u32, u16)>,
+    pub hash: Option<u64>,
+    pub reference: Option<PathBuf>,
+}
+
+impl TestCase {
+    pub fn keystate(&self, frame: u32) -> u16 {
+        self.input
+            .iter()
+            .take_while(|&&(at, _)| at <= frame)
+            .last()
+            .map_or(0x03FF, |&(_, keys)| keys)
+    }
+
+    pub fn input_done(&self, frame: u32) -> bool {
+        !matches!(self.input.last(), Some(&(at, _)) if frame < at)
+    }
+}
+
+fn keystate(test: &str, keys: &[String]) -> Result<u16, ManifestError> {
+    keys.iter().try_fold(0x03FF, |state, key| {
+        match BUTTON_NAMES.iter().position(|&name| name == key) {
+            Some(bit) => Ok(state & !(1 << bit)),
+            None => Err(ManifestError::UnknownButton(test.to_string(), key.clone())),
+        }
+    })
+}
+
+pub fn load(path: &Path) -> Result<Vec<TestCase>, ManifestError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| ManifestError::Read(path.to_path_buf(), e.to_string()))?;
+    parse(&text, path.parent().unwrap_or(Path::new("")))
+}
+
+pub fn parse(text: &str, base: &Path) -> Result<Vec<TestCase>, ManifestError> {
+    let file: ManifestFile = toml::from_str(text).map_err(|e| ManifestError::Parse(e.to_string()))?;
+    let defaults = file.defaults;
+    for (i, entry) in file.test.iter().enumerate() {
+        if file.test[..i].iter().any(|other| other.name == entry.name) {
+            return Err(ManifestError::DuplicateName(entry.name.clone()));
+        }
+    }
+    file.test
+        .into_iter()
+        .map(|entry| {
+            let hash = entry
+                .hash
+                .map(|hash| {
+                    u64::from_str_radix(&hash, 16)
+                        .map_err(|_| ManifestError::BadHash(entry.name.clone(), hash))
+                })
+                .transpose()?;
+            if hash.is_none() && entry.reference.is_none() {
+                return Err(ManifestError::NoExpectation(entry.name));
+            }
+            if entry.input.windows(2).any(|w| w[0].frame >= w[1].frame) {
+                return Err(ManifestError::UnorderedInput(entry.name));
+            }
+            let input = entry
+                .input
+                .iter()
+                .map(|step| Ok((step.frame, keystate(&entry.name, &step.keys)?)))
+                .collect::<Result<_, ManifestError>>()?;
+            Ok(TestCase {
+                suite: entry.suite.unwrap_or_else(|| entry.name.clone()),
+                rom: base.join(entry.rom),
+                bios: entry.bios.or_else(|| defaults.bios.clone()).map(|p| base.join(p)),
+                frames: entry.frames.or(defaults.frames).unwrap_or(DEFAULT_FRAMES),
+                skip_bios: entry.skip_bios.or(defaults.skip_bios).unwrap_or(false),
+                input,
+                hash,
+                reference: entry.reference.map(|p| base.join(p)),
+                name: entry.name,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_inherit_defaults_and_resolve_paths() {
+        let manifest = r#"
+            [defaults]
+            bios = "bios.bin"
+            frames = 120
+
+            [[test]]
+            name = "arm"
+            suite = "jsmolka"
+            rom = "jsmolka/arm.gba"
+            hash = "00ff"
+            input = [{ frame = 10, keys = ["start", "a"] }, { frame = 12, keys = [] }]
+
+            [[test]]
+            name = "armwrestler"
+            rom = "armwrestler.gba"
+            frames = 300
+            skip_bios = true
+            reference = "refs/armwrestler.png"
+        "#;
+        let tests = parse(manifest, Path::new("roms")).expect("parse");
+        assert_eq!(tests[0].suite, "jsmolka");
+        assert_eq!(tests[0].rom, Path::new("roms/jsmolka/arm.gba"));
+        assert_eq!(tests[0].bios.as_deref(), Some(Path::new("roms/bios.bin")));
+        assert_eq!((tests[0].frames, tests[0].hash), (120, Some(0xFF)));
+        assert_eq!(tests[0].keystate(9), 0x03FF);
+        assert_eq!(tests[0].keystate(11), 0x03F6);
+        assert_eq!(tests[0].keystate(500), 0x03FF);
+        assert!(!tests[0].input_done(11) && tests[0].input_done(12));
+        assert_eq!(tests[1].suite, "armwrestler");
+        assert_eq!((tests[1].frames, tests[1].skip_bios), (300, true));
+
+        let missing = "[[test]]\nname = \"x\"\nrom = \"x.gba\"\n";
+        assert!(matches!(parse(missing, Path::new("")), Err(ManifestError::NoExpectation(_))));
+        let bad = "[[test]]\nname = \"x\"\nrom = \"x.gba\"\nhash = \"1\"\n\
+                   input = [{ frame = 1, keys = [\"turbo\"] }]\n";
+        assert!(matches!(parse(bad, Path::new("")), Err(ManifestError::UnknownButton(..))));
+        let twice = "[[test]]\nname = \"x\"\nrom = \"x.gba\"\nhash = \"1\"\n".repeat(2);
+        assert!(matches!(parse(&twice, Path::new("")), Err(ManifestError::DuplicateName(_))));
+    }
+}
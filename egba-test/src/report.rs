@@ -0,0 +1,144 @@
+use std::fmt::Write;
+
+use crate::{manifest::TestCase, runner::Outcome};
+
+const TABLE_START: &str = "<!-- egba-test:start -->";
+const TABLE_END: &str = "<!-- egba-test:end -->";
+
+pub fn summary(tests: &[TestCase], outcomes: &[Outcome]) -> String {
+    let mut out = String::new();
+    for (test, outcome) in tests.iter().zip(outcomes) {
+        let _ = match outcome {
+            Outcome::Pass { frames } => writeln!(out, "PASS  {} ({} frames)", test.name, frames),
+            Outcome::Fail { frames, hash } => writeln!(
+                out,
+                "FAIL  {}: framebuffer {:016x} after {} frames",
+                test.name, hash, frames
+            ),
+            Outcome::Blessed { hash } => {
+                writeln!(out, "BLESS {}: framebuffer {:016x}", test.name, hash)
+            }
+            Outcome::Skipped(reason) => writeln!(out, "SKIP  {}: {}", test.name, reason),
+            Outcome::Error(err) => writeln!(out, "ERROR {}: {}", test.name, err),
+        };
+    }
+    let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|o| f(o)).count();
+    let _ = writeln!(
+        out,
+        "{} passed, {} failed, {} errors, {} skipped",
+        count(|o| matches!(o, Outcome::Pass { .. })),
+        count(|o| matches!(o, Outcome::Fail { .. })),
+        count(|o| matches!(o, Outcome::Error(_))),
+        count(|o| matches!(o, Outcome::Skipped(_))),
+    );
+    out
+}
+
+pub fn markdown(tests: &[TestCase], outcomes: &[Outcome]) -> String {
+    let mut suites: Vec<&str> = Vec::new();
+    for test in tests {
+        if !suites.contains(&test.suite.as_str()) {
+            suites.push(&test.suite);
+        }
+    }
+
+    let mut out = String::from("| Test ROM | Status | Notes |\n|----------|--------|-------|\n");
+    for suite in suites {
+        let results: Vec<(&TestCase, &Outcome)> =
+            tests.iter().zip(outcomes).filter(|(test, _)| test.suite == suite).collect();
+        let names = |f: fn(&Outcome) -> bool| -> Vec<&str> {
+            results
+                .iter()
+                .filter(|(_, outcome)| f(outcome))
+                .map(|(test, _)| test.name.as_str())
+                .collect()
+        };
+        let passed = names(|o| matches!(o, Outcome::Pass { .. } | Outcome::Blessed { .. }));
+        let failed = names(|o| matches!(o, Outcome::Fail { .. } | Outcome::Error(_)));
+        let run = passed.len() + failed.len();
+
+        let status = if run == 0 {
+            "⬜ Not yet run"
+        } else if failed.is_empty() {
+            "✅ Passes"
+        } else if passed.is_empty() {
+            "❌ Fails"
+        } else {
+            "🟡 Partial"
+        };
+        let mut notes = format!("{}/{} pass", passed.len(), results.len());
+        if !failed.is_empty() && results.len() > 1 {
+            let _ = write!(notes, "; fails {}", failed.join(", "));
+        }
+        if run < results.len() {
+            let _ = write!(notes, "; {} not run", results.len() - run);
+        }
+        let _ = writeln!(out, "| {} | {} | {} |", suite, status, notes);
+    }
+    out
+}
+
+pub fn splice(document: &str, table: &str) -> Option<String> {
+    let start = document.find(&format!("\n{}\n", TABLE_START))? + TABLE_START.len() + 2;
+    let end = start + document[start..].find(&format!("{}\n", TABLE_END))?;
+    Some(format!("{}{}{}", &document[..start], table, &document[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn test_case(name: &str, suite: &str) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            suite: suite.to_string(),
+            rom: PathBuf::new(),
+            bios: None,
+            frames: 1,
+            skip_bios: false,
+            input: Vec::new(),
+            hash: Some(0),
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn suites_collapse_into_one_row() {
+        let tests = [
+            test_case("arm", "jsmolka"),
+            test_case("thumb", "jsmolka"),
+            test_case("memory", "jsmolka"),
+            test_case("armwrestler", "armwrestler"),
+            test_case("ags", "ags"),
+        ];
+        let outcomes = [
+            Outcome::Fail { frames: 1, hash: 0 },
+            Outcome::Pass { frames: 1 },
+            Outcome::Skipped("missing".to_string()),
+            Outcome::Pass { frames: 1 },
+            Outcome::Skipped("missing".to_string()),
+        ];
+        let table = markdown(&tests, &outcomes);
+        let rows: Vec<&str> = table.lines().skip(2).collect();
+        assert_eq!(
+            rows,
+            [
+                "| jsmolka | 🟡 Partial | 1/3 pass; fails arm; 1 not run |",
+                "| armwrestler | ✅ Passes | 1/1 pass |",
+                "| ags | ⬜ Not yet run | 0/1 pass; 1 not run |",
+            ]
+        );
+        assert!(summary(&tests, &outcomes).ends_with("2 passed, 1 failed, 0 errors, 2 skipped\n"));
+
+        let readme = format!(
+            "Markers `{0}`:\n{0}\nold\n{1}\nLegend\n",
+            TABLE_START, TABLE_END
+        );
+        assert_eq!(
+            splice(&readme, "| new |\n"),
+            Some(format!("Markers `{0}`:\n{0}\n| new |\n{1}\nLegend\n", TABLE_START, TABLE_END))
+        );
+        assert_eq!(splice("no markers", "| new |\n"), None);
+    }
+}
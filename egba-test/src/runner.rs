@@ -0,0 +1,178 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use egba_core::{bios::Bios, cartridge::Cartridge, gba::GBA, movie::content_hash, rom::Rom};
+
+use crate::{image, manifest::TestCase};
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Pass { frames: u32 },
+    Fail { frames: u32, hash: u64 },
+    Blessed { hash: u64 },
+    Skipped(String),
+    Error(String),
+}
+
+pub struct Options<'a> {
+    pub bless: bool,
+    pub actual_dir: Option<&'a Path>,
+}
+
+fn boot(test: &TestCase) -> Result<GBA, String> {
+    let rom_data = fs::read(&test.rom).map_err(|e| format!("{}: {}", test.rom.display(), e))?;
+    let bios = match &test.bios {
+        Some(path) => {
+            let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Bios::new(Rom::new(&data)).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => Bios::hle(),
+    };
+    let sav_path = test.rom.with_extension("sav");
+    let cartridge = Cartridge::with_backup_data(Rom::new(&rom_data), &sav_path, None)
+        .map_err(|e| format!("{}: {}", test.rom.display(), e))?;
+    Ok(if test.skip_bios {
+        GBA::new_skipping_bios(bios, cartridge)
+    } else {
+        GBA::new(bios, cartridge)
+    })
+}
+
+pub fn run(test: &TestCase, options: &Options) -> Outcome {
+    if !test.rom.exists() {
+        return Outcome::Skipped(format!("{} not found", test.rom.display()));
+    }
+    if let Some(path) = test.reference.as_ref().filter(|path| !options.bless && !path.exists()) {
+        return Outcome::Skipped(format!(
+            "reference {} not found; run with --bless to create it",
+            path.display()
+        ));
+    }
+    let mut gba = match boot(test) {
+        Ok(gba) => gba,
+        Err(err) => return Outcome::Error(err),
+    };
+    let reference = match &test.reference {
+        Some(path) if !options.bless => match image::read_png(path) {
+            Ok(pixels) => Some(pixels),
+            Err(err) => return Outcome::Error(err),
+        },
+        _ => None,
+    };
+
+    for frame in 0..test.frames {
+        gba.update_keypad(test.keystate(frame));
+        gba.run_frame();
+        gba.clear_audio();
+        if options.bless || !test.input_done(frame) {
+            continue;
+        }
+        let pixels = image::rgb(gba.framebuffer());
+        let hash_ok = test.hash.iter().all(|&hash| content_hash(&pixels) == hash);
+        if hash_ok && reference.iter().all(|reference| *reference == pixels) {
+            return Outcome::Pass { frames: frame + 1 };
+        }
+    }
+    let pixels = image::rgb(gba.framebuffer());
+    let hash = content_hash(&pixels);
+
+    if options.bless {
+        if let Some(path) = &test.reference {
+            if let Err(err) = image::write_png(path, &pixels) {
+                return Outcome::Error(err);
+            }
+        }
+        return Outcome::Blessed { hash };
+    }
+    if let Some(dir) = options.actual_dir {
+        if let Err(err) = image::write_png(&dir.join(format!("{}.png", test.name)), &pixels) {
+            return Outcome::Error(err);
+        }
+    }
+    Outcome::Fail {
+        frames: test.frames,
+        hash,
+    }
+}
+
+pub fn run_all(tests: &[TestCase], options: &Options) -> Vec<Outcome> {
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new((0..tests.len()).map(|_| None).collect::<Vec<_>>());
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(tests.len());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(test) = tests.get(index) else {
+                    break;
+                };
+                let outcome = run(test, options);
+                outcomes.lock().unwrap()[index] = Some(outcome);
+            });
+        }
+    });
+    outcomes
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|outcome| outcome.expect("every test ran"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn test_case(rom: PathBuf, hash: Option<u64>) -> TestCase {
+        TestCase {
+            name: "blank".to_string(),
+            suite: "blank".to_string(),
+            rom,
+            bios: None,
+            frames: 4,
+            skip_bios: true,
+            input: vec![(2, 0x03FE)],
+            hash,
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn blessed_hash_passes_on_the_next_run() {
+        let rom = std::env::temp_dir().join(format!("egba-test-{}.gba", std::process::id()));
+        fs::write(&rom, vec![0u8; 0x1000]).expect("write rom");
+        let bless = Options {
+            bless: true,
+            actual_dir: None,
+        };
+        let check = Options {
+            bless: false,
+            actual_dir: None,
+        };
+
+        let Outcome::Blessed { hash } = run(&test_case(rom.clone(), None), &bless) else {
+            panic!("expected a blessed outcome");
+        };
+        let tests = [test_case(rom.clone(), Some(hash)), test_case(rom.clone(), Some(!hash))];
+        let outcomes = run_all(&tests, &check);
+
+        let mut unblessed = test_case(rom.clone(), None);
+        unblessed.reference = Some(PathBuf::from("/nonexistent/ref.png"));
+        let unblessed = run(&unblessed, &check);
+        let _ = fs::remove_file(&rom);
+        assert_eq!(outcomes[0], Outcome::Pass { frames: 3 });
+        assert_eq!(outcomes[1], Outcome::Fail { frames: 4, hash });
+        assert!(matches!(unblessed, Outcome::Skipped(reason) if reason.contains("--bless")));
+
+        let missing = run(&test_case(PathBuf::from("/nonexistent/rom.gba"), Some(hash)), &check);
+        assert!(matches!(missing, Outcome::Skipped(_)));
+    }
+}
@@ -0,0 +1,280 @@
+use std::{collections::BTreeMap, error::Error, fmt, fs, path::Path};
+
+use sdl2::{controller::Button, keyboard::Scancode};
+use serde::Deserialize;
+
+const GBA_BUTTONS: [&str; 10] = [
+    "a", "b", "select", "start", "right", "left", "up", "down", "r", "l",
+];
+const DEFAULT_KEYS: [&str; 10] = [
+    "Z", "X", "Backspace", "Return", "Right", "Left", "Up", "Down", "S", "A",
+];
+const DEFAULT_BUTTONS: [&str; 10] = [
+    "a",
+    "b",
+    "back",
+    "start",
+    "dpright",
+    "dpleft",
+    "dpup",
+    "dpdown",
+    "rightshoulder",
+    "leftshoulder",
+];
+const DEFAULT_STICK_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hotkey {
+    Quit,
+    FastForward,
+    Turbo,
+    SlowMotion,
+    Pause,
+    FrameAdvance,
+    Rewind,
+    LightDown,
+    LightUp,
+    TiltUp,
+    TiltDown,
+    TiltLeft,
+    TiltRight,
+    GyroLeft,
+    GyroRight,
+    Screenshot,
+    Fullscreen,
+    SaveSlot(u8),
+    LoadSlot(u8),
+}
+
+const HOTKEYS: [(&str, Hotkey, &str); 35] = [
+    ("quit", Hotkey::Quit, "Escape"),
+    ("fast_forward", Hotkey::FastForward, "Tab"),
+    ("turbo", Hotkey::Turbo, "`"),
+    ("slow_motion", Hotkey::SlowMotion, "\\"),
+    ("pause", Hotkey::Pause, "P"),
+    ("frame_advance", Hotkey::FrameAdvance, "N"),
+    ("rewind", Hotkey::Rewind, "R"),
+    ("light_down", Hotkey::LightDown, "-"),
+    ("light_up", Hotkey::LightUp, "="),
+    ("tilt_up", Hotkey::TiltUp, "I"),
+    ("tilt_down", Hotkey::TiltDown, "K"),
+    ("tilt_left", Hotkey::TiltLeft, "J"),
+    ("tilt_right", Hotkey::TiltRight, "L"),
+    ("gyro_left", Hotkey::GyroLeft, "U"),
+    ("gyro_right", Hotkey::GyroRight, "O"),
+    ("screenshot", Hotkey::Screenshot, "F12"),
+    ("fullscreen", Hotkey::Fullscreen, "F11"),
+    ("save_slot_1", Hotkey::SaveSlot(1), "Shift+F1"),
+    ("save_slot_2", Hotkey::SaveSlot(2), "Shift+F2"),
+    ("save_slot_3", Hotkey::SaveSlot(3), "Shift+F3"),
+    ("save_slot_4", Hotkey::SaveSlot(4), "Shift+F4"),
+    ("save_slot_5", Hotkey::SaveSlot(5), "Shift+F5"),
+    ("save_slot_6", Hotkey::SaveSlot(6), "Shift+F6"),
+    ("save_slot_7", Hotkey::SaveSlot(7), "Shift+F7"),
+    ("save_slot_8", Hotkey::SaveSlot(8), "Shift+F8"),
+    ("save_slot_9", Hotkey::SaveSlot(9), "Shift+F9"),
+    ("load_slot_1", Hotkey::LoadSlot(1), "F1"),
+    ("load_slot_2", Hotkey::LoadSlot(2), "F2"),
+    ("load_slot_3", Hotkey::LoadSlot(3), "F3"),
+    ("load_slot_4", Hotkey::LoadSlot(4), "F4"),
+    ("load_slot_5", Hotkey::LoadSlot(5), "F5"),
+    ("load_slot_6", Hotkey::LoadSlot(6), "F6"),
+    ("load_slot_7", Hotkey::LoadSlot(7), "F7"),
+    ("load_slot_8", Hotkey::LoadSlot(8), "F8"),
+    ("load_slot_9", Hotkey::LoadSlot(9), "F9"),
+];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String),
+    Parse(String),
+    UnknownEntry(String),
+    UnknownKey(String),
+    UnknownButton(String),
+    BadThreshold(f32),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(e) => write!(f, "Failed to read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "Failed to parse config: {}", e),
+            ConfigError::UnknownEntry(e) => write!(f, "Unknown config entry: {}", e),
+            ConfigError::UnknownKey(e) => write!(f, "Unknown key name: {}", e),
+            ConfigError::UnknownButton(e) => write!(f, "Unknown controller button: {}", e),
+            ConfigError::BadThreshold(t) => {
+                write!(f, "Stick threshold {} is outside 0.0..1.0", t)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    keyboard: BTreeMap<String, String>,
+    controller: ControllerSection,
+    hotkeys: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ControllerSection {
+    stick_threshold: Option<f32>,
+    #[serde(flatten)]
+    buttons: BTreeMap<String, String>,
+}
+
+pub struct Bindings {
+    keys: [Scancode; 10],
+    buttons: [Button; 10],
+    stick_threshold: i16,
+    hotkeys: Vec<(Scancode, bool, Hotkey)>,
+}
+
+fn key(name: &str) -> Result<Scancode, ConfigError> {
+    Scancode::from_name(name).ok_or_else(|| ConfigError::UnknownKey(name.to_string()))
+}
+
+fn chord(name: &str) -> Result<(Scancode, bool), ConfigError> {
+    match name.strip_prefix("Shift+") {
+        Some(name) => Ok((key(name)?, true)),
+        None => Ok((key(name)?, false)),
+    }
+}
+
+fn button(name: &str) -> Result<Button, ConfigError> {
+    Button::from_string(name).ok_or_else(|| ConfigError::UnknownButton(name.to_string()))
+}
+
+fn check_names<'a>(
+    section: &str,
+    entries: &BTreeMap<String, String>,
+    known: impl Iterator<Item = &'a str> + Clone,
+) -> Result<(), ConfigError> {
+    match entries.keys().find(|name| !known.clone().any(|k| k == name.as_str())) {
+        Some(name) => Err(ConfigError::UnknownEntry(format!("{}.{}", section, name))),
+        None => Ok(()),
+    }
+}
+
+impl Bindings {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Read(e.to_string()))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let file: ConfigFile = toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        check_names("keyboard", &file.keyboard, GBA_BUTTONS.into_iter())?;
+        check_names("controller", &file.controller.buttons, GBA_BUTTONS.into_iter())?;
+        check_names("hotkeys", &file.hotkeys, HOTKEYS.iter().map(|h| h.0))?;
+
+        let mut keys = [Scancode::Z; 10];
+        let mut buttons = [Button::A; 10];
+        for (i, name) in GBA_BUTTONS.iter().enumerate() {
+            keys[i] = key(file.keyboard.get(*name).map_or(DEFAULT_KEYS[i], String::as_str))?;
+            buttons[i] = button(
+                file.controller
+                    .buttons
+                    .get(*name)
+                    .map_or(DEFAULT_BUTTONS[i], String::as_str),
+            )?;
+        }
+        let threshold = file.controller.stick_threshold.unwrap_or(DEFAULT_STICK_THRESHOLD);
+        if !(0.0..1.0).contains(&threshold) {
+            return Err(ConfigError::BadThreshold(threshold));
+        }
+        let hotkeys = HOTKEYS
+            .iter()
+            .map(|&(name, hotkey, default)| {
+                let (key, shift) = chord(file.hotkeys.get(name).map_or(default, String::as_str))?;
+                Ok((key, shift, hotkey))
+            })
+            .collect::<Result<_, ConfigError>>()?;
+
+        Ok(Self {
+            keys,
+            buttons,
+            stick_threshold: (threshold * i16::MAX as f32) as i16,
+            hotkeys,
+        })
+    }
+
+    pub fn hotkey(&self, scancode: Scancode, shift: bool) -> Option<Hotkey> {
+        let find = |shift| {
+            self.hotkeys
+                .iter()
+                .find(|&&(key, s, _)| key == scancode && s == shift)
+                .map(|&(_, _, hotkey)| hotkey)
+        };
+        find(shift).or_else(|| find(false).filter(|_| shift))
+    }
+
+    pub(crate) fn hotkey_scancodes(&self, hotkey: Hotkey) -> impl Iterator<Item = Scancode> + '_ {
+        self.hotkeys
+            .iter()
+            .filter(move |&&(_, _, h)| h == hotkey)
+            .map(|&(key, _, _)| key)
+    }
+
+    pub(crate) fn keys(&self) -> &[Scancode; 10] {
+        &self.keys
+    }
+
+    pub(crate) fn buttons(&self) -> &[Button; 10] {
+        &self.buttons
+    }
+
+    pub(crate) fn stick_threshold(&self) -> i16 {
+        self.stick_threshold
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::parse("").expect("default bindings are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_merge_with_defaults() {
+        let bindings = Bindings::parse(
+            "[keyboard]\nselect = \"Space\"\n\n[controller]\nstick_threshold = 0.25\na = \"x\"\n\n\
+             [hotkeys]\nrewind = \"Backspace\"\n",
+        )
+        .expect("parse");
+        assert_eq!(bindings.keys()[2], Scancode::Space);
+        assert_eq!(bindings.keys()[0], Scancode::Z);
+        assert_eq!(bindings.buttons()[0], Button::X);
+        assert_eq!(bindings.stick_threshold(), (0.25 * i16::MAX as f32) as i16);
+        assert_eq!(bindings.hotkey(Scancode::Backspace, false), Some(Hotkey::Rewind));
+        assert_eq!(bindings.hotkey(Scancode::Tab, false), Some(Hotkey::FastForward));
+        assert_eq!(bindings.hotkey(Scancode::Tab, true), Some(Hotkey::FastForward));
+
+        let slots =
+            Bindings::parse("[hotkeys]\nsave_slot_2 = \"Shift+K\"\nload_slot_9 = \"Keypad 9\"\n")
+                .expect("parse");
+        assert_eq!(slots.hotkey(Scancode::F1, true), Some(Hotkey::SaveSlot(1)));
+        assert_eq!(slots.hotkey(Scancode::F1, false), Some(Hotkey::LoadSlot(1)));
+        assert_eq!(slots.hotkey(Scancode::K, true), Some(Hotkey::SaveSlot(2)));
+        assert_eq!(slots.hotkey(Scancode::K, false), Some(Hotkey::TiltDown));
+        assert_eq!(slots.hotkey(Scancode::Kp9, false), Some(Hotkey::LoadSlot(9)));
+        assert_eq!(slots.hotkey(Scancode::F9, false), None);
+
+        assert!(matches!(
+            Bindings::parse("[keyboard]\nturbo = \"T\"\n"),
+            Err(ConfigError::UnknownEntry(_))
+        ));
+        assert!(matches!(
+            Bindings::parse("[keyboard]\na = \"NoSuchKey\"\n"),
+            Err(ConfigError::UnknownKey(_))
+        ));
+    }
+}
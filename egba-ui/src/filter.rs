@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    #[default]
+    None,
+    Lcd,
+    Scanlines,
+    Scale2x,
+}
+
+impl Filter {
+    pub const NAMES: [&'static str; 4] = ["none", "lcd", "scanlines", "scale2x"];
+
+    pub(crate) fn factor(self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scanlines | Filter::Scale2x => 2,
+            Filter::Lcd => 3,
+        }
+    }
+
+    fn apply(self, src: &[u32], width: usize, out: &mut Vec<u32>) {
+        out.clear();
+        match self {
+            Filter::None => out.extend_from_slice(src),
+            Filter::Lcd => lcd(src, width, out),
+            Filter::Scanlines => scanlines(src, width, out),
+            Filter::Scale2x => scale2x(src, width, out),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Filter::None),
+            "lcd" => Ok(Filter::Lcd),
+            "scanlines" => Ok(Filter::Scanlines),
+            "scale2x" => Ok(Filter::Scale2x),
+            _ => Err(format!("unknown filter {:?}", s)),
+        }
+    }
+}
+
+fn average(a: u32, b: u32) -> u32 {
+    (a & b) + (((a ^ b) & 0xFE_FEFE) >> 1)
+}
+
+fn darken(px: u32, eighths: u32) -> u32 {
+    let channel = |shift: u32| (((px >> shift) & 0xFF) * eighths / 8) << shift;
+    channel(16) | channel(8) | channel(0)
+}
+
+fn lcd(src: &[u32], width: usize, out: &mut Vec<u32>) {
+    for row in src.chunks_exact(width) {
+        for y in 0..3 {
+            for &px in row {
+                let edge = if y == 2 { 6 } else { 8 };
+                out.extend([darken(px, edge), darken(px, edge), darken(px, edge - 2)]);
+            }
+        }
+    }
+}
+
+fn scanlines(src: &[u32], width: usize, out: &mut Vec<u32>) {
+    for row in src.chunks_exact(width) {
+        out.extend(row.iter().flat_map(|&px| [px, px]));
+        out.extend(row.iter().flat_map(|&px| [darken(px, 5); 2]));
+    }
+}
+
+fn scale2x(src: &[u32], width: usize, out: &mut Vec<u32>) {
+    let height = src.len() / width;
+    let at = |x: usize, y: usize| src[y * width + x];
+    for y in 0..height {
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        for (near, far) in [(up, down), (down, up)] {
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (e, n, o) = (at(x, y), at(x, near), at(x, far));
+                let (d, f) = (at(left, y), at(right, y));
+                let corner = |side: u32, other: u32| {
+                    if side == n && n != other && side != o {
+                        side
+                    } else {
+                        e
+                    }
+                };
+                out.extend([corner(d, f), corner(f, d)]);
+            }
+        }
+    }
+}
+
+pub(crate) struct PostProcess {
+    filter: Filter,
+    blend: bool,
+    previous: Vec<u32>,
+    blended: Vec<u32>,
+    filtered: Vec<u32>,
+}
+
+impl PostProcess {
+    pub(crate) fn new(filter: Filter, blend: bool) -> Self {
+        Self {
+            filter,
+            blend,
+            previous: Vec::new(),
+            blended: Vec::new(),
+            filtered: Vec::new(),
+        }
+    }
+
+    pub(crate) fn process<'a>(&'a mut self, frame: &'a [u32], width: usize) -> &'a [u32] {
+        let mut frame = frame;
+        if self.blend {
+            if self.previous.len() != frame.len() {
+                self.previous = frame.to_vec();
+            }
+            self.blended.clear();
+            self.blended
+                .extend(self.previous.iter().zip(frame).map(|(&a, &b)| average(a, b)));
+            self.previous.copy_from_slice(frame);
+            frame = &self.blended;
+        }
+        if self.filter == Filter::None {
+            return frame;
+        }
+        self.filter.apply(frame, width, &mut self.filtered);
+        &self.filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_scale_and_shade_pixels() {
+        let src = [0x80_4020, 0xFF_FFFF];
+        let mut out = Vec::new();
+        for filter in Filter::NAMES.map(|name| name.parse::<Filter>().unwrap()) {
+            filter.apply(&src, 2, &mut out);
+            assert_eq!(out.len(), src.len() * filter.factor() * filter.factor(), "{:?}", filter);
+        }
+        Filter::Scanlines.apply(&src, 2, &mut out);
+        assert_eq!(out[..4], [0x80_4020, 0x80_4020, 0xFF_FFFF, 0xFF_FFFF]);
+        assert_eq!(out[4..], [0x50_2814, 0x50_2814, 0x9F_9F9F, 0x9F_9F9F]);
+        Filter::Lcd.apply(&src, 2, &mut out);
+        assert_eq!(out[..3], [0x80_4020, 0x80_4020, 0x60_3018]);
+        assert_eq!(out[12..15], [0x60_3018, 0x60_3018, 0x40_2010]);
+    }
+
+    #[test]
+    fn scale2x_rounds_off_diagonal_steps_only() {
+        let mut out = Vec::new();
+        Filter::Scale2x.apply(&[0x12_3456; 16], 4, &mut out);
+        assert!(out.iter().all(|&px| px == 0x12_3456), "flat areas stay flat");
+
+        let (w, b) = (0xFF_FFFF, 0);
+        let staircase = [
+            b, b, b, b,
+            b, b, b, w,
+            b, b, w, w,
+            b, w, w, w,
+        ];
+        Filter::Scale2x.apply(&staircase, 4, &mut out);
+        assert_eq!(out[0], b);
+        assert_eq!(out[3 * 8 + 5], w, "inner corner of a step is filled");
+        assert_eq!(out[2 * 8 + 6], b, "outer corner of a step is cut");
+        assert_eq!(out[3 * 8 + 4], b);
+        assert_eq!(out[7 * 8 + 7], w);
+        assert_eq!(out[3 * 8 + 6..3 * 8 + 8], [w, w], "straight edges stay sharp");
+    }
+
+    #[test]
+    fn frame_blending_averages_with_the_previous_frame() {
+        let mut post = PostProcess::new(Filter::None, true);
+        assert_eq!(post.process(&[0x10_2030], 1), [0x10_2030]);
+        assert_eq!(post.process(&[0x30_4050], 1), [0x20_3040]);
+        assert_eq!(post.process(&[0x30_4050], 1), [0x30_4050]);
+    }
+}
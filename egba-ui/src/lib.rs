@@ -1,3 +1,10 @@
+pub mod config;
+pub mod filter;
 pub mod window;
 
-pub use sdl2::{event::Event, keyboard::Keycode};
\ No newline at end of file
+mod resample;
+
+pub use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Mod, Scancode},
+};
\ No newline at end of file
@@ -0,0 +1,58 @@
+#[derive(Default)]
+pub(crate) struct Resampler {
+    history: [(f32, f32); 4],
+    pos: f64,
+}
+
+fn cubic(x: [f32; 4], t: f32) -> f32 {
+    x[1] + 0.5
+        * t
+        * (x[2] - x[0]
+            + t * (2.0 * x[0] - 5.0 * x[1] + 4.0 * x[2] - x[3]
+                + t * (3.0 * (x[1] - x[2]) + x[3] - x[0])))
+}
+
+impl Resampler {
+    pub(crate) fn process(&mut self, input: &[(i16, i16)], step: f64, out: &mut Vec<i16>) {
+        for &(left, right) in input {
+            self.history.rotate_left(1);
+            self.history[3] = (left as f32, right as f32);
+            while self.pos < 1.0 {
+                let t = self.pos as f32;
+                let h = self.history;
+                for channel in [h.map(|s| s.0), h.map(|s| s.1)] {
+                    let sample = cubic(channel, t).round();
+                    out.push(sample.clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+                }
+                self.pos += step;
+            }
+            self.pos -= 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_length_follows_the_ratio() {
+        let mut resampler = Resampler::default();
+        let input = vec![(1000, -1000); 32768];
+        let mut out = Vec::new();
+        resampler.process(&input, 32768.0 / 48000.0, &mut out);
+        assert!((out.len() as i64 / 2 - 48000).abs() <= 1, "{}", out.len() / 2);
+        assert_eq!(out[out.len() - 2..], [1000, -1000], "constant signal stays constant");
+    }
+
+    #[test]
+    fn ramp_is_interpolated_between_calls() {
+        let mut resampler = Resampler::default();
+        let mut out = Vec::new();
+        for i in 0..8 {
+            resampler.process(&[(i * 100, 0)], 0.5, &mut out);
+        }
+        let left: Vec<i16> = out.iter().step_by(2).copied().collect();
+        assert_eq!(left[6..], [100, 150, 200, 250, 300, 350, 400, 450, 500, 550]);
+    }
+}
@@ -3,43 +3,33 @@ use sdl2::{
     pixels::{Color, PixelFormatEnum},
     rect::Rect,
     render::{Canvas, Texture, TextureAccess, TextureCreator},
-    video::{Window, WindowContext},
+    video::{FullscreenType, Window, WindowContext},
     EventPump, Sdl,
 };
 
 const WIDTH: u32 = 240;
 const HEIGHT: u32 = 160;
-const SCALE: u32 = 3;
-const AUDIO_TARGET_QUEUED_BYTES: u32 = 8192;
-const AUDIO_DROP_THRESHOLD_BYTES: u32 = 16384;
+const DEFAULT_SCALE: u32 = 3;
+const AUDIO_FALLBACK_RATE: i32 = 48000;
+const AUDIO_LATENCY: f64 = 0.05;
+const AUDIO_MAX_RATE_DELTA: f64 = 0.01;
 
 use std::{error::Error, fmt};
 
-use sdl2::keyboard::Scancode;
-
-const KEY_BINDINGS: [(Scancode, u8); 10] = [
-    (Scancode::A, 0),
-    (Scancode::S, 1),
-    (Scancode::Z, 2),
-    (Scancode::X, 3),
-    (Scancode::Return, 4),
-    (Scancode::Space, 5),
-    (Scancode::Up, 6),
-    (Scancode::Down, 7),
-    (Scancode::Left, 8),
-    (Scancode::Right, 9),
-];
-
-pub fn get_keystate(event_pump: &EventPump) -> u16 {
-    let keyboard = event_pump.keyboard_state();
-    let mut keystate: u16 = 0xFFFF;
-    for &(code, bit) in &KEY_BINDINGS {
-        if keyboard.is_scancode_pressed(code) {
-            keystate &= !(1 << bit);
-        }
-    }
-    keystate
-}
+use sdl2::{
+    controller::{Axis, GameController},
+    event::{Event, WindowEvent},
+    keyboard::{Mod, Scancode},
+    GameControllerSubsystem,
+};
+
+use crate::{
+    config::{Bindings, Hotkey},
+    filter::{Filter, PostProcess},
+    resample::Resampler,
+};
+
+const MOTION_STEP: i16 = 0x2000;
 
 #[derive(Debug)]
 pub enum EgbaUIError {
@@ -49,6 +39,7 @@ pub enum EgbaUIError {
     WindowCreationError(String),
     CanvasCreationError(String),
     ContextInitError(String),
+    ControllerInitError(String),
 }
 
 impl fmt::Display for EgbaUIError {
@@ -62,24 +53,69 @@ impl fmt::Display for EgbaUIError {
             EgbaUIError::ContextInitError(e) => {
                 write!(f, "SDL context initialization error: {}", e)
             }
+            EgbaUIError::ControllerInitError(e) => {
+                write!(f, "SDL game controller initialization error: {}", e)
+            }
         }
     }
 }
 
 impl Error for EgbaUIError {}
 
+#[derive(Clone, Copy, Debug)]
+pub struct VideoOptions {
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub integer_scaling: bool,
+    pub filter: Filter,
+    pub frame_blending: bool,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            scale: DEFAULT_SCALE,
+            fullscreen: false,
+            integer_scaling: false,
+            filter: Filter::None,
+            frame_blending: false,
+        }
+    }
+}
+
+fn letterbox(output: (u32, u32), integer_scaling: bool) -> Rect {
+    let (out_w, out_h) = output;
+    let scale = (out_w as f64 / WIDTH as f64).min(out_h as f64 / HEIGHT as f64);
+    let scale = if integer_scaling && scale >= 1.0 { scale.floor() } else { scale };
+    let width = ((WIDTH as f64 * scale) as u32).max(1);
+    let height = ((HEIGHT as f64 * scale) as u32).max(1);
+    Rect::new(
+        (out_w.saturating_sub(width) / 2) as i32,
+        (out_h.saturating_sub(height) / 2) as i32,
+        width,
+        height,
+    )
+}
+
 pub struct EgbaUI {
     canvas: Canvas<Window>,
     context: Sdl,
     audio_device: AudioQueue<i16>,
     _texture_creator: TextureCreator<WindowContext>,
     texture: Texture,
-    dest_rect: Rect,
+    post: PostProcess,
+    filter_scale: u32,
+    integer_scaling: bool,
     audio_buf: Vec<i16>,
+    resampler: Resampler,
+    audio_target: u32,
+    controllers: GameControllerSubsystem,
+    controller: Option<GameController>,
+    bindings: Bindings,
 }
 
 impl EgbaUI {
-    pub fn new() -> Result<Self, EgbaUIError> {
+    pub fn new(bindings: Bindings, options: VideoOptions) -> Result<Self, EgbaUIError> {
         let driver = std::env::var("EGBA_RENDER_DRIVER").unwrap_or_else(|_| "opengl".to_string());
         sdl2::hint::set("SDL_HINT_RENDER_DRIVER", &driver);
         sdl2::hint::set("SDL_RENDER_VSYNC", "0");
@@ -90,9 +126,12 @@ impl EgbaUI {
             .video()
             .map_err(|e| EgbaUIError::VideoInitError(e.to_string()))?;
 
-        let window = video
-            .window("EGBA", WIDTH * SCALE, HEIGHT * SCALE)
-            .position_centered()
+        let mut builder = video.window("EGBA", WIDTH * options.scale, HEIGHT * options.scale);
+        builder.position_centered().resizable();
+        if options.fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let window = builder
             .build()
             .map_err(|e| EgbaUIError::WindowCreationError(e.to_string()))?;
 
@@ -107,13 +146,14 @@ impl EgbaUI {
         canvas.clear();
         canvas.present();
 
+        let filter_scale = options.filter.factor() as u32;
         let texture_creator = canvas.texture_creator();
         let texture = texture_creator
             .create_texture(
                 PixelFormatEnum::RGB888,
                 TextureAccess::Streaming,
-                WIDTH,
-                HEIGHT,
+                WIDTH * filter_scale,
+                HEIGHT * filter_scale,
             )
             .map_err(|e| EgbaUIError::CanvasCreationError(e.to_string()))?;
 
@@ -121,8 +161,11 @@ impl EgbaUI {
             .audio()
             .map_err(|e| EgbaUIError::AudioInitError(e.to_string()))?;
 
+        let device_rate = audio_subsystem
+            .audio_playback_device_spec(0)
+            .map_or(AUDIO_FALLBACK_RATE, |spec| spec.freq);
         let desired_spec = AudioSpecDesired {
-            freq: Some(32768),
+            freq: Some(device_rate),
             channels: Some(2),
             samples: Some(512),
         };
@@ -132,6 +175,11 @@ impl EgbaUI {
             .map_err(|e| EgbaUIError::AudioInitError(e.to_string()))?;
 
         audio_device.resume();
+        let audio_target = (audio_device.spec().freq as f64 * AUDIO_LATENCY) as u32;
+
+        let controllers = context
+            .game_controller()
+            .map_err(|e| EgbaUIError::ControllerInitError(e.to_string()))?;
 
         Ok(Self {
             canvas,
@@ -139,51 +187,177 @@ impl EgbaUI {
             audio_device,
             _texture_creator: texture_creator,
             texture,
-            dest_rect: Rect::new(0, 0, WIDTH * SCALE, HEIGHT * SCALE),
+            post: PostProcess::new(options.filter, options.frame_blending),
+            filter_scale,
+            integer_scaling: options.integer_scaling,
             audio_buf: Vec::with_capacity(4096),
+            resampler: Resampler::default(),
+            audio_target,
+            controllers,
+            controller: None,
+            bindings,
         })
     }
 
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } if self.controller.is_none() => {
+                match self.controllers.open(which) {
+                    Ok(controller) => {
+                        println!("Controller connected: {}", controller.name());
+                        self.controller = Some(controller);
+                    }
+                    Err(err) => eprintln!("Error: failed to open controller: {}", err),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. }
+                if self.controller.as_ref().is_some_and(|c| c.instance_id() == which) =>
+            {
+                println!("Controller disconnected.");
+                self.controller = None;
+            }
+            Event::Window {
+                win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                ..
+            } => self.present(),
+            _ => {}
+        }
+    }
+
+    pub fn hotkey(&self, scancode: Scancode, keymod: Mod) -> Option<Hotkey> {
+        self.bindings
+            .hotkey(scancode, keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD))
+    }
+
+    pub fn is_held(&self, event_pump: &EventPump, hotkey: Hotkey) -> bool {
+        let keyboard = event_pump.keyboard_state();
+        self.bindings
+            .hotkey_scancodes(hotkey)
+            .any(|code| keyboard.is_scancode_pressed(code))
+    }
+
+    pub fn keystate(&self, event_pump: &EventPump) -> u16 {
+        let keyboard = event_pump.keyboard_state();
+        let mut keystate: u16 = 0xFFFF;
+        for (bit, &code) in self.bindings.keys().iter().enumerate() {
+            if keyboard.is_scancode_pressed(code) {
+                keystate &= !(1 << bit);
+            }
+        }
+        if let Some(controller) = &self.controller {
+            for (bit, &button) in self.bindings.buttons().iter().enumerate() {
+                if controller.button(button) {
+                    keystate &= !(1 << bit);
+                }
+            }
+            let threshold = self.bindings.stick_threshold();
+            let x = controller.axis(Axis::LeftX);
+            let y = controller.axis(Axis::LeftY);
+            for (pressed, bit) in [
+                (x > threshold, 4),
+                (x < -threshold, 5),
+                (y < -threshold, 6),
+                (y > threshold, 7),
+            ] {
+                if pressed {
+                    keystate &= !(1 << bit);
+                }
+            }
+        }
+        keystate
+    }
+
+    fn axis(&self, event_pump: &EventPump, negative: Hotkey, positive: Hotkey) -> i16 {
+        let mut value = 0;
+        if self.is_held(event_pump, negative) {
+            value -= MOTION_STEP;
+        }
+        if self.is_held(event_pump, positive) {
+            value += MOTION_STEP;
+        }
+        value
+    }
+
+    pub fn tilt(&self, event_pump: &EventPump) -> (i16, i16) {
+        (
+            self.axis(event_pump, Hotkey::TiltLeft, Hotkey::TiltRight),
+            self.axis(event_pump, Hotkey::TiltUp, Hotkey::TiltDown),
+        )
+    }
+
+    pub fn gyro(&self, event_pump: &EventPump) -> i16 {
+        self.axis(event_pump, Hotkey::GyroLeft, Hotkey::GyroRight)
+    }
+
     pub fn get_event_pump(&mut self) -> Result<EventPump, String> {
         self.context.event_pump()
     }
 
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let state = if window.fullscreen_state() == FullscreenType::Off {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        if let Err(err) = window.set_fullscreen(state) {
+            eprintln!("Error: failed to switch fullscreen: {}", err);
+        }
+    }
+
     pub fn render_frame(&mut self, framebuffer: &[u32]) {
+        let frame = self.post.process(framebuffer, WIDTH as usize);
         let bytes: &[u8] = unsafe {
-            std::slice::from_raw_parts(framebuffer.as_ptr().cast::<u8>(), framebuffer.len() * 4)
+            std::slice::from_raw_parts(frame.as_ptr().cast::<u8>(), frame.len() * 4)
         };
         self.texture
-            .update(None, bytes, (WIDTH * 4) as usize)
+            .update(None, bytes, (WIDTH * self.filter_scale * 4) as usize)
             .expect("Failed to update texture");
+        self.present();
+    }
 
+    fn present(&mut self) {
+        let output = self.canvas.output_size().unwrap_or((WIDTH, HEIGHT));
+        self.canvas.clear();
         self.canvas
-            .copy(&self.texture, None, Some(self.dest_rect))
+            .copy(&self.texture, None, Some(letterbox(output, self.integer_scaling)))
             .expect("Failed to copy texture to canvas");
         self.canvas.present();
     }
 
-    pub fn queue_audio(&mut self, samples: &[(i16, i16)]) {
-        if samples.is_empty() {
+    pub fn queue_audio(&mut self, samples: &[(i16, i16)], source_rate: u32, speed: Option<f64>) {
+        let Some(speed) = speed else {
+            self.audio_device.clear();
+            self.resampler = Resampler::default();
             return;
-        }
-        let queued = self.audio_device.size();
-        if queued > AUDIO_DROP_THRESHOLD_BYTES {
-            return;
-        }
-        let take = if queued > AUDIO_TARGET_QUEUED_BYTES {
-            samples.len() / 2
-        } else {
-            samples.len()
         };
-        if take == 0 {
-            return;
+        let mut queued = self.audio_device.size() / 4;
+        if queued == 0 {
+            self.audio_buf.clear();
+            self.audio_buf.resize(self.audio_target as usize * 2, 0);
+            let _ = self.audio_device.queue_audio(&self.audio_buf);
+            queued = self.audio_target;
         }
+
+        let fill = (queued as f64 / (self.audio_target * 2) as f64).min(1.0);
+        let adjust = 1.0 + (1.0 - 2.0 * fill) * AUDIO_MAX_RATE_DELTA;
+        let device_rate = self.audio_device.spec().freq as f64 * adjust;
         self.audio_buf.clear();
-        self.audio_buf.reserve(take * 2);
-        for &(l, r) in &samples[..take] {
-            self.audio_buf.push(l);
-            self.audio_buf.push(r);
-        }
+        self.resampler
+            .process(samples, source_rate as f64 * speed / device_rate, &mut self.audio_buf);
         let _ = self.audio_device.queue_audio(&self.audio_buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_keeps_the_aspect_ratio_centred() {
+        assert_eq!(letterbox((720, 480), false), Rect::new(0, 0, 720, 480));
+        assert_eq!(letterbox((1920, 1080), false), Rect::new(150, 0, 1620, 1080));
+        assert_eq!(letterbox((1920, 1080), true), Rect::new(240, 60, 1440, 960));
+        assert_eq!(letterbox((200, 200), true), Rect::new(0, 33, 200, 133));
+    }
+}
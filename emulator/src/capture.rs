@@ -0,0 +1,357 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use egba_core::gba::{
+    AUDIO_SAMPLE_RATE, AUDIO_TAP_NAMES, CPU_CLOCK, CYCLES_PER_FRAME, FB_HEIGHT, FB_WIDTH, GBA,
+};
+use png::{BitDepth, ColorType, Compression, Encoder};
+
+const DELAY_DEN: u64 = 60_000;
+
+fn encoder<W: Write>(out: W) -> Encoder<'static, W> {
+    let mut encoder = Encoder::new(out, FB_WIDTH as u32, FB_HEIGHT as u32);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.set_compression(Compression::Fast);
+    encoder
+}
+
+fn animation_header(frames: u32) -> io::Result<Vec<u8>> {
+    let mut header = Vec::new();
+    let mut encoder = encoder(&mut header);
+    encoder.set_animated(frames, 0)?;
+    encoder.write_header()?.finish()?;
+    header.truncate(header.len() - 12);
+    Ok(header)
+}
+
+fn rgb(framebuffer: &[u32], out: &mut Vec<u8>) {
+    out.clear();
+    out.extend(framebuffer.iter().flat_map(|px| [(px >> 16) as u8, (px >> 8) as u8, *px as u8]));
+}
+
+fn frame_ticks(frames: u64) -> u64 {
+    frames * CYCLES_PER_FRAME as u64 * DELAY_DEN / CPU_CLOCK as u64
+}
+
+pub struct WavWriter {
+    out: BufWriter<File>,
+    paths: Vec<PathBuf>,
+    data_bytes: u32,
+    rate: Option<u32>,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            out: Self::open(path)?,
+            paths: vec![path.to_path_buf()],
+            data_bytes: 0,
+            rate: None,
+        })
+    }
+
+    fn open(path: &Path) -> io::Result<BufWriter<File>> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&wav_header(0, AUDIO_SAMPLE_RATE))?;
+        Ok(out)
+    }
+
+    pub fn push(&mut self, samples: &[(i16, i16)], rate: u32) -> io::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        if self.rate.is_some_and(|current| current != rate) {
+            self.finish_segment()?;
+            let first = &self.paths[0];
+            let stem = first.file_stem().unwrap_or_default().to_string_lossy();
+            let path = first.with_file_name(format!("{}-{}.wav", stem, self.paths.len() + 1));
+            self.out = Self::open(&path)?;
+            self.paths.push(path);
+            self.data_bytes = 0;
+        }
+        self.rate = Some(rate);
+        for &(left, right) in samples {
+            self.out.write_all(&left.to_le_bytes())?;
+            self.out.write_all(&right.to_le_bytes())?;
+        }
+        self.data_bytes += samples.len() as u32 * 4;
+        Ok(())
+    }
+
+    fn finish_segment(&mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        let rate = self.rate.unwrap_or(AUDIO_SAMPLE_RATE);
+        self.out.write_all(&wav_header(self.data_bytes, rate))?;
+        self.out.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.finish_segment()?;
+        Ok(self.paths)
+    }
+}
+
+pub struct AudioDump {
+    mixed: WavWriter,
+    stems: Vec<WavWriter>,
+}
+
+impl AudioDump {
+    pub fn create(path: &Path, stems: bool) -> io::Result<Self> {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let stems = if stems {
+            AUDIO_TAP_NAMES
+                .iter()
+                .map(|name| path.with_file_name(format!("{}-{}.wav", stem, name)))
+                .map(|path| WavWriter::create(&path))
+                .collect::<io::Result<_>>()?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            mixed: WavWriter::create(path)?,
+            stems,
+        })
+    }
+
+    pub fn push_frame(&mut self, gba: &GBA) -> io::Result<()> {
+        for (rate, run) in gba.audio_runs() {
+            self.mixed.push(&gba.audio_samples()[run.clone()], rate)?;
+            for (i, stem) in self.stems.iter_mut().enumerate() {
+                let taps = gba.audio_taps().get(run.clone()).unwrap_or_default();
+                let samples: Vec<(i16, i16)> = taps.iter().map(|taps| taps[i]).collect();
+                stem.push(&samples, rate)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = self.mixed.finish()?;
+        for stem in self.stems {
+            paths.extend(stem.finish()?);
+        }
+        Ok(paths)
+    }
+}
+
+pub struct AvRecorder {
+    video: png::Writer<BufWriter<File>>,
+    file: File,
+    audio: WavWriter,
+    path: PathBuf,
+    frames: u32,
+    pixels: Vec<u8>,
+}
+
+impl AvRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = File::create(path)?;
+        let mut encoder = encoder(BufWriter::new(file.try_clone()?));
+        encoder.set_animated(u32::MAX, 0)?;
+        let video = encoder.write_header()?;
+        let audio = WavWriter::create(&path.with_extension("wav"))?;
+        Ok(Self {
+            video,
+            file,
+            audio,
+            path: path.to_path_buf(),
+            frames: 0,
+            pixels: Vec::with_capacity(FB_WIDTH * FB_HEIGHT * 3),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn push_frame(&mut self, framebuffer: &[u32]) -> io::Result<()> {
+        let delay = frame_ticks(self.frames as u64 + 1) - frame_ticks(self.frames as u64);
+        self.video.set_frame_delay(delay as u16, DELAY_DEN as u16)?;
+        rgb(framebuffer, &mut self.pixels);
+        self.video.write_image_data(&self.pixels)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn push_audio(&mut self, samples: &[(i16, i16)], rate: u32) -> io::Result<()> {
+        self.audio.push(samples, rate)
+    }
+
+    pub fn finish(mut self) -> io::Result<(u32, Vec<PathBuf>)> {
+        if self.frames == 0 {
+            self.push_frame(&[0; FB_WIDTH * FB_HEIGHT])?;
+        }
+        self.video.finish()?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&animation_header(self.frames)?)?;
+        Ok((self.frames, self.audio.finish()?))
+    }
+}
+
+fn wav_header(data_bytes: u32, rate: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(44);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_bytes).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&rate.to_le_bytes());
+    out.extend_from_slice(&(rate * 4).to_le_bytes());
+    out.extend_from_slice(&4u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_bytes.to_le_bytes());
+    out
+}
+
+#[derive(Default)]
+pub struct Capture {
+    pub recorder: Option<AvRecorder>,
+    pub audio: Option<AudioDump>,
+}
+
+impl Capture {
+    pub fn frame_done(&mut self, gba: &GBA) {
+        if let Some(recorder) = &mut self.recorder {
+            let result = recorder.push_frame(gba.framebuffer()).and_then(|()| {
+                gba.audio_runs().try_for_each(|(rate, run)| {
+                    recorder.push_audio(&gba.audio_samples()[run], rate)
+                })
+            });
+            if let Err(err) = result {
+                eprintln!("Error: capture stopped: {}", err);
+                self.recorder = None;
+            }
+        }
+        if let Some(audio) = &mut self.audio {
+            if let Err(err) = audio.push_frame(gba) {
+                eprintln!("Error: audio dump stopped: {}", err);
+                self.audio = None;
+            }
+        }
+    }
+
+    pub fn finish(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path().to_owned();
+            match recorder.finish() {
+                Ok((frames, audio_paths)) => {
+                    let audio: Vec<String> =
+                        audio_paths.iter().map(|p| p.display().to_string()).collect();
+                    println!(
+                        "Captured {} frames to {} and {}.",
+                        frames,
+                        path.display(),
+                        audio.join(", ")
+                    )
+                }
+                Err(err) => eprintln!("Error: failed to finish {}: {}", path.display(), err),
+            }
+        }
+        if let Some(audio) = self.audio.take() {
+            match audio.finish() {
+                Ok(paths) => {
+                    for path in paths {
+                        println!("Wrote audio to {}.", path.display());
+                    }
+                }
+                Err(err) => eprintln!("Error: failed to finish audio dump: {}", err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egba_core::{bios::Bios, cartridge::Cartridge, rom::Rom};
+
+    #[test]
+    fn recording_patches_frame_count_and_wav_length() {
+        let dir = std::env::temp_dir().join(format!("egba-capture-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.png");
+        let mut recorder = AvRecorder::create(&path).expect("create");
+        let framebuffer: Vec<u32> =
+            (0..FB_WIDTH * FB_HEIGHT).map(|i| i as u32 * 0x0102).collect();
+        for _ in 0..3 {
+            recorder.push_frame(&framebuffer).expect("frame");
+            recorder.push_audio(&[(1, -1); 549], AUDIO_SAMPLE_RATE).expect("audio");
+        }
+        let (frames, audio_paths) = recorder.finish().expect("finish");
+        assert_eq!((frames, audio_paths), (3, vec![path.with_extension("wav")]));
+        let video = std::fs::read(&path).unwrap();
+        let audio = std::fs::read(path.with_extension("wav")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut reader = png::Decoder::new(video.as_slice()).read_info().expect("decode");
+        let actl = reader.info().animation_control.expect("animated");
+        assert_eq!((actl.num_frames, actl.num_plays), (3, 0));
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let mut delays = 0;
+        for _ in 0..3 {
+            reader.next_frame(&mut pixels).expect("frame");
+            let fctl = reader.info().frame_control.expect("fcTL");
+            assert_eq!(fctl.delay_den, DELAY_DEN as u16);
+            delays += fctl.delay_num as u64;
+        }
+        assert_eq!(delays, frame_ticks(3));
+        assert_eq!(pixels[3..6], [0x00, 0x01, 0x02]);
+
+        assert_eq!(audio.len(), 44 + 3 * 549 * 4);
+        assert_eq!(audio[40..44], (3u32 * 549 * 4).to_le_bytes());
+    }
+
+    #[test]
+    fn wav_starts_a_new_segment_when_the_rate_changes() {
+        let dir = std::env::temp_dir().join(format!("egba-rate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut wav = WavWriter::create(&dir.join("song.wav")).expect("create");
+        wav.push(&[], 262144).expect("empty");
+        wav.push(&[(8, -8); 2], 65536).expect("first");
+        wav.push(&[(1, 1)], 65536).expect("same rate");
+        wav.push(&[(3, 5); 3], 131072).expect("higher");
+        let paths = wav.finish().expect("finish");
+        let files: Vec<Vec<u8>> = paths.iter().map(|p| std::fs::read(p).unwrap()).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(paths, [dir.join("song.wav"), dir.join("song-2.wav")]);
+        assert_eq!(files[0][24..28], 65536u32.to_le_bytes());
+        assert_eq!(files[0][40..44], 12u32.to_le_bytes());
+        assert_eq!(files[0][44..], [8, 0, 248, 255, 8, 0, 248, 255, 1, 0, 1, 0]);
+        assert_eq!(files[1][24..28], 131072u32.to_le_bytes());
+        assert_eq!(files[1].len(), 44 + 3 * 4);
+    }
+
+    #[test]
+    fn audio_dump_writes_one_stem_per_source() {
+        let cart = Cartridge::new(Rom::new(&[0u8; 0x1000]), Path::new("/nonexistent/no.sav"))
+            .expect("cart");
+        let mut gba = GBA::new_skipping_bios(Bios::hle(), cart);
+        gba.write_byte(0x0400_0084, 0x80);
+        gba.enable_audio_taps(true);
+
+        let dir = std::env::temp_dir().join(format!("egba-dump-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut dump = AudioDump::create(&dir.join("song.wav"), true).expect("create");
+        for _ in 0..2 {
+            gba.run_frame();
+            dump.push_frame(&gba).expect("frame");
+            gba.clear_audio();
+        }
+        let paths = dump.finish().expect("finish");
+        let sizes: Vec<usize> = paths.iter().map(|p| std::fs::read(p).unwrap().len()).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(paths.len(), 1 + AUDIO_TAP_NAMES.len());
+        assert_eq!(paths[1], dir.join("song-square1.wav"));
+        assert!(sizes[0] > 44 + 1000 * 4, "two frames of audio");
+        assert!(sizes.iter().all(|&size| size == sizes[0]));
+    }
+}
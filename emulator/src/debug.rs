@@ -0,0 +1,58 @@
+use std::io;
+
+use egba_core::gba::GBA;
+#[cfg(feature = "debug-hooks")]
+use egba_debugger::{gdb::GdbStub, tui::Debugger};
+
+#[derive(Default)]
+pub struct Debugging {
+    #[cfg(feature = "debug-hooks")]
+    tui: Option<Debugger>,
+    #[cfg(feature = "debug-hooks")]
+    gdb: Option<GdbStub>,
+}
+
+impl Debugging {
+    #[cfg(feature = "debug-hooks")]
+    pub fn open_tui(&mut self) -> io::Result<()> {
+        self.tui = Some(Debugger::new()?);
+        Ok(())
+    }
+
+    #[cfg(feature = "debug-hooks")]
+    pub fn attach_gdb(&mut self, port: u16) -> io::Result<()> {
+        let mut stub = GdbStub::bind(port)?;
+        println!("Waiting for GDB on localhost:{}...", port);
+        stub.wait_for_client()?;
+        self.gdb = Some(stub);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "debug-hooks"))]
+    pub fn open_tui(&mut self) -> io::Result<()> {
+        Err(unsupported("--debug"))
+    }
+
+    #[cfg(not(feature = "debug-hooks"))]
+    pub fn attach_gdb(&mut self, _port: u16) -> io::Result<()> {
+        Err(unsupported("--gdb"))
+    }
+
+    pub fn run_frame(&mut self, gba: &mut GBA) {
+        #[cfg(feature = "debug-hooks")]
+        match (self.tui.as_mut(), self.gdb.as_mut()) {
+            (Some(debugger), _) => return debugger.run_frame(gba),
+            (None, Some(stub)) => return stub.run_frame(gba),
+            (None, None) => {}
+        }
+        gba.run_frame();
+    }
+}
+
+#[cfg(not(feature = "debug-hooks"))]
+fn unsupported(flag: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} needs a build with `--features debug-hooks`", flag),
+    )
+}
@@ -1,68 +1,223 @@
+mod capture;
+mod debug;
+mod movie;
+mod speed;
+
 use std::{
     fs,
-    path::PathBuf,
-    time::{Duration, Instant},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-use clap::{command, Arg};
-use egba_core::{bios::Bios, cartridge::Cartridge, gba::GBA, rom::Rom};
+use clap::{command, parser::ValueSource, Arg};
+use egba_core::{
+    bios::Bios,
+    cartridge::Cartridge,
+    gba::{Renderer, GBA},
+    link::LinkTransport,
+    movie::{content_hash, FrameInput, Movie},
+    rom::Rom,
+};
+use capture::{AudioDump, AvRecorder, Capture};
+use debug::Debugging;
 use egba_debugger::EGBADebugger;
+use egba_link::socket::SocketLink;
 use egba_ui::{
-    window::{get_keystate, EgbaUI},
-    Event, Keycode,
+    config::{Bindings, Hotkey},
+    filter::Filter,
+    window::{EgbaUI, VideoOptions},
+    Event,
 };
+use movie::MovieMode;
+use speed::{SpeedControl, FRAME_DURATION};
 
-const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const LIGHT_STEP: u8 = 0x20;
 
-fn run(ui: &mut EgbaUI, gba: &mut GBA, debug: bool) {
+fn state_path(sav_path: &Path, slot: u8) -> PathBuf {
+    sav_path.with_extension(format!("ss{}", slot))
+}
+
+fn save_slot(gba: &GBA, sav_path: &Path, slot: u8) {
+    let path = state_path(sav_path, slot);
+    match fs::write(&path, gba.save_state()) {
+        Ok(()) => println!("Saved state to slot {}.", slot),
+        Err(err) => eprintln!("Error: failed to write {}: {}", path.display(), err),
+    }
+}
+
+fn load_slot(gba: &mut GBA, sav_path: &Path, slot: u8) {
+    let path = state_path(sav_path, slot);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Error: failed to read {}: {}", path.display(), err);
+            return;
+        }
+    };
+    match gba.load_state(&data) {
+        Ok(()) => println!("Loaded state from slot {}.", slot),
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
+
+fn take_screenshot(gba: &GBA, sav_path: &Path) {
+    let stem = sav_path.file_stem().unwrap_or_default().to_string_lossy();
+    let path = (1..)
+        .map(|n| sav_path.with_file_name(format!("{}-{}.png", stem, n)))
+        .find(|path| !path.exists())
+        .expect("a free screenshot name");
+    match gba.dump_screenshot(&path) {
+        Ok(()) => println!("Saved screenshot to {}.", path.display()),
+        Err(err) => eprintln!("Error: failed to write {}: {}", path.display(), err),
+    }
+}
+
+fn shutdown(gba: &GBA, movie: Option<&MovieMode>, capture: &mut Capture) {
+    if !movie.is_some_and(MovieMode::is_replay) {
+        gba.save_backup();
+    }
+    if let Some(movie) = movie {
+        movie.finish();
+    }
+    capture.finish();
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn run(
+    ui: &mut EgbaUI,
+    gba: &mut GBA,
+    sav_path: &Path,
+    speed: &mut SpeedControl,
+    mut movie: Option<MovieMode>,
+    mut capture: Capture,
+    mut debugging: Debugging,
+) {
     let mut event_pump = ui
         .get_event_pump()
         .expect("Failed to create SDL2 event pump");
 
     let mut next_frame_at = Instant::now() + FRAME_DURATION;
+    let mut last_present = Instant::now();
+    let mut light_level: u8 = 0;
 
     '_game: loop {
         for event in event_pump.poll_iter() {
+            ui.handle_event(&event);
             match event {
                 Event::Quit { .. } => {
                     println!("Quit event received. Exiting.");
-                    gba.save_backup();
+                    shutdown(gba, movie.as_ref(), &mut capture);
                     return;
                 }
                 Event::KeyUp {
-                    keycode: Some(Keycode::Escape),
+                    scancode: Some(scancode),
+                    keymod,
                     ..
-                } => {
-                    println!("Escape key pressed. Exiting.");
-                    gba.save_backup();
+                } if ui.hotkey(scancode, keymod) == Some(Hotkey::Quit) => {
+                    println!("Quit key pressed. Exiting.");
+                    shutdown(gba, movie.as_ref(), &mut capture);
                     return;
                 }
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => match ui.hotkey(scancode, keymod) {
+                    Some(hotkey @ (Hotkey::LightDown | Hotkey::LightUp)) => {
+                        light_level = if hotkey == Hotkey::LightDown {
+                            light_level.saturating_sub(LIGHT_STEP)
+                        } else {
+                            light_level.saturating_add(LIGHT_STEP)
+                        };
+                        println!("Solar sensor light level: {}", light_level);
+                    }
+                    Some(Hotkey::Turbo) => println!("Turbo {}.", on_off(speed.toggle_turbo())),
+                    Some(Hotkey::SlowMotion) => {
+                        println!("Slow motion {}.", on_off(speed.toggle_slow_motion()))
+                    }
+                    Some(Hotkey::Pause) => {
+                        let paused = speed.toggle_pause();
+                        println!("{}", if paused { "Paused." } else { "Resumed." })
+                    }
+                    Some(Hotkey::FrameAdvance) => speed.advance_frame(),
+                    Some(Hotkey::Screenshot) => take_screenshot(gba, sav_path),
+                    Some(Hotkey::Fullscreen) => ui.toggle_fullscreen(),
+                    Some(Hotkey::SaveSlot(slot)) => save_slot(gba, sav_path, slot),
+                    Some(Hotkey::LoadSlot(_)) if movie.is_some() => {
+                        eprintln!("Error: save states can't be loaded during a movie")
+                    }
+                    Some(Hotkey::LoadSlot(slot)) => load_slot(gba, sav_path, slot),
+                    _ => {}
+                },
                 _ => {}
             }
         }
-        let keystate = get_keystate(&event_pump);
-        gba.update_keypad(keystate);
-
-        if debug {
-            gba.show_stats();
-            std::thread::sleep(Duration::from_millis(300));
+        let live = FrameInput {
+            keys: ui.keystate(&event_pump),
+            tilt: ui.tilt(&event_pump),
+            gyro: ui.gyro(&event_pump),
+            light: light_level,
+        };
+        if movie.is_none() {
+            live.apply(gba);
         }
+        speed.set_holding(ui.is_held(&event_pump, Hotkey::FastForward));
 
-        gba.run_frame();
-        ui.render_frame(gba.framebuffer());
-        ui.queue_audio(gba.audio_samples());
-        gba.clear_audio();
+        let paused = speed.is_paused();
+        let rewinding = ui.is_held(&event_pump, Hotkey::Rewind);
+        let frame_duration = if paused || rewinding {
+            Some(FRAME_DURATION)
+        } else {
+            speed.frame_duration()
+        };
+        if rewinding {
+            if gba.rewind() {
+                ui.render_frame(gba.framebuffer());
+                last_present = Instant::now();
+            }
+            ui.queue_audio(&[], gba.audio_sample_rate(), None);
+        } else if speed.should_run() {
+            if let Some(movie) = movie.as_mut() {
+                movie.input(live).apply(gba);
+            }
+            debugging.run_frame(gba);
+            if let Some(movie) = movie.as_mut() {
+                movie.frame_done(gba);
+            }
+            capture.frame_done(gba);
+            if frame_duration.is_some() || last_present.elapsed() >= FRAME_DURATION {
+                ui.render_frame(gba.framebuffer());
+                last_present = Instant::now();
+            }
+            let audio_speed = if paused { None } else { speed.speed() };
+            for (rate, run) in gba.audio_runs() {
+                ui.queue_audio(&gba.audio_samples()[run], rate, audio_speed);
+            }
+            gba.clear_audio();
+        }
 
+        let Some(frame_duration) = frame_duration else {
+            next_frame_at = Instant::now() + FRAME_DURATION;
+            continue;
+        };
         let now = Instant::now();
         if now < next_frame_at {
             std::thread::sleep(next_frame_at - now);
-            next_frame_at += FRAME_DURATION;
+            next_frame_at += frame_duration;
         } else {
             let behind = now - next_frame_at;
-            if behind > FRAME_DURATION * 4 {
-                next_frame_at = now + FRAME_DURATION;
+            if behind > frame_duration * 4 {
+                next_frame_at = now + frame_duration;
             } else {
-                next_frame_at += FRAME_DURATION;
+                next_frame_at += frame_duration;
             }
         }
     }
@@ -72,11 +227,11 @@ fn main() {
     let args = command!()
         .arg(
             Arg::new("bios")
-                .help("Enter BIOS file path")
+                .help("Enter BIOS file path; omit to use the built-in HLE BIOS")
                 .short('b')
                 .long("bios")
                 .value_parser(clap::value_parser!(PathBuf))
-                .required(true),
+                .required(false),
         )
         .arg(
             Arg::new("rom")
@@ -96,10 +251,11 @@ fn main() {
         )
         .arg(
             Arg::new("debug")
-                .help("Enable debug mode")
+                .help("Open the interactive TUI debugger (starts paused)")
                 .short('d')
                 .long("debug")
-                .action(clap::ArgAction::SetTrue),
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("gdb"),
         )
         .arg(
             Arg::new("headless")
@@ -116,10 +272,142 @@ fn main() {
         )
         .arg(
             Arg::new("screenshot")
-                .help("After headless run, dump framebuffer PPM to this path")
+                .help("After headless run, dump the framebuffer to this path (PNG for .png, otherwise PPM)")
                 .long("screenshot")
                 .value_parser(clap::value_parser!(PathBuf)),
         )
+        .arg(
+            Arg::new("gdb")
+                .help("Listen for a GDB remote connection on this localhost port and wait for it before starting")
+                .long("gdb")
+                .value_parser(clap::value_parser!(u16))
+                .conflicts_with("headless"),
+        )
+        .arg(
+            Arg::new("link-host")
+                .help("Host a link cable on this Unix socket path and wait for --link-players - 1 consoles to join")
+                .long("link-host")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("link-join"),
+        )
+        .arg(
+            Arg::new("link-join")
+                .help("Join a link cable hosted on this Unix socket path")
+                .long("link-join")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("link-players")
+                .help("Number of linked consoles, including the host")
+                .long("link-players")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("2"),
+        )
+        .arg(
+            Arg::new("dot-renderer")
+                .help("Render pixels as the beam advances so mid-scanline register writes are visible (slower)")
+                .long("dot-renderer")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fast-forward")
+                .help("Speed multiplier while fast-forwarding (Tab held or turbo toggled); 0 runs uncapped")
+                .long("fast-forward")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("slow-motion")
+                .help("Speed multiplier while slow motion is toggled, between 0 and 1")
+                .long("slow-motion")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.5"),
+        )
+        .arg(
+            Arg::new("rewind-interval")
+                .help("Frames between rewind snapshots")
+                .long("rewind-interval")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("4"),
+        )
+        .arg(
+            Arg::new("rewind-buffer")
+                .help("Memory budget for rewind snapshots in MiB; 0 disables rewind")
+                .long("rewind-buffer")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("64"),
+        )
+        .arg(
+            Arg::new("record")
+                .help("Record the session's input to this movie file, written on exit")
+                .long("record")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["replay", "debug", "gdb", "link-host", "link-join"]),
+        )
+        .arg(
+            Arg::new("replay")
+                .help("Replay input from this movie file and report where emulation diverges from the recording")
+                .long("replay")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["debug", "gdb", "link-host", "link-join"]),
+        )
+        .arg(
+            Arg::new("capture")
+                .help("Record every emulated frame to this animated PNG and the audio to a WAV file beside it; a sample rate change starts <name>-2.wav, <name>-3.wav, …")
+                .long("capture")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("dump-audio")
+                .help("Write the mixed audio output to this WAV file; a sample rate change starts <name>-2.wav, <name>-3.wav, …")
+                .long("dump-audio")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("dump-stems")
+                .help("With --dump-audio, also write each PSG channel and DMA FIFO to <name>-<source>.wav")
+                .long("dump-stems")
+                .action(clap::ArgAction::SetTrue)
+                .requires("dump-audio"),
+        )
+        .arg(
+            Arg::new("config")
+                .help("Load keyboard, controller and hotkey bindings from this TOML file")
+                .long("config")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("scale")
+                .help("Initial window size as a multiple of 240x160")
+                .long("scale")
+                .value_parser(clap::value_parser!(u32).range(1..=10))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("fullscreen")
+                .help("Start in fullscreen (F11 toggles)")
+                .long("fullscreen")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("integer-scale")
+                .help("Only scale the picture by whole multiples when the window is resized or fullscreen")
+                .long("integer-scale")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("filter")
+                .help("Post-processing filter applied to each frame")
+                .long("filter")
+                .value_parser(Filter::NAMES)
+                .default_value("none"),
+        )
+        .arg(
+            Arg::new("frame-blend")
+                .help("Blend each frame with the previous one to emulate LCD ghosting")
+                .long("frame-blend")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("skip-bios")
                 .help("Skip BIOS boot animation; jump straight to cart entry at 0x08000000 with post-BIOS register/SP state")
@@ -128,43 +416,149 @@ fn main() {
         )
         .get_matches();
 
-    let bios_path = args
-        .get_one::<PathBuf>("bios")
-        .expect("Failed to read BIOS ROM path");
-    let bios_buffer = fs::read(bios_path).unwrap();
-    let bios_rom = Rom::new(&bios_buffer);
-    let bios = Bios::new(bios_rom).unwrap_or_else(|err| {
-        eprintln!("Error: {}", err);
-        std::process::exit(1);
-    });
+    let (bios, bios_hash) = match args.get_one::<PathBuf>("bios") {
+        Some(bios_path) => {
+            let bios_buffer = fs::read(bios_path).unwrap();
+            let bios_rom = Rom::new(&bios_buffer);
+            let bios = Bios::new(bios_rom).unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            });
+            (bios, content_hash(&bios_buffer))
+        }
+        None => (Bios::hle(), 0),
+    };
 
     let rom_path = args
         .get_one::<PathBuf>("rom")
         .expect("Failed to read Game ROM path");
     let rom_buffer = fs::read(rom_path).unwrap();
     let rom = Rom::new(&rom_buffer);
+    if let Some(header) = rom.header() {
+        println!("{} [{}] v{}", header.title, header.game_code, header.version);
+        if !header.checksum_valid {
+            eprintln!("Warning: ROM header checksum mismatch");
+        }
+    }
 
     let backup_path = args.get_one::<PathBuf>("backup").unwrap_or(rom_path);
     let mut sav_path = backup_path.to_owned();
     sav_path.set_extension("sav");
-    let cartridge = Cartridge::new(rom, &sav_path).unwrap_or_else(|err| {
-        eprintln!("Error: {}", err);
-        std::process::exit(1);
-    });
+
+    let rom_hash = content_hash(&rom_buffer);
+    let mut movie = if let Some(path) = args.get_one::<PathBuf>("replay") {
+        let data = fs::read(path).unwrap_or_else(|err| {
+            eprintln!("Error: failed to read {}: {}", path.display(), err);
+            std::process::exit(1);
+        });
+        let movie = Movie::decode(&data)
+            .and_then(|movie| movie.check_media(rom_hash, bios_hash).map(|()| movie))
+            .unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            });
+        println!("Replaying {} frames from {}.", movie.len(), path.display());
+        Some(MovieMode::Replay {
+            movie,
+            frame: 0,
+            desynced: false,
+        })
+    } else {
+        args.get_one::<PathBuf>("record").map(|path| {
+            let rtc_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            let save = fs::read(&sav_path).ok();
+            MovieMode::Record {
+                movie: Movie::new(rom_hash, bios_hash, args.get_flag("skip-bios"), rtc_time, save),
+                path: path.clone(),
+            }
+        })
+    };
+
+    let backup_data = match &movie {
+        Some(movie) => movie.movie().save.clone(),
+        None => fs::read(&sav_path).ok(),
+    };
+    let mut cartridge =
+        Cartridge::with_backup_data(rom, &sav_path, backup_data).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        });
+    if let Some(movie) = movie.as_mut() {
+        movie.prepare(&mut cartridge);
+    }
 
     let debug = args.get_flag("debug");
     let headless = args.get_flag("headless");
-    let skip_bios = args.get_flag("skip-bios");
+    let skip_bios = match &movie {
+        Some(movie) => movie.movie().skip_bios,
+        None => args.get_flag("skip-bios"),
+    };
+    let renderer = if args.get_flag("dot-renderer") {
+        Renderer::Dot
+    } else {
+        Renderer::Scanline
+    };
     let mut egba = if skip_bios {
         GBA::new_skipping_bios(bios, cartridge)
     } else {
         GBA::new(bios, cartridge)
+    }
+    .with_renderer(renderer);
+
+    let link = if let Some(path) = args.get_one::<PathBuf>("link-host") {
+        let players = *args.get_one::<usize>("link-players").unwrap_or(&2);
+        println!(
+            "Waiting for {} more console(s) on {}...",
+            players.saturating_sub(1),
+            path.display()
+        );
+        Some(SocketLink::host(path, players))
+    } else {
+        args.get_one::<PathBuf>("link-join").map(|path| SocketLink::join(path))
     };
+    if let Some(link) = link {
+        let link = link.unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        });
+        println!("Linked as player {} of {}.", link.player() + 1, link.players());
+        egba.connect_link(Box::new(link));
+    }
+
+    let mut capture = Capture::default();
+    if let Some(path) = args.get_one::<PathBuf>("capture") {
+        capture.recorder = Some(AvRecorder::create(path).unwrap_or_else(|err| {
+            eprintln!("Error: failed to create {}: {}", path.display(), err);
+            std::process::exit(1);
+        }));
+    }
+    if let Some(path) = args.get_one::<PathBuf>("dump-audio") {
+        let stems = args.get_flag("dump-stems");
+        capture.audio = Some(AudioDump::create(path, stems).unwrap_or_else(|err| {
+            eprintln!("Error: failed to create {}: {}", path.display(), err);
+            std::process::exit(1);
+        }));
+        egba.enable_audio_taps(stems);
+    }
 
     if headless {
-        let frames = *args.get_one::<u32>("frames").unwrap_or(&1);
+        let default_frames = args.value_source("frames") == Some(ValueSource::DefaultValue);
+        let frames = match &movie {
+            Some(movie) if movie.is_replay() && default_frames => movie.movie().len() as u32,
+            _ => *args.get_one::<u32>("frames").unwrap_or(&1),
+        };
         for _ in 0..frames {
+            if let Some(movie) = movie.as_mut() {
+                movie.input(FrameInput::default()).apply(&mut egba);
+            }
             egba.run_frame();
+            capture.frame_done(&egba);
+            egba.clear_audio();
+            if let Some(movie) = movie.as_mut() {
+                movie.frame_done(&egba);
+            }
         }
         if let Some(path) = args.get_one::<PathBuf>("screenshot") {
             egba.dump_screenshot(path).unwrap_or_else(|err| {
@@ -172,14 +566,62 @@ fn main() {
                 std::process::exit(1);
             });
         }
-        egba.save_backup();
+        shutdown(&egba, movie.as_ref(), &mut capture);
+        if movie.as_ref().is_some_and(MovieMode::desynced) {
+            std::process::exit(1);
+        }
         return;
     }
 
-    let mut egba_ui = EgbaUI::new().unwrap_or_else(|err| {
+    let mut debugging = Debugging::default();
+    if let Some(&port) = args.get_one::<u16>("gdb") {
+        debugging.attach_gdb(port).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        });
+    }
+
+    let fast_forward = *args.get_one::<f64>("fast-forward").unwrap_or(&0.0);
+    let slow_motion = *args.get_one::<f64>("slow-motion").unwrap_or(&0.5);
+    if !(fast_forward == 0.0 || fast_forward > 1.0) || !(slow_motion > 0.0 && slow_motion < 1.0) {
+        eprintln!("Error: --fast-forward must be 0 or above 1 and --slow-motion between 0 and 1");
+        std::process::exit(1);
+    }
+    let mut speed = SpeedControl::new(fast_forward, slow_motion);
+
+    let rewind_interval = *args.get_one::<u32>("rewind-interval").unwrap_or(&4);
+    let rewind_buffer = *args.get_one::<usize>("rewind-buffer").unwrap_or(&64);
+    let rewind_buffer = if movie.is_some() { 0 } else { rewind_buffer };
+    egba.enable_rewind(rewind_interval, rewind_buffer << 20);
+
+    let bindings = match args.get_one::<PathBuf>("config") {
+        Some(path) => Bindings::load(path).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }),
+        None => Bindings::default(),
+    };
+    let video = VideoOptions {
+        scale: *args.get_one::<u32>("scale").unwrap_or(&3),
+        fullscreen: args.get_flag("fullscreen"),
+        integer_scaling: args.get_flag("integer-scale"),
+        filter: args
+            .get_one::<String>("filter")
+            .and_then(|name| name.parse().ok())
+            .unwrap_or_default(),
+        frame_blending: args.get_flag("frame-blend"),
+    };
+    let mut egba_ui = EgbaUI::new(bindings, video).unwrap_or_else(|err| {
         eprintln!("Error: {}", err);
         std::process::exit(1);
     });
 
-    run(&mut egba_ui, &mut egba, debug);
+    if debug {
+        debugging.open_tui().unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        });
+    }
+
+    run(&mut egba_ui, &mut egba, &sav_path, &mut speed, movie, capture, debugging);
 }
@@ -0,0 +1,84 @@
+use std::{fs, path::PathBuf};
+
+use egba_core::{
+    cartridge::{gpio::rtc::RtcClock, Cartridge},
+    gba::GBA,
+    movie::{FrameInput, Movie},
+};
+
+pub enum MovieMode {
+    Record { movie: Movie, path: PathBuf },
+    Replay { movie: Movie, frame: usize, desynced: bool },
+}
+
+impl MovieMode {
+    pub fn movie(&self) -> &Movie {
+        match self {
+            MovieMode::Record { movie, .. } | MovieMode::Replay { movie, .. } => movie,
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, MovieMode::Replay { .. })
+    }
+
+    pub fn desynced(&self) -> bool {
+        matches!(self, MovieMode::Replay { desynced: true, .. })
+    }
+
+    pub fn prepare(&mut self, cartridge: &mut Cartridge) {
+        match self {
+            MovieMode::Record { movie, .. } => movie.rtc_state = cartridge.rtc_state(),
+            MovieMode::Replay { movie, .. } => {
+                if let Some(state) = &movie.rtc_state {
+                    cartridge.restore_rtc(state);
+                }
+            }
+        }
+        cartridge.set_rtc_clock(RtcClock::Emulated(self.movie().rtc_time));
+    }
+
+    pub fn input(&mut self, live: FrameInput) -> FrameInput {
+        match self {
+            MovieMode::Record { movie, .. } => {
+                movie.record(live);
+                live
+            }
+            MovieMode::Replay { movie, frame, .. } => movie.input(*frame).unwrap_or(live),
+        }
+    }
+
+    pub fn frame_done(&mut self, gba: &GBA) {
+        match self {
+            MovieMode::Record { movie, .. } => movie.record_check(gba),
+            MovieMode::Replay {
+                movie,
+                frame,
+                desynced,
+            } => {
+                if *frame >= movie.len() {
+                    return;
+                }
+                *frame += 1;
+                if !*desynced {
+                    if let Err(desync) = movie.verify(*frame, gba) {
+                        eprintln!("Warning: replay desynced, {}", desync);
+                        *desynced = true;
+                    }
+                }
+                if *frame == movie.len() {
+                    println!("Replay finished after {} frames.", frame);
+                }
+            }
+        }
+    }
+
+    pub fn finish(&self) {
+        if let MovieMode::Record { movie, path } = self {
+            match fs::write(path, movie.encode()) {
+                Ok(()) => println!("Recorded {} frames to {}.", movie.len(), path.display()),
+                Err(err) => eprintln!("Error: failed to write {}: {}", path.display(), err),
+            }
+        }
+    }
+}
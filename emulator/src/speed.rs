@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use egba_core::gba::{CPU_CLOCK, CYCLES_PER_FRAME};
+
+pub const FRAME_DURATION: Duration =
+    Duration::from_nanos(1_000_000_000 * CYCLES_PER_FRAME as u64 / CPU_CLOCK as u64);
+
+pub struct SpeedControl {
+    fast_forward: f64,
+    slow_motion: f64,
+    holding: bool,
+    turbo: bool,
+    slow: bool,
+    paused: bool,
+    advance: bool,
+}
+
+impl SpeedControl {
+    pub fn new(fast_forward: f64, slow_motion: f64) -> Self {
+        Self {
+            fast_forward,
+            slow_motion,
+            holding: false,
+            turbo: false,
+            slow: false,
+            paused: false,
+            advance: false,
+        }
+    }
+
+    pub fn set_holding(&mut self, holding: bool) {
+        self.holding = holding;
+    }
+
+    pub fn toggle_turbo(&mut self) -> bool {
+        self.turbo = !self.turbo;
+        self.turbo
+    }
+
+    pub fn toggle_slow_motion(&mut self) -> bool {
+        self.slow = !self.slow;
+        self.slow
+    }
+
+    pub fn toggle_pause(&mut self) -> bool {
+        self.paused = !self.paused;
+        self.advance = false;
+        self.paused
+    }
+
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.advance = true;
+        }
+    }
+
+    pub fn should_run(&mut self) -> bool {
+        !self.paused || std::mem::take(&mut self.advance)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> Option<f64> {
+        if self.holding || self.turbo {
+            (self.fast_forward > 0.0).then_some(self.fast_forward)
+        } else if self.slow {
+            Some(self.slow_motion)
+        } else {
+            Some(1.0)
+        }
+    }
+
+    pub fn frame_duration(&self) -> Option<Duration> {
+        self.speed().map(|speed| FRAME_DURATION.div_f64(speed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_forward_overrides_slow_motion() {
+        let mut speed = SpeedControl::new(0.0, 0.5);
+        assert_eq!(speed.frame_duration(), Some(FRAME_DURATION));
+        assert!(speed.toggle_slow_motion());
+        assert!(speed.frame_duration() > Some(FRAME_DURATION * 2 - Duration::from_micros(1)));
+        speed.set_holding(true);
+        assert_eq!(speed.frame_duration(), None, "0x fast-forward is uncapped");
+        speed.set_holding(false);
+        assert_eq!(speed.speed(), Some(0.5));
+
+        let mut speed = SpeedControl::new(3.0, 0.5);
+        assert!(speed.toggle_turbo());
+        assert_eq!(speed.speed(), Some(3.0));
+    }
+
+    #[test]
+    fn frame_advance_only_while_paused() {
+        let mut speed = SpeedControl::new(0.0, 0.5);
+        speed.advance_frame();
+        assert!(speed.should_run());
+        assert!(speed.toggle_pause());
+        assert!(!speed.should_run());
+        speed.advance_frame();
+        assert!(speed.should_run());
+        assert!(!speed.should_run(), "one frame per press");
+    }
+}